@@ -0,0 +1,3 @@
+ALTER TABLE users DROP locked_until;
+ALTER TABLE users DROP failed_attempts;
+ALTER TABLE users DROP last_login_at;
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD last_login_at BIGINT;
+ALTER TABLE users ADD failed_attempts INTEGER NOT NULL DEFAULT 0;
+-- unix timestamp in seconds, login is refused until then
+ALTER TABLE users ADD locked_until BIGINT;
@@ -0,0 +1,5 @@
+ALTER TABLE users ADD totp_secret BYTEA;
+-- totp_secret is pending until the first code is confirmed
+ALTER TABLE users ADD totp_enabled BOOLEAN NOT NULL DEFAULT 'f';
+-- digests of unused recovery codes
+ALTER TABLE users ADD totp_recovery_codes BYTEA[] NOT NULL DEFAULT '{}';
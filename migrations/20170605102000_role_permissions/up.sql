@@ -0,0 +1,16 @@
+CREATE TABLE role_permissions (
+    id SERIAL PRIMARY KEY,
+    role TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    UNIQUE (role, permission)
+);
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('user', 'paste:read'),
+    ('user', 'paste:write'),
+    ('user', 'user:read'),
+    ('user', 'user:write'),
+    ('moderator', 'moderation'),
+    ('admin', 'paste:admin'),
+    ('admin', 'user:admin'),
+    ('admin', 'moderation');
@@ -0,0 +1 @@
+ALTER TABLE users DROP email_verified;
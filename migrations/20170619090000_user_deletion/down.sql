@@ -0,0 +1,4 @@
+ALTER TABLE api_keys
+    DROP CONSTRAINT api_keys_user_id_fkey,
+    ADD CONSTRAINT api_keys_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id);
@@ -0,0 +1,6 @@
+-- api keys are worthless without their user, pastes are handled by the
+-- deletion mode of DELETE /users/<id>
+ALTER TABLE api_keys
+    DROP CONSTRAINT api_keys_user_id_fkey,
+    ADD CONSTRAINT api_keys_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
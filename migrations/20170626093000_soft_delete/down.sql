@@ -0,0 +1,3 @@
+DROP INDEX pastes_deleted_at_idx;
+ALTER TABLE pastes DROP deleted_at;
+ALTER TABLE users DROP deleted_at;
@@ -0,0 +1,4 @@
+-- unix timestamp in seconds, rows are purged after the trash retention
+ALTER TABLE users ADD deleted_at BIGINT;
+ALTER TABLE pastes ADD deleted_at BIGINT;
+CREATE INDEX pastes_deleted_at_idx ON pastes (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,3 @@
+DROP TABLE reports;
+ALTER TABLE pastes DROP hidden_reason;
+ALTER TABLE pastes DROP hidden_by_moderator;
@@ -0,0 +1,19 @@
+-- hidden pastes are kept, but answered with 451 instead of their content
+ALTER TABLE pastes ADD hidden_by_moderator BOOLEAN NOT NULL DEFAULT 'f';
+ALTER TABLE pastes ADD hidden_reason TEXT;
+
+CREATE TABLE reports (
+    id SERIAL PRIMARY KEY,
+    paste_id INTEGER NOT NULL REFERENCES pastes (id) ON DELETE CASCADE,
+    reporter_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    -- open, resolved or dismissed
+    status TEXT NOT NULL DEFAULT 'open',
+    created_at BIGINT NOT NULL,
+    resolved_by INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    resolved_at BIGINT
+);
+
+-- one open report per paste and reporter
+CREATE UNIQUE INDEX reports_open_report_key ON reports (paste_id, reporter_id)
+    WHERE status = 'open';
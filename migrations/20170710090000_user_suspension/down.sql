@@ -0,0 +1,2 @@
+ALTER TABLE users DROP suspended_reason;
+ALTER TABLE users DROP suspended_at;
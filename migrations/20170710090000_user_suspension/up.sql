@@ -0,0 +1,3 @@
+-- suspended users can't log in and their tokens are rejected
+ALTER TABLE users ADD suspended_at BIGINT;
+ALTER TABLE users ADD suspended_reason TEXT;
@@ -0,0 +1,4 @@
+ALTER TABLE pastes DROP CONSTRAINT pastes_encryption_check;
+ALTER TABLE pastes DROP nonce;
+ALTER TABLE pastes DROP cipher;
+ALTER TABLE pastes DROP encrypted;
@@ -0,0 +1,7 @@
+-- zero-knowledge pastes, data is the ciphertext and the key never reaches
+-- the server
+ALTER TABLE pastes ADD encrypted BOOLEAN NOT NULL DEFAULT 'f';
+ALTER TABLE pastes ADD cipher TEXT;
+ALTER TABLE pastes ADD nonce TEXT;
+ALTER TABLE pastes ADD CONSTRAINT pastes_encryption_check
+    CHECK (NOT encrypted OR (cipher IS NOT NULL AND nonce IS NOT NULL));
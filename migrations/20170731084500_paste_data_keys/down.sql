@@ -0,0 +1,2 @@
+DROP INDEX pastes_data_key_id_idx;
+ALTER TABLE pastes DROP data_key_id;
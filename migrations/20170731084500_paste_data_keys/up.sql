@@ -0,0 +1,3 @@
+-- id of the DATA_KEYS key pastes.data is sealed with, NULL for plain text
+ALTER TABLE pastes ADD data_key_id TEXT;
+CREATE INDEX pastes_data_key_id_idx ON pastes (data_key_id);
@@ -0,0 +1,2 @@
+-- "<salt hex>$<pbkdf2 hex>" of the paste password, NULL for no password
+ALTER TABLE pastes ADD password_digest TEXT;
@@ -0,0 +1,3 @@
+DROP TABLE paste_shares;
+DROP TABLE group_members;
+DROP TABLE groups;
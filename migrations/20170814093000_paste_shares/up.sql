@@ -0,0 +1,35 @@
+CREATE TABLE groups (
+    id SERIAL PRIMARY KEY,
+    owner_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX groups_owner_id_name_key ON groups (owner_id, name);
+
+CREATE TABLE group_members (
+    id SERIAL PRIMARY KEY,
+    group_id INTEGER NOT NULL REFERENCES groups (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX group_members_group_id_user_id_key ON group_members (group_id, user_id);
+CREATE INDEX group_members_user_id_idx ON group_members (user_id);
+
+-- access granted on a paste to either a user or a group
+CREATE TABLE paste_shares (
+    id SERIAL PRIMARY KEY,
+    paste_id INTEGER NOT NULL REFERENCES pastes (id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+    group_id INTEGER REFERENCES groups (id) ON DELETE CASCADE,
+    -- read or edit
+    access TEXT NOT NULL,
+    created_by INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    created_at BIGINT NOT NULL,
+    CHECK ((user_id IS NULL) <> (group_id IS NULL))
+);
+CREATE UNIQUE INDEX paste_shares_paste_id_user_id_key ON paste_shares (paste_id, user_id)
+    WHERE user_id IS NOT NULL;
+CREATE UNIQUE INDEX paste_shares_paste_id_group_id_key ON paste_shares (paste_id, group_id)
+    WHERE group_id IS NOT NULL;
+CREATE INDEX paste_shares_user_id_idx ON paste_shares (user_id);
+CREATE INDEX paste_shares_group_id_idx ON paste_shares (group_id);
@@ -0,0 +1,5 @@
+DROP INDEX pastes_org_id_idx;
+ALTER TABLE pastes DROP org_id;
+DROP TABLE org_invitations;
+DROP TABLE org_members;
+DROP TABLE orgs;
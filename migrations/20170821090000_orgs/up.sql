@@ -0,0 +1,33 @@
+CREATE TABLE orgs (
+    id SERIAL PRIMARY KEY,
+    slug VARCHAR(32) NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE org_members (
+    id SERIAL PRIMARY KEY,
+    org_id INTEGER NOT NULL REFERENCES orgs (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- owner, maintainer or member
+    role TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX org_members_org_id_user_id_key ON org_members (org_id, user_id);
+CREATE INDEX org_members_user_id_idx ON org_members (user_id);
+
+-- pending until the invited user accepts or declines
+CREATE TABLE org_invitations (
+    id SERIAL PRIMARY KEY,
+    org_id INTEGER NOT NULL REFERENCES orgs (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    invited_by INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX org_invitations_org_id_user_id_key ON org_invitations (org_id, user_id);
+
+-- pastes.user_id stays the author, org_id is set when an org owns the paste.
+-- Orgs are only deleted once their pastes are, see services::org::delete_org
+ALTER TABLE pastes ADD org_id INTEGER REFERENCES orgs (id) ON DELETE RESTRICT;
+CREATE INDEX pastes_org_id_idx ON pastes (org_id);
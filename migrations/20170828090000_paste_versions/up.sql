@@ -0,0 +1,2 @@
+-- bumped on every update, updates have to name the version they're based on
+ALTER TABLE pastes ADD version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,34 @@
+CREATE TABLE webhooks (
+    id SERIAL PRIMARY KEY,
+    -- who registered the hook
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- pastes of the org instead of the personal ones of user_id
+    org_id INTEGER REFERENCES orgs (id) ON DELETE CASCADE,
+    -- registered by an admin, gets the events of everyone
+    global BOOLEAN NOT NULL DEFAULT FALSE,
+    url TEXT NOT NULL,
+    -- HMAC-SHA256 key of the payload signatures
+    secret TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX webhooks_user_id_idx ON webhooks (user_id);
+CREATE INDEX webhooks_org_id_idx ON webhooks (org_id);
+
+CREATE TABLE webhook_deliveries (
+    id SERIAL PRIMARY KEY,
+    webhook_id INTEGER NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    -- pending, delivered, or failed once out of attempts
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at BIGINT NOT NULL,
+    response_code INTEGER,
+    last_error TEXT,
+    created_at BIGINT NOT NULL,
+    delivered_at BIGINT
+);
+CREATE INDEX webhook_deliveries_webhook_id_idx ON webhook_deliveries (webhook_id);
+CREATE INDEX webhook_deliveries_next_attempt_at_idx ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'pending';
@@ -0,0 +1,19 @@
+-- review comments on pastes, purging a paste or its author takes them along
+CREATE TABLE comments (
+    id SERIAL PRIMARY KEY,
+    paste_id INTEGER NOT NULL REFERENCES pastes (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    body TEXT NOT NULL,
+    -- inclusive range of 1-based lines, NULL for comments on the whole paste
+    line_start INTEGER,
+    line_end INTEGER,
+    -- pastes.version the comment refers to, always set for line comments
+    version INTEGER,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT,
+    CHECK ((line_start IS NULL) = (line_end IS NULL)),
+    CHECK (line_start IS NULL OR
+           (line_start >= 1 AND line_end >= line_start AND version IS NOT NULL))
+);
+CREATE INDEX comments_paste_id_idx ON comments (paste_id);
+CREATE INDEX comments_user_id_idx ON comments (user_id);
@@ -0,0 +1,2 @@
+ALTER TABLE pastes DROP views;
+DROP TABLE stars;
@@ -0,0 +1,11 @@
+CREATE TABLE stars (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    paste_id INTEGER NOT NULL REFERENCES pastes (id) ON DELETE CASCADE,
+    created_at BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX stars_user_id_paste_id_key ON stars (user_id, paste_id);
+CREATE INDEX stars_paste_id_idx ON stars (paste_id);
+
+-- flushed from memory every now and then, see helpers::views
+ALTER TABLE pastes ADD views BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+ALTER TABLE users DROP created_at;
+ALTER TABLE users DROP avatar_url;
+ALTER TABLE users DROP bio;
+ALTER TABLE users DROP display_name;
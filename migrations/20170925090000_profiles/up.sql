@@ -0,0 +1,5 @@
+ALTER TABLE users ADD display_name TEXT;
+ALTER TABLE users ADD bio TEXT;
+ALTER TABLE users ADD avatar_url TEXT;
+-- join date, unknown for users from before it was recorded
+ALTER TABLE users ADD created_at BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE users DROP totp_last_step;
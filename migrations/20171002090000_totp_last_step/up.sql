@@ -0,0 +1,2 @@
+-- time step of the last accepted totp code, a code is only accepted once
+ALTER TABLE users ADD totp_last_step BIGINT;
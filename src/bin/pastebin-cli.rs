@@ -0,0 +1,214 @@
+//! Reference client for encrypted pastes. The paste is encrypted before it
+//! leaves the machine and the key only ever lives in the url fragment, which
+//! browsers and this client never send to the server.
+//!
+//!     PASTEBIN_TOKEN=<jwt> pastebin-cli put localhost:8000 < notes.txt
+//!     pastebin-cli get 'http://localhost:8000/pastes/42#<key>'
+//!
+//! Plain http only, put a tls terminating proxy in front for anything real.
+extern crate ring;
+extern crate serde_json;
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::process;
+
+use ring::aead;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use serde_json::Value;
+
+const CIPHER: &str = "aes-256-gcm";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const BASE64URL_ALPHABET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn base64url_encode(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 6 {
+            bits -= 6;
+            encoded.push(BASE64URL_ALPHABET[((buffer >> bits) & 0x3f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE64URL_ALPHABET[((buffer << (6 - bits)) & 0x3f) as usize] as char);
+    }
+    encoded
+}
+
+fn base64url_decode(encoded: &str) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.bytes() {
+        let value = BASE64URL_ALPHABET
+            .iter()
+            .position(|&b| b == c)
+            .ok_or(format!("invalid base64url character {}", c as char))?;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(decoded)
+}
+
+fn encrypt(plaintext: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>, String> {
+    let algorithm = &aead::AES_256_GCM;
+    let key = aead::SealingKey::new(algorithm, key).map_err(|_| "invalid key")?;
+    let mut in_out = plaintext.to_vec();
+    in_out.extend(vec![0u8; algorithm.tag_len()]);
+    let len = aead::seal_in_place(&key, nonce, &mut in_out, algorithm.tag_len(), &[])
+        .map_err(|_| "fail to encrypt")?;
+    in_out.truncate(len);
+    Ok(in_out)
+}
+
+fn decrypt(ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>, String> {
+    let key = aead::OpeningKey::new(&aead::AES_256_GCM, key).map_err(|_| "invalid key")?;
+    let mut in_out = ciphertext.to_vec();
+    aead::open_in_place(&key, nonce, 0, &mut in_out, &[])
+        .map(|plaintext| plaintext.to_vec())
+        .map_err(|_| "fail to decrypt, wrong key or tampered paste".to_string())
+}
+
+/// Status and body of a plain http/1.0 request.
+fn request(method: &str,
+           host: &str,
+           path: &str,
+           token: Option<&str>,
+           form: Option<&str>)
+           -> Result<(u16, String), String> {
+    let mut stream = TcpStream::connect(host).map_err(|err| err.to_string())?;
+    let mut req = format!("{} {} HTTP/1.0\r\nHost: {}\r\n", method, path, host);
+    if let Some(token) = token {
+        req.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    if let Some(form) = form {
+        req.push_str(&format!("Content-Type: application/x-www-form-urlencoded\r\n\
+                               Content-Length: {}\r\n\r\n{}",
+                              form.len(),
+                              form));
+    } else {
+        req.push_str("\r\n");
+    }
+    stream
+        .write_all(req.as_bytes())
+        .map_err(|err| err.to_string())?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|err| err.to_string())?;
+    let mut parts = response.splitn(2, "\r\n\r\n");
+    let status = parts
+        .next()
+        .and_then(|head| head.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or("invalid http response")?;
+    Ok((status, parts.next().unwrap_or("").to_string()))
+}
+
+fn json_body(status: u16, body: &str) -> Result<Value, String> {
+    let value: Value = serde_json::from_str(body).map_err(|err| err.to_string())?;
+    if status != 200 {
+        return Err(format!("server error {}: {}", status, value["msg"]));
+    }
+    Ok(value)
+}
+
+fn put(host: &str) -> Result<String, String> {
+    let token = env::var("PASTEBIN_TOKEN").map_err(|_| "PASTEBIN_TOKEN must be set")?;
+    let mut plaintext = Vec::new();
+    io::stdin()
+        .read_to_end(&mut plaintext)
+        .map_err(|err| err.to_string())?;
+
+    let rng = SystemRandom::new();
+    let mut key = [0u8; KEY_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut key).map_err(|_| "fail to generate key")?;
+    rng.fill(&mut nonce).map_err(|_| "fail to generate nonce")?;
+    let ciphertext = encrypt(&plaintext, &key, &nonce)?;
+
+    let (status, body) = request("GET", host, "/users/me", Some(&token), None)?;
+    let user_id = json_body(status, &body)?["id"].clone();
+    let form = format!("user_id={}&data={}&encrypted=true&cipher={}&nonce={}",
+                       user_id,
+                       base64url_encode(&ciphertext),
+                       CIPHER,
+                       base64url_encode(&nonce));
+    let (status, body) = request("POST", host, "/pastes", Some(&token), Some(&form))?;
+    let paste = json_body(status, &body)?;
+    Ok(format!("http://{}/pastes/{}#{}", host, paste["id"], base64url_encode(&key)))
+}
+
+fn get(url: &str) -> Result<String, String> {
+    let url = url.trim_left_matches("http://");
+    let mut parts = url.splitn(2, '#');
+    let location = parts.next().unwrap();
+    let key = parts
+        .next()
+        .ok_or("the key is missing from the url fragment")
+        .and_then(|key| base64url_decode(key).map_err(|_| "invalid key"))?;
+    let slash = location.find('/').ok_or("invalid paste url")?;
+    let (host, path) = location.split_at(slash);
+
+    let (status, body) = request("GET", host, path, None, None)?;
+    let paste = json_body(status, &body)?;
+    if paste["encrypted"] != Value::Bool(true) || paste["cipher"] != Value::from(CIPHER) {
+        return Err("not an encrypted paste".to_string());
+    }
+    let ciphertext = base64url_decode(paste["data"].as_str().unwrap_or(""))?;
+    let nonce = base64url_decode(paste["nonce"].as_str().unwrap_or(""))?;
+    let plaintext = decrypt(&ciphertext, &key, &nonce)?;
+    String::from_utf8(plaintext).map_err(|_| "paste is not utf-8 text".to_string())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let result = match (args.get(1).map(|arg| arg.as_str()), args.get(2)) {
+        (Some("put"), Some(host)) => put(host),
+        (Some("get"), Some(url)) => get(url),
+        _ => Err("usage: pastebin-cli put <host:port> | get <url>".to_string()),
+    };
+    match result {
+        Ok(output) => println!("{}", output),
+        Err(err) => {
+            writeln!(io::stderr(), "{}", err).unwrap();
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64url() {
+        assert_eq!(base64url_encode(b"any carnal pleas"), "YW55IGNhcm5hbCBwbGVhcw");
+        assert_eq!(base64url_encode(&[0xfb, 0xff]), "-_8");
+        assert_eq!(base64url_decode("YW55IGNhcm5hbCBwbGVhcw").unwrap(), b"any carnal pleas");
+        assert!(base64url_decode("a+b=").is_err());
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let key = [7u8; KEY_LEN];
+        let nonce = [1u8; NONCE_LEN];
+        let ciphertext = encrypt(b"secret", &key, &nonce).unwrap();
+        assert_eq!(ciphertext.len(), 6 + aead::AES_256_GCM.tag_len());
+        assert_eq!(decrypt(&ciphertext, &key, &nonce).unwrap(), b"secret");
+        assert!(decrypt(&ciphertext, &[8u8; KEY_LEN], &nonce).is_err());
+    }
+}
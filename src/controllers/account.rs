@@ -0,0 +1,146 @@
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::user as user_serv;
+use services::user::User;
+use services::auth;
+use services::mail;
+use helpers::mailer::MailerState;
+use helpers::guard::{UserToken, User as UserPerm};
+use helpers::validation::Validator;
+use helpers::error;
+use self::error::{Error, ErrorKind};
+
+pub fn send_verification(user: &User, mailer: &MailerState) -> Result<(), Error> {
+    auth::mail_token(user, auth::VERIFY_EMAIL)
+        .or(Err(error::internal_server_error("fail to generate verification token")))
+        .and_then(|token| {
+                      mailer
+                          .send(&mail::verification_mail(user, &token))
+                          .or(Err(error::internal_server_error("fail to send mail")))
+                  })
+}
+
+#[post("/users/me/verification")]
+pub fn resend_verification(token: Result<UserToken<UserPerm>, Error>,
+                           mailer: State<MailerState>,
+                           db_pool: State<DBPool>)
+                           -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write")
+            .and_then(|user| get_conn!(db_pool).and_then(|conn| Ok((user, conn))))
+            .and_then(|(user, conn)| call_serv!(user_serv::get_user_by_id(user.user_id, &conn)))
+            .and_then(|user| {
+                if user.email_verified {
+                    return Err(error::badrequest("email already verified"));
+                }
+                send_verification(&user, &mailer)
+            })
+    })
+}
+
+#[derive(FromForm)]
+pub struct TokenPayload {
+    pub token: String,
+}
+
+#[post("/users/verify", data = "<payload>")]
+pub fn verify_email(payload: Form<TokenPayload>, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
+    let token_error = error::badrequest("invalid or expired token");
+    let payload = payload.into_inner();
+
+    call_ctrl!(|| {
+        auth::decode_mail_token(&payload.token, auth::VERIFY_EMAIL)
+            .ok_or(token_error.clone())
+            .and_then(|user_id| get_conn!(db_pool).and_then(|conn| Ok((user_id, conn))))
+            .and_then(|(user_id, conn)| {
+                let user = call_serv!(user_serv::get_user_by_id(user_id, &conn))?;
+                if !auth::verify_mail_token(&payload.token, auth::VERIFY_EMAIL, &user) {
+                    return Err(token_error.clone());
+                }
+                call_serv!(user_serv::verify_email(user.id, &conn))
+            })
+    })
+}
+
+#[derive(FromForm)]
+pub struct ForgotPayload {
+    pub email: String,
+}
+
+#[post("/password/forgot", data = "<payload>")]
+pub fn forgot_password(payload: Form<ForgotPayload>,
+                       mailer: State<MailerState>,
+                       db_pool: State<DBPool>)
+                       -> Custom<JSON<Value>> {
+    let payload = payload.into_inner();
+
+    call_ctrl!(|| {
+        get_conn!(db_pool)
+            .and_then(|conn| match user_serv::get_user_by_email(&payload.email, &conn) {
+                          Ok(user) => Ok(Some(user)),
+                          // same answer for unknown emails, don't reveal who has an account
+                          Err(_) => Ok(None),
+                      })
+            .and_then(|user| {
+                // failures are only logged, an error would tell the email is registered
+                if let Some(user) = user {
+                    let sent = auth::mail_token(&user, auth::RESET_PASSWORD)
+                        .map_err(|err| err.to_string())
+                        .and_then(|token| {
+                            mailer
+                                .send(&mail::password_reset_mail(&user, &token))
+                                .map_err(|err| err.to_string())
+                        });
+                    if let Err(err) = sent {
+                        eprintln!("Fail to send password reset mail to user {}: {}", user.id, err);
+                    }
+                }
+                Ok("password reset mail sent if the email is registered")
+            })
+    })
+}
+
+#[derive(FromForm)]
+pub struct ResetPayload {
+    pub token: String,
+    pub password: String,
+    pub confirm_password: String,
+}
+
+#[post("/password/reset", data = "<payload>")]
+pub fn reset_password(payload: Form<ResetPayload>, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
+    let token_error = error::badrequest("invalid or expired token");
+    let payload = payload.into_inner();
+
+    call_ctrl!(|| {
+        if payload.password != payload.confirm_password {
+            return Err(error::badrequest("password mismatch")
+                           .with_kind(ErrorKind::PasswordMismatch));
+        }
+        auth::decode_mail_token(&payload.token, auth::RESET_PASSWORD)
+            .ok_or(token_error.clone())
+            .and_then(|user_id| get_conn!(db_pool).and_then(|conn| Ok((user_id, conn))))
+            .and_then(|(user_id, conn)| {
+                let user = call_serv!(user_serv::get_user_by_id(user_id, &conn))?;
+                if !auth::verify_mail_token(&payload.token, auth::RESET_PASSWORD, &user) {
+                    return Err(token_error.clone());
+                }
+                Validator::new()
+                    .password("password", &payload.password, &user.username)
+                    .finish()?;
+
+                let updated_user = user_serv::UpdatedUser {
+                    username: None,
+                    email: None,
+                    password: Some(&payload.password),
+                };
+                call_serv!(user_serv::update_user(user.id, &updated_user, &conn))
+            })
+    })
+}
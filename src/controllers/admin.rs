@@ -0,0 +1,197 @@
+use std::net::SocketAddr;
+
+use diesel::Connection;
+use diesel::pg::PgConnection;
+
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::user as user_serv;
+use services::auth;
+use services::permission as permission_serv;
+use services::audit::{self, AuditFilter, Event};
+
+use helpers::guard::{Admin, UserToken};
+use helpers::validation::{Validator, REASON_MAX_LEN};
+use helpers::error;
+use self::error::Error;
+
+/// Run `change` on user `id` and record it with what changed of the user,
+/// both or neither happen.
+fn audited_change<F>(id: i32,
+                     action: &str,
+                     admin: &UserToken<Admin>,
+                     remote: Option<SocketAddr>,
+                     conn: &PgConnection,
+                     change: F)
+                     -> Result<user_serv::User, Error>
+    where F: FnOnce() -> Result<user_serv::User, Error>
+{
+    conn.transaction(|| {
+        let before = call_serv!(user_serv::get_user_by_id(id, conn))?;
+        let user = change()?;
+        let event = Event::new(action, audit::TARGET_USER, Some(id))
+            .actor(admin.user_id)
+            .remote(remote)
+            .changes(&json!(before), &json!(user));
+        call_serv!(audit::record(event, conn))?;
+        Ok(user)
+    })
+}
+
+#[derive(FromForm)]
+pub struct RolePayload {
+    pub role: String,
+}
+
+#[post("/users/<id>/roles", data = "<payload>")]
+pub fn grant_role(id: i32,
+                  payload: Form<RolePayload>,
+                  remote: Option<SocketAddr>,
+                  token: Result<UserToken<Admin>, Error>,
+                  db_pool: State<DBPool>)
+                  -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        let payload = payload.into_inner();
+        token.and_then(|admin| {
+            get_conn!(db_pool).and_then(|conn| {
+                let roles = call_serv!(permission_serv::get_roles(&conn))?;
+                if !roles.contains(&payload.role) {
+                    return Err(error::badrequest(&format!("invalid role {}", payload.role)));
+                }
+                audited_change(id, audit::ROLE_GRANT, &admin, remote, &conn, || {
+                    call_serv!(user_serv::grant_role(id, &payload.role, &conn))
+                })
+            })
+        })
+    })
+}
+
+#[delete("/users/<id>/roles/<role>")]
+pub fn revoke_role(id: i32,
+                   role: String,
+                   remote: Option<SocketAddr>,
+                   token: Result<UserToken<Admin>, Error>,
+                   db_pool: State<DBPool>)
+                   -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token.and_then(|admin| {
+            // somebody has to stay admin
+            if admin.match_user_id(id) && role == "admin" {
+                return Err(error::badrequest("cannot revoke own admin role"));
+            }
+            get_conn!(db_pool).and_then(|conn| {
+                audited_change(id, audit::ROLE_REVOKE, &admin, remote, &conn, || {
+                    call_serv!(user_serv::revoke_role(id, &role, &conn))
+                })
+            })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct SuspendPayload {
+    pub reason: String,
+}
+
+#[post("/users/<id>/suspend", data = "<payload>")]
+pub fn suspend_user(id: i32,
+                    payload: Form<SuspendPayload>,
+                    remote: Option<SocketAddr>,
+                    token: Result<UserToken<Admin>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token.and_then(|admin| {
+            if admin.match_user_id(id) {
+                return Err(error::badrequest("cannot suspend yourself"));
+            }
+            let payload = payload.into_inner();
+            Validator::new()
+                .text("reason", &payload.reason, REASON_MAX_LEN)
+                .finish()?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                audited_change(id, audit::USER_SUSPEND, &admin, remote, &conn, || {
+                    call_serv!(user_serv::suspend_user(id, &payload.reason, &conn))
+                })
+            })
+        })
+    })
+}
+
+#[post("/users/<id>/unsuspend")]
+pub fn unsuspend_user(id: i32,
+                      remote: Option<SocketAddr>,
+                      token: Result<UserToken<Admin>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token.and_then(|admin| {
+            get_conn!(db_pool).and_then(|conn| {
+                audited_change(id, audit::USER_UNSUSPEND, &admin, remote, &conn, || {
+                    call_serv!(user_serv::unsuspend_user(id, &conn))
+                })
+            })
+        })
+    })
+}
+
+/// Read only token of another user for support, see
+/// `services::auth::impersonate`.
+#[post("/users/<id>/impersonate")]
+pub fn impersonate_user(id: i32,
+                        remote: Option<SocketAddr>,
+                        token: Result<UserToken<Admin>, Error>,
+                        db_pool: State<DBPool>)
+                        -> Custom<JSON<Value>> {
+    let jwt_error = error::internal_server_error("fail to generate jwt token");
+
+    call_ctrl!(|| {
+        token.and_then(|admin| {
+            get_conn!(db_pool).and_then(|conn| {
+                let user = call_serv!(user_serv::get_user_by_id(id, &conn))?;
+                // no detours to the permissions of another admin
+                if user.roles.contains(&"admin".to_owned()) {
+                    return Err(error::forbidden("cannot impersonate admins"));
+                }
+                let token = auth::impersonate(&user, admin.user_id)
+                    .or_else(|_| Err(jwt_error.clone()))?;
+                let event = Event::new(audit::USER_IMPERSONATE, audit::TARGET_USER, Some(id))
+                    .actor(admin.user_id)
+                    .remote(remote);
+                call_serv!(audit::record(event, &conn))?;
+                Ok(format!("token: {}", token))
+            })
+        })
+    })
+}
+
+#[get("/admin/audit?<filter>")]
+pub fn get_audit_events_with_filter(filter: AuditFilter,
+                                    token: Result<UserToken<Admin>, Error>,
+                                    db_pool: State<DBPool>)
+                                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| get_audit_events_by(&filter, token, db_pool))
+}
+
+#[get("/admin/audit", rank = 2)]
+pub fn get_audit_events(token: Result<UserToken<Admin>, Error>,
+                        db_pool: State<DBPool>)
+                        -> Custom<JSON<Value>> {
+    call_ctrl!(|| get_audit_events_by(&AuditFilter::default(), token, db_pool))
+}
+
+fn get_audit_events_by(filter: &AuditFilter,
+                       token: Result<UserToken<Admin>, Error>,
+                       db_pool: State<DBPool>)
+                       -> Result<Vec<audit::AuditEvent>, Error> {
+    token
+        .and_then(|_| get_conn!(db_pool))
+        .and_then(|conn| call_serv!(audit::get_events(filter, &conn)))
+}
@@ -0,0 +1,74 @@
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::api_key as api_key_serv;
+
+use helpers::guard::{User, UserToken};
+use helpers::error;
+use self::error::Error;
+
+#[derive(FromForm)]
+pub struct ApiKeyPayload {
+    pub name: String,
+    // comma separated, e.g. "paste:read,paste:write"
+    pub scopes: String,
+}
+
+#[post("/users/me/api-keys", data = "<payload>")]
+pub fn create_api_key(payload: Form<ApiKeyPayload>,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            let payload = payload.into_inner();
+            let scopes = payload
+                .scopes
+                .split(',')
+                .map(|scope| scope.trim().to_string())
+                .filter(|scope| !scope.is_empty())
+                .collect::<Vec<String>>();
+            if let Some(scope) = scopes
+                   .iter()
+                   .find(|scope| !api_key_serv::SCOPES.contains(&scope.as_ref())) {
+                return Err(error::badrequest(&format!("invalid scope {}", scope)));
+            }
+
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(api_key_serv::create_api_key(user.user_id, &payload.name, scopes, &conn))
+            })
+        })
+    })
+}
+
+#[get("/users/me/api-keys")]
+pub fn get_api_keys(token: Result<UserToken<User>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:read")
+            .and_then(|user| get_conn!(db_pool).and_then(|conn| Ok((user, conn))))
+            .and_then(|(user, conn)| {
+                          call_serv!(api_key_serv::get_api_keys_by_user_id(user.user_id, &conn))
+                      })
+    })
+}
+
+#[delete("/users/me/api-keys/<id>")]
+pub fn revoke_api_key(id: i32,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write")
+            .and_then(|user| get_conn!(db_pool).and_then(|conn| Ok((user, conn))))
+            .and_then(|(user, conn)| {
+                          call_serv!(api_key_serv::revoke_api_key(id, user.user_id, &conn))
+                      })
+    })
+}
@@ -1,3 +1,9 @@
+use std::net::SocketAddr;
+
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+
 use rocket::State;
 use rocket::request::Form;
 use rocket::response::status::Custom;
@@ -7,9 +13,38 @@ use rocket_contrib::{JSON, Value};
 use DBPool;
 
 use services::user as user_serv;
+use services::mfa as mfa_serv;
 use services::auth;
+use services::audit::{self, Event};
 use helpers::error;
-use helpers::error::Error;
+use helpers::error::{Error, ErrorKind};
+use helpers::throttle::LoginThrottle;
+
+fn record_failure(user_id: Option<i32>,
+                  username: &str,
+                  reason: &str,
+                  remote: Option<SocketAddr>,
+                  conn: &PgConnection)
+                  -> Result<(), Error> {
+    let event = Event::new(audit::LOGIN_FAILED, audit::TARGET_USER, user_id)
+        .remote(remote)
+        .after(json!({"username": username, "reason": reason}));
+    call_serv!(audit::record(event, conn)).and(Ok(()))
+}
+
+fn record_success(user_id: i32,
+                  remote: Option<SocketAddr>,
+                  conn: &PgConnection)
+                  -> Result<user_serv::User, Error> {
+    conn.transaction(|| {
+        let user = call_serv!(user_serv::record_login_success(user_id, conn))?;
+        let event = Event::new(audit::LOGIN, audit::TARGET_USER, Some(user.id))
+            .actor(user.id)
+            .remote(remote);
+        call_serv!(audit::record(event, conn))?;
+        Ok(user)
+    })
+}
 
 #[derive(FromForm)]
 pub struct LoginPayload {
@@ -18,25 +53,124 @@ pub struct LoginPayload {
 }
 
 #[post("/login", data = "<payload>")]
-pub fn login(payload: Form<LoginPayload>, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
-    let user_error = error::badrequest("wrong username or password");
+pub fn login(payload: Form<LoginPayload>,
+             remote: Option<SocketAddr>,
+             login_throttle: State<LoginThrottle>,
+             db_pool: State<DBPool>)
+             -> Custom<JSON<Value>> {
+    let user_error =
+        error::badrequest("wrong username or password").with_kind(ErrorKind::InvalidCredentials);
     let jwt_error = error::internal_server_error("fail to generate jwt token");
     let payload = payload.into_inner();
+    let remote_ip = remote.map(|addr| addr.ip().to_string());
 
     call_ctrl!(|| {
-        get_conn!(db_pool)
-            .and_then(|conn| call_serv!(user_serv::get_user_by_name(&payload.username, &conn)))
-            .or_else(|err| {
-                if err.code == Status::NotFound.code {
-                    return Err(user_error.clone());
+        remote_ip
+            .as_ref()
+            .map_or(Ok(()), |ip| login_throttle.check(ip))
+            .and_then(|_| get_conn!(db_pool))
+            .and_then(|conn| {
+                let username = &payload.username;
+                match user_serv::get_user_by_name(username, &conn) {
+                    Ok(user) => Ok((user, conn)),
+                    Err(DieselError::NotFound) => {
+                        if let Some(ref ip) = remote_ip {
+                            login_throttle.fail(ip);
+                        }
+                        record_failure(None, username, "unknown user", remote, &conn)?;
+                        Err(user_error.clone())
+                    }
+                    Err(err) => Err(Error::from(err)),
                 }
-                Err(err)
             })
-            .and_then(|user| {
+            .and_then(|(user, conn)| {
+                // answered like unknown usernames, a lockout would tell the
+                // account exists
+                if user.is_locked() {
+                    if let Some(ref ip) = remote_ip {
+                        login_throttle.fail(ip);
+                    }
+                    record_failure(Some(user.id), &user.username, "account locked", remote, &conn)?;
+                    return Err(user_error.clone());
+                }
                 if !user.verify_password(&payload.password) {
+                    if let Some(ref ip) = remote_ip {
+                        login_throttle.fail(ip);
+                    }
+                    call_serv!(user_serv::record_login_failure(user.id, &conn))?;
+                    record_failure(Some(user.id), &user.username, "wrong password", remote, &conn)?;
                     return Err(user_error.clone());
                 }
-                Ok(user)
+
+                // only told after the right password
+                if user.is_suspended() {
+                    return Err(error::forbidden("account suspended")
+                                   .with_kind(ErrorKind::AccountSuspended)
+                                   .with_details(json!({"reason": user.suspended_reason})));
+                }
+                if user.totp_enabled {
+                    // second step at /login/mfa
+                    return auth::mfa_pending(&user)
+                               .or_else(|_| Err(jwt_error.clone()))
+                               .and_then(|token| Ok(format!("mfa_token: {}", token)));
+                }
+
+                record_success(user.id, remote, &conn).and_then(|user| {
+                    auth::login(&user)
+                        .or_else(|_| Err(jwt_error.clone()))
+                        .and_then(|token| Ok(format!("token: {}", token)))
+                })
+            })
+    })
+}
+
+#[derive(FromForm)]
+pub struct MfaPayload {
+    mfa_token: String,
+    code: String,
+}
+
+#[post("/login/mfa", data = "<payload>")]
+pub fn login_mfa(payload: Form<MfaPayload>,
+                 remote: Option<SocketAddr>,
+                 login_throttle: State<LoginThrottle>,
+                 db_pool: State<DBPool>)
+                 -> Custom<JSON<Value>> {
+    let code_error = error::badrequest("wrong 2fa code").with_kind(ErrorKind::InvalidCredentials);
+    let jwt_error = error::internal_server_error("fail to generate jwt token");
+    let payload = payload.into_inner();
+    let remote_ip = remote.map(|addr| addr.ip().to_string());
+
+    call_ctrl!(|| {
+        remote_ip
+            .as_ref()
+            .map_or(Ok(()), |ip| login_throttle.check(ip))
+            .and_then(|_| {
+                auth::verify_mfa_pending(&payload.mfa_token)
+                    .ok_or(error::unauthorized("invalid mfa token"))
+            })
+            .and_then(|user_id| get_conn!(db_pool).and_then(|conn| Ok((user_id, conn))))
+            .and_then(|(user_id, conn)| {
+                call_serv!(user_serv::get_user_by_id(user_id, &conn))
+                    .and_then(|user| Ok((user, conn)))
+            })
+            .and_then(|(user, conn)| {
+                if user.is_locked() {
+                    if let Some(ref ip) = remote_ip {
+                        login_throttle.fail(ip);
+                    }
+                    return Err(code_error.clone());
+                }
+                if !call_serv!(mfa_serv::verify(&user, &payload.code, &conn))? {
+                    if let Some(ref ip) = remote_ip {
+                        login_throttle.fail(ip);
+                    }
+                    call_serv!(user_serv::record_login_failure(user.id, &conn))?;
+                    record_failure(Some(user.id), &user.username, "wrong 2fa code", remote, &conn)?;
+                    return Err(code_error.clone());
+                }
+
+                record_success(user.id, remote, &conn)
             })
             .and_then(|user| {
                 auth::login(&user)
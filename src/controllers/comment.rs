@@ -0,0 +1,202 @@
+use std::net::SocketAddr;
+
+use diesel::pg::PgConnection;
+
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use time;
+
+use DBPool;
+
+use controllers::paste::read_paste;
+use services::comment as comment_serv;
+use services::paste as paste_serv;
+use services::paste::Owner;
+use services::org as org_serv;
+use models::comment::{Comment, NewComment};
+use models::paste::Paste;
+
+use helpers::guard::{User, UserToken, PastePassword};
+use helpers::throttle::LoginThrottle;
+use helpers::validation::{self, Validator};
+use helpers::error;
+use self::error::{Error, ErrorKind};
+
+fn permission_denied() -> Error {
+    error::forbidden("permission denied").with_kind(ErrorKind::PermissionDenied)
+}
+
+/// Besides their author, comments are removed by the owner of the paste,
+/// owners and maintainers of the org owning it and paste admins.
+fn can_remove(comment: &Comment,
+              paste: &Paste,
+              token: &UserToken<User>,
+              conn: &PgConnection)
+              -> Result<bool, Error> {
+    if token.match_user_id(comment.user_id) || token.has_permission("paste:admin") {
+        return Ok(true);
+    }
+    match paste_serv::owner(paste) {
+        Owner::User(owner_id) => Ok(token.match_user_id(owner_id)),
+        Owner::Org(org_id) => {
+            call_serv!(org_serv::get_role(org_id, token.user_id, conn))
+                .map(|role| role.map_or(false, |role| org_serv::can_manage(&role)))
+        }
+    }
+}
+
+#[derive(FromForm)]
+pub struct CommentPayload {
+    pub body: String,
+    // inclusive range of 1-based lines, left out for the whole paste
+    pub line_start: Option<i32>,
+    pub line_end: Option<i32>,
+    // the version the lines refer to, the current one by default
+    pub version: Option<i32>,
+}
+
+/// Line range and version of a comment on `paste`. Lines are only checked
+/// against the paste for the current version of plain text pastes, the
+/// server neither keeps older versions nor can read encrypted ones.
+fn anchor(payload: &CommentPayload,
+          paste: &Paste)
+          -> Result<(Option<i32>, Option<i32>, Option<i32>), Error> {
+    let mut validator = Validator::new();
+    validator.text("body", &payload.body, validation::COMMENT_MAX_LEN);
+    let version = match (payload.line_start, payload.version) {
+        (Some(_), None) => Some(paste.version),
+        _ => payload.version,
+    };
+    if let Some(version) = version {
+        validator.check("version",
+                        version >= 1 && version <= paste.version,
+                        "no such version of the paste");
+    }
+    match (payload.line_start, payload.line_end) {
+        (Some(start), Some(end)) => {
+            validator
+                .check("line_start", start >= 1, "must be at least 1")
+                .check("line_end", end >= start, "must not be before line_start");
+            if version == Some(paste.version) && !paste.encrypted {
+                validator.check("line_end",
+                                end as usize <= paste.data.lines().count(),
+                                "must not be after the last line");
+            }
+        }
+        (None, None) => {}
+        _ => return Err(error::badrequest("line_start and line_end go together")),
+    }
+    validator.finish()?;
+    Ok((payload.line_start, payload.line_end, version))
+}
+
+/// Comments are for whoever may read the paste.
+#[get("/pastes/<id>/comments")]
+pub fn get_comments(id: i32,
+                    password: PastePassword,
+                    remote: Option<SocketAddr>,
+                    token: Result<UserToken<User>, Error>,
+                    login_throttle: State<LoginThrottle>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        get_conn!(db_pool).and_then(|conn| {
+            read_paste(id, password.0, token.as_ref().ok(), remote, &login_throttle, &conn)?;
+            call_serv!(comment_serv::get_comments_by_paste_id(id, &conn))
+        })
+    })
+}
+
+#[post("/pastes/<id>/comments", data = "<payload>")]
+pub fn create_comment(id: i32,
+                      payload: Form<CommentPayload>,
+                      password: PastePassword,
+                      remote: Option<SocketAddr>,
+                      token: Result<UserToken<User>, Error>,
+                      login_throttle: State<LoginThrottle>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            let payload = payload.into_inner();
+            get_conn!(db_pool).and_then(|conn| {
+                let paste =
+                    read_paste(id, password.0, Some(&token), remote, &login_throttle, &conn)?;
+                let (line_start, line_end, version) = anchor(&payload, &paste)?;
+                let new_comment = NewComment {
+                    paste_id: id,
+                    user_id: token.user_id,
+                    body: &payload.body,
+                    line_start,
+                    line_end,
+                    version,
+                    created_at: time::get_time().sec,
+                };
+                call_serv!(comment_serv::create_comment(&new_comment, &conn))
+            })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct UpdateCommentPayload {
+    pub body: String,
+}
+
+/// Only the author edits a comment.
+#[put("/pastes/<id>/comments/<comment_id>", data = "<payload>")]
+pub fn update_comment(id: i32,
+                      comment_id: i32,
+                      payload: Form<UpdateCommentPayload>,
+                      password: PastePassword,
+                      remote: Option<SocketAddr>,
+                      token: Result<UserToken<User>, Error>,
+                      login_throttle: State<LoginThrottle>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            let payload = payload.into_inner();
+            Validator::new()
+                .text("body", &payload.body, validation::COMMENT_MAX_LEN)
+                .finish()?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                read_paste(id, password.0, Some(&token), remote, &login_throttle, &conn)?;
+                let comment = call_serv!(comment_serv::get_comment(id, comment_id, &conn))?;
+                if !token.match_user_id(comment.user_id) {
+                    return Err(permission_denied());
+                }
+                call_serv!(comment_serv::update_comment(comment_id, &payload.body, &conn))
+            })
+        })
+    })
+}
+
+#[delete("/pastes/<id>/comments/<comment_id>")]
+pub fn delete_comment(id: i32,
+                      comment_id: i32,
+                      password: PastePassword,
+                      remote: Option<SocketAddr>,
+                      token: Result<UserToken<User>, Error>,
+                      login_throttle: State<LoginThrottle>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            get_conn!(db_pool).and_then(|conn| {
+                let paste =
+                    read_paste(id, password.0, Some(&token), remote, &login_throttle, &conn)?;
+                let comment = call_serv!(comment_serv::get_comment(id, comment_id, &conn))?;
+                if !can_remove(&comment, &paste, &token, &conn)? {
+                    return Err(permission_denied());
+                }
+                call_serv!(comment_serv::delete_comment(comment_id, &conn))
+            })
+        })
+    })
+}
@@ -0,0 +1,144 @@
+use diesel::pg::PgConnection;
+
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::group as group_serv;
+use models::group::Group;
+
+use helpers::guard::{User, UserToken};
+use helpers::validation::{Validator, GROUP_NAME_MAX_LEN};
+use helpers::error;
+use self::error::{Error, ErrorKind};
+
+fn permission_denied() -> Error {
+    error::forbidden("permission denied").with_kind(ErrorKind::PermissionDenied)
+}
+
+/// Only the owner manages a group.
+fn owned_group(id: i32, token: &UserToken<User>, conn: &PgConnection) -> Result<Group, Error> {
+    let group = call_serv!(group_serv::get_group_by_id(id, conn))?;
+    if !token.match_user_id(group.owner_id) {
+        return Err(permission_denied());
+    }
+    Ok(group)
+}
+
+#[derive(FromForm)]
+pub struct GroupPayload {
+    pub name: String,
+}
+
+#[post("/groups", data = "<payload>")]
+pub fn create_group(payload: Form<GroupPayload>,
+                    token: Result<UserToken<User>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|user| {
+            let payload = payload.into_inner();
+            Validator::new()
+                .text("name", &payload.name, GROUP_NAME_MAX_LEN)
+                .finish()?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(group_serv::create_group(user.user_id, payload.name.trim(), &conn))
+            })
+        })
+    })
+}
+
+#[get("/users/me/groups")]
+pub fn get_my_groups(token: Result<UserToken<User>, Error>,
+                     db_pool: State<DBPool>)
+                     -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|user| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(group_serv::get_groups_by_user_id(user.user_id, &conn)))
+        })
+    })
+}
+
+#[delete("/groups/<id>")]
+pub fn delete_group(id: i32,
+                    token: Result<UserToken<User>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                owned_group(id, &user, &conn)?;
+                call_serv!(group_serv::delete_group(id, &conn))
+            })
+        })
+    })
+}
+
+/// Visible to members only.
+#[get("/groups/<id>/members")]
+pub fn get_members(id: i32,
+                   token: Result<UserToken<User>, Error>,
+                   db_pool: State<DBPool>)
+                   -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                if !call_serv!(group_serv::is_member(id, user.user_id, &conn))? {
+                    return Err(permission_denied());
+                }
+                call_serv!(group_serv::get_members(id, &conn))
+            })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct MemberPayload {
+    pub user_id: i32,
+}
+
+#[post("/groups/<id>/members", data = "<payload>")]
+pub fn add_member(id: i32,
+                  payload: Form<MemberPayload>,
+                  token: Result<UserToken<User>, Error>,
+                  db_pool: State<DBPool>)
+                  -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|user| {
+            let payload = payload.into_inner();
+            get_conn!(db_pool).and_then(|conn| {
+                owned_group(id, &user, &conn)?;
+                call_serv!(group_serv::add_member(id, payload.user_id, &conn))
+            })
+        })
+    })
+}
+
+/// Owners remove members, members can leave on their own.
+#[delete("/groups/<id>/members/<user_id>")]
+pub fn remove_member(id: i32,
+                     user_id: i32,
+                     token: Result<UserToken<User>, Error>,
+                     db_pool: State<DBPool>)
+                     -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                let group = call_serv!(group_serv::get_group_by_id(id, &conn))?;
+                if group.owner_id == user_id {
+                    return Err(error::badrequest("cannot remove the owner of a group"));
+                }
+                if !user.match_user_id(group.owner_id) && !user.match_user_id(user_id) {
+                    return Err(permission_denied());
+                }
+                call_serv!(group_serv::remove_member(id, user_id, &conn))
+            })
+        })
+    })
+}
@@ -18,13 +18,25 @@ macro_rules! call_serv {
     )
 }
 
-macro_rules! match_or_has_roles {
-    ($token: expr, $user_id: expr, $roles: expr) => ({
+macro_rules! match_or_has_permission {
+    ($token: expr, $user_id: expr, $permission: expr) => ({
         $token.and_then(|token| {
-            if !token.match_user_id($user_id) && $roles.into_iter().filter(|role| token.has_role(role)).count() == 0 {
-                return Err(error::forbidden("permission denied"));
+            if !token.match_user_id($user_id) && !token.has_permission($permission) {
+                return Err(error::forbidden("permission denied")
+                           .with_kind(error::ErrorKind::PermissionDenied));
             }
-            Ok(())
+            Ok(token)
+        })
+    })
+}
+
+macro_rules! require_permission {
+    ($token: expr, $permission: expr) => ({
+        $token.and_then(|token| {
+            if let Err(err) = token.check_permission($permission) {
+                return Err(err);
+            }
+            Ok(token)
         })
     })
 }
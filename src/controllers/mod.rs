@@ -1,5 +1,16 @@
 #[macro_use]
 pub mod macros;
 pub mod auth;
+pub mod account;
 pub mod user;
 pub mod paste;
+pub mod api_key;
+pub mod admin;
+pub mod ratelimit;
+pub mod report;
+pub mod group;
+pub mod share;
+pub mod org;
+pub mod webhook;
+pub mod comment;
+pub mod star;
@@ -0,0 +1,263 @@
+use diesel::pg::PgConnection;
+
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::org as org_serv;
+use services::paste as paste_serv;
+use models::org::Org;
+
+use helpers::guard::{User, UserToken};
+use helpers::validation::{Validator, ORG_NAME_MAX_LEN};
+use helpers::error;
+use self::error::{Error, ErrorKind};
+
+fn permission_denied() -> Error {
+    error::forbidden("permission denied").with_kind(ErrorKind::PermissionDenied)
+}
+
+/// The org and the role of the user in it, non-members are turned away.
+fn membership(slug: &str,
+              token: &UserToken<User>,
+              conn: &PgConnection)
+              -> Result<(Org, String), Error> {
+    let org = call_serv!(org_serv::get_org_by_slug(slug, conn))?;
+    match call_serv!(org_serv::get_role(org.id, token.user_id, conn))? {
+        Some(role) => Ok((org, role)),
+        None => Err(permission_denied()),
+    }
+}
+
+fn check_role(role: &str) -> Result<(), Error> {
+    if !org_serv::ROLES.contains(&role) {
+        return Err(error::badrequest(&format!("invalid role {}", role)));
+    }
+    Ok(())
+}
+
+#[derive(FromForm)]
+pub struct OrgPayload {
+    pub slug: String,
+    pub name: String,
+}
+
+#[post("/orgs", data = "<payload>")]
+pub fn create_org(payload: Form<OrgPayload>,
+                  token: Result<UserToken<User>, Error>,
+                  db_pool: State<DBPool>)
+                  -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            let payload = payload.into_inner();
+            Validator::new()
+                .slug("slug", &payload.slug)
+                .text("name", &payload.name, ORG_NAME_MAX_LEN)
+                .finish()?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(org_serv::create_org(&payload.slug,
+                                                payload.name.trim(),
+                                                user.user_id,
+                                                &conn))
+            })
+        })
+    })
+}
+
+/// Owners only, once the org has no pastes left.
+#[delete("/orgs/<slug>")]
+pub fn delete_org(slug: String,
+                  token: Result<UserToken<User>, Error>,
+                  db_pool: State<DBPool>)
+                  -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                let (org, role) = membership(&slug, &user, &conn)?;
+                if role != org_serv::OWNER {
+                    return Err(permission_denied());
+                }
+                call_serv!(org_serv::delete_org(org.id, &conn))
+            })
+        })
+    })
+}
+
+#[get("/users/me/orgs")]
+pub fn get_my_orgs(token: Result<UserToken<User>, Error>,
+                   db_pool: State<DBPool>)
+                   -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:read").and_then(|user| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(org_serv::get_orgs_by_user_id(user.user_id, &conn)))
+        })
+    })
+}
+
+#[get("/orgs/<slug>/pastes")]
+pub fn get_org_pastes(slug: String,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                let (org, _) = membership(&slug, &user, &conn)?;
+                call_serv!(paste_serv::get_pastes_by_org_id(org.id, &conn))
+            })
+        })
+    })
+}
+
+#[get("/orgs/<slug>/members")]
+pub fn get_members(slug: String,
+                   token: Result<UserToken<User>, Error>,
+                   db_pool: State<DBPool>)
+                   -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:read").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                let (org, _) = membership(&slug, &user, &conn)?;
+                call_serv!(org_serv::get_members(org.id, &conn))
+            })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct InvitationPayload {
+    pub user_id: i32,
+    pub role: String,
+}
+
+/// Owners invite with any role, maintainers only below owner.
+#[post("/orgs/<slug>/invitations", data = "<payload>")]
+pub fn invite(slug: String,
+              payload: Form<InvitationPayload>,
+              token: Result<UserToken<User>, Error>,
+              db_pool: State<DBPool>)
+              -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            let payload = payload.into_inner();
+            check_role(&payload.role)?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                let (org, role) = membership(&slug, &user, &conn)?;
+                if !org_serv::can_manage(&role) ||
+                   (payload.role == org_serv::OWNER && role != org_serv::OWNER) {
+                    return Err(permission_denied());
+                }
+                if call_serv!(org_serv::get_role(org.id, payload.user_id, &conn))?.is_some() {
+                    return Err(error::conflict("already a member"));
+                }
+                call_serv!(org_serv::invite(org.id,
+                                            payload.user_id,
+                                            &payload.role,
+                                            user.user_id,
+                                            &conn))
+            })
+        })
+    })
+}
+
+#[get("/users/me/invitations")]
+pub fn get_my_invitations(token: Result<UserToken<User>, Error>,
+                          db_pool: State<DBPool>)
+                          -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:read").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(org_serv::get_invitations_by_user_id(user.user_id, &conn))
+            })
+        })
+    })
+}
+
+#[post("/invitations/<id>/accept")]
+pub fn accept_invitation(id: i32,
+                         token: Result<UserToken<User>, Error>,
+                         db_pool: State<DBPool>)
+                         -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(org_serv::accept_invitation(id, user.user_id, &conn)))
+        })
+    })
+}
+
+#[delete("/invitations/<id>")]
+pub fn decline_invitation(id: i32,
+                          token: Result<UserToken<User>, Error>,
+                          db_pool: State<DBPool>)
+                          -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(org_serv::decline_invitation(id, user.user_id, &conn)))
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct RolePayload {
+    pub role: String,
+}
+
+/// Owners only, an org always keeps at least one owner.
+#[put("/orgs/<slug>/members/<user_id>", data = "<payload>")]
+pub fn set_role(slug: String,
+                user_id: i32,
+                payload: Form<RolePayload>,
+                token: Result<UserToken<User>, Error>,
+                db_pool: State<DBPool>)
+                -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            let payload = payload.into_inner();
+            check_role(&payload.role)?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                let (org, role) = membership(&slug, &user, &conn)?;
+                if role != org_serv::OWNER {
+                    return Err(permission_denied());
+                }
+                if payload.role != org_serv::OWNER &&
+                   call_serv!(org_serv::is_last_owner(org.id, user_id, &conn))? {
+                    return Err(error::badrequest("an org needs at least one owner"));
+                }
+                call_serv!(org_serv::set_role(org.id, user_id, &payload.role, &conn))
+            })
+        })
+    })
+}
+
+/// Owners remove members, members can leave on their own.
+#[delete("/orgs/<slug>/members/<user_id>")]
+pub fn remove_member(slug: String,
+                     user_id: i32,
+                     token: Result<UserToken<User>, Error>,
+                     db_pool: State<DBPool>)
+                     -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                let (org, role) = membership(&slug, &user, &conn)?;
+                if role != org_serv::OWNER && !user.match_user_id(user_id) {
+                    return Err(permission_denied());
+                }
+                if call_serv!(org_serv::is_last_owner(org.id, user_id, &conn))? {
+                    return Err(error::badrequest("an org needs at least one owner"));
+                }
+                call_serv!(org_serv::remove_member(org.id, user_id, &conn))
+            })
+        })
+    })
+}
@@ -1,20 +1,122 @@
-use rocket::State;
+use std::net::SocketAddr;
+
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+
+use rocket::{State, Response};
 use rocket::request::Form;
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
 use rocket::response::status::Custom;
 use rocket_contrib::{JSON, Value};
 
 use services::paste as paste_serv;
-use models::paste::{Paste, NewPaste};
+use services::paste::{Owner, Page, PasteError};
+use services::share as share_serv;
+use services::org as org_serv;
+use services::audit::{self, Event};
+use services::webhook as webhook_serv;
+use models::paste::{Paste, NewPaste, UpdatePaste};
 
 use DBPool;
 
-use helpers::guard::{UserToken, User, Admin};
+use helpers::guard::{UserToken, User, Require, PasteAdmin, PastePassword, IfMatch};
+use helpers::throttle::LoginThrottle;
+use helpers::validation::Validator;
+use helpers::etag;
+use helpers::events::{self, PasteEvents};
+use helpers::views::ViewCounter;
 use helpers::error;
-use self::error::Error;
+use self::error::{Error, ErrorKind};
+
+/// The paste, with a `secrets` list when the secret scanner warned about
+/// or redacted parts of it.
+fn saved_response(saved: paste_serv::Saved) -> Value {
+    let mut response = json!(saved.paste);
+    if !saved.findings.is_empty() {
+        response["secrets"] = json!(saved.findings);
+    }
+    response
+}
+
+/// The paste along with its version for `etag::respond`.
+fn tagged(paste: Paste) -> (Value, i32) {
+    let version = paste.version;
+    (json!(paste), version)
+}
+
+/// Paste for the audit log and webhooks, without the data, which is only
+/// stored sealed.
+fn audit_snapshot(paste: &Paste) -> Value {
+    json!({
+        "id": paste.id,
+        "user_id": paste.user_id,
+        "data_len": paste.data.len(),
+        "encrypted": paste.encrypted,
+        "hidden_by_moderator": paste.hidden_by_moderator,
+        "deleted_at": paste.deleted_at,
+        "org_id": paste.org_id,
+        "version": paste.version
+    })
+}
+
+fn permission_denied() -> Error {
+    error::forbidden("permission denied").with_kind(ErrorKind::PermissionDenied)
+}
+
+/// The author of a personal paste, owners and maintainers of an org paste as
+/// well as its author while still a member, and paste admins.
+fn owns_paste(id: i32,
+              user_id: i32,
+              token: &UserToken<User>,
+              conn: &PgConnection)
+              -> Result<bool, Error> {
+    if token.has_permission("paste:admin") {
+        return Ok(true);
+    }
+    match call_serv!(paste_serv::get_owner(id, user_id, conn))? {
+        Some(Owner::Org(org_id)) => {
+            call_serv!(org_serv::get_role(org_id, token.user_id, conn)).map(|role| match role {
+                Some(role) => org_serv::can_manage(&role) || token.match_user_id(user_id),
+                None => false,
+            })
+        }
+        Some(Owner::User(_)) => Ok(token.match_user_id(user_id)),
+        // no such paste by `user_id`, whoever names it owns nothing
+        None => Ok(false),
+    }
+}
+
+/// Whoever owns the paste and whoever it is shared with for editing.
+fn check_edit_access(id: i32,
+                     user_id: i32,
+                     token: &UserToken<User>,
+                     conn: &PgConnection)
+                     -> Result<(), Error> {
+    if owns_paste(id, user_id, token, conn)? {
+        return Ok(());
+    }
+    let edit = share_serv::EDIT;
+    if !call_serv!(share_serv::has_access(id, user_id, token.user_id, edit, conn))? {
+        return Err(permission_denied());
+    }
+    Ok(())
+}
+
+/// Only whoever owns the paste, shares for editing don't reach this far.
+fn check_delete_access(id: i32,
+                       user_id: i32,
+                       token: &UserToken<User>,
+                       conn: &PgConnection)
+                       -> Result<(), Error> {
+    if !owns_paste(id, user_id, token, conn)? {
+        return Err(permission_denied());
+    }
+    Ok(())
+}
 
 #[get("/pastes")]
-pub fn get_pastes(token: Result<UserToken<Admin>, Error>,
+pub fn get_pastes(token: Result<Require<PasteAdmin>, Error>,
                   db_pool: State<DBPool>)
                   -> Custom<JSON<Value>> {
     call_ctrl!(|| {
@@ -30,33 +132,176 @@ pub fn create_paste(payload: Form<NewPaste>,
                     db_pool: State<DBPool>)
                     -> Custom<JSON<Value>> {
     call_ctrl!(|| {
-        user.and_then(|user| {
+        require_permission!(user, "paste:write").and_then(|user| {
             let payload = payload.into_inner();
             if user.user_id != payload.user_id {
                 return Err(error::badrequest("user_id doesn't match jwt token"));
             }
+            let mut validator = Validator::new();
+            validator.paste(&payload.data,
+                            payload.encrypted,
+                            payload.cipher.as_ref().map(|cipher| cipher.as_str()),
+                            payload.nonce.as_ref().map(|nonce| nonce.as_str()));
+            if let Some(ref password) = payload.password {
+                validator.password("password", password, &user.username);
+            }
+            validator.finish()?;
 
             get_conn!(db_pool).and_then(|conn| {
-                call_serv!(paste_serv::create_paste(&payload, &conn))
+                if let Some(org_id) = payload.org_id {
+                    if call_serv!(org_serv::get_role(org_id, user.user_id, &conn))?.is_none() {
+                        return Err(permission_denied());
+                    }
+                }
+                conn.transaction(|| {
+                    let saved = call_serv!(paste_serv::create_paste(&payload, &conn))?;
+                    call_serv!(webhook_serv::enqueue(webhook_serv::PASTE_CREATED,
+                                                     Some(paste_serv::owner(&saved.paste)),
+                                                     audit_snapshot(&saved.paste),
+                                                     &conn))?;
+                    Ok(saved_response(saved))
+                })
             })
         })
     })
 }
 
+/// Password protected pastes are readable by their owner, members of the
+/// org owning it, paste admins, whoever it's shared with and whoever knows
+/// the password. Wrong passwords are throttled per paste and client ip like
+/// logins.
+pub fn read_paste(id: i32,
+                  password: Option<String>,
+                  token: Option<&UserToken<User>>,
+                  remote: Option<SocketAddr>,
+                  throttle: &LoginThrottle,
+                  conn: &PgConnection)
+                  -> Result<Paste, Error> {
+    let paste = call_serv!(paste_serv::get_paste_by_id(id, conn))?;
+    let privileged = match token {
+        Some(token) if paste_serv::is_protected(&paste) => {
+            let member = match paste_serv::owner(&paste) {
+                Owner::User(owner_id) => token.match_user_id(owner_id),
+                Owner::Org(org_id) => {
+                    call_serv!(org_serv::get_role(org_id, token.user_id, conn))?.is_some()
+                }
+            };
+            member || token.has_permission("paste:admin") ||
+            call_serv!(share_serv::has_access(id,
+                                              paste.user_id,
+                                              token.user_id,
+                                              share_serv::READ,
+                                              conn))?
+        }
+        _ => false,
+    };
+
+    if paste_serv::is_protected(&paste) && !privileged {
+        let password = password
+            .ok_or(error::unauthorized("paste password required")
+                       .with_kind(ErrorKind::PastePasswordRequired))?;
+        let key = format!("paste:{}:{}",
+                          id,
+                          remote.map_or("".to_string(), |addr| addr.ip().to_string()));
+        throttle.check(&key)?;
+        if !paste_serv::verify_password(&paste, &password) {
+            throttle.fail(&key);
+            return Err(error::unauthorized("wrong paste password")
+                           .with_kind(ErrorKind::InvalidCredentials));
+        }
+        throttle.reset(&key);
+    }
+
+    if paste.hidden_by_moderator {
+        return Err(error::unavailable_for_legal_reasons("paste hidden by moderator")
+                       .with_details(json!({"reason": paste.hidden_reason})));
+    }
+    Ok(paste)
+}
+
+/// Every read counts as a view, see `helpers::views`.
 #[get("/pastes/<id>")]
-pub fn get_paste_by_id(id: i32, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
-    call_ctrl!(|| {
-        get_conn!(db_pool).and_then(|conn| call_serv!(paste_serv::get_paste_by_id(id, &conn)))
+pub fn get_paste_by_id(id: i32,
+                       password: PastePassword,
+                       remote: Option<SocketAddr>,
+                       token: Result<UserToken<User>, Error>,
+                       login_throttle: State<LoginThrottle>,
+                       db_pool: State<DBPool>,
+                       views: State<ViewCounter>)
+                       -> Response<'static> {
+    etag::respond(|| {
+        get_conn!(db_pool).and_then(|conn| {
+            read_paste(id, password.0, token.as_ref().ok(), remote, &login_throttle, &conn)
+                .map(|paste| {
+                         views.record(paste.id);
+                         tagged(paste)
+                     })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct UnlockPayload {
+    pub password: String,
+}
+
+/// `GET /pastes/<id>` for clients which can't set the password header.
+#[post("/pastes/<id>/unlock", data = "<payload>")]
+pub fn unlock_paste(id: i32,
+                    payload: Form<UnlockPayload>,
+                    remote: Option<SocketAddr>,
+                    token: Result<UserToken<User>, Error>,
+                    login_throttle: State<LoginThrottle>,
+                    db_pool: State<DBPool>,
+                    views: State<ViewCounter>)
+                    -> Response<'static> {
+    let password = payload.into_inner().password;
+    etag::respond(|| {
+        get_conn!(db_pool).and_then(|conn| {
+            read_paste(id, Some(password), token.as_ref().ok(), remote, &login_throttle, &conn)
+                .map(|paste| {
+                         views.record(paste.id);
+                         tagged(paste)
+                     })
+        })
     })
 }
 
+/// Changes of the paste as server-sent events, for whoever may read it.
+/// Viewers fetch the paste again on `update`, the stream ends on `delete`.
+#[get("/pastes/<id>/events")]
+pub fn get_paste_events(id: i32,
+                        password: PastePassword,
+                        remote: Option<SocketAddr>,
+                        token: Result<UserToken<User>, Error>,
+                        login_throttle: State<LoginThrottle>,
+                        db_pool: State<DBPool>,
+                        events: State<PasteEvents>)
+                        -> Response<'static> {
+    let read = get_conn!(db_pool).and_then(|conn| {
+        read_paste(id, password.0, token.as_ref().ok(), remote, &login_throttle, &conn)
+    });
+    match read.map(|_| events.subscribe(id)) {
+        Ok(Some(stream)) => {
+            Response::build()
+                .header(ContentType::new("text", "event-stream"))
+                .raw_header("Cache-Control", "no-cache")
+                .chunked_body(stream, events::CHUNK_SIZE)
+                .finalize()
+        }
+        Ok(None) => Response::from(error::service_unavailable("too many event streams")),
+        Err(err) => Response::from(err),
+    }
+}
+
 #[get("/users/<user_id>/pastes")]
 pub fn get_pastes_by_user_id(user_id: i32,
                              token: Result<UserToken<User>, Error>,
                              db_pool: State<DBPool>)
                              -> Custom<JSON<Value>> {
     call_ctrl!(|| {
-        match_or_has_roles!(token, user_id, ["admin"]).and_then(|_| {
+        let token = require_permission!(token, "paste:read");
+        match_or_has_permission!(token, user_id, "paste:admin").and_then(|_| {
             get_conn!(db_pool).and_then(|conn| {
                 call_serv!(paste_serv::get_pastes_by_user_id(user_id, &conn))
             })
@@ -64,21 +309,84 @@ pub fn get_pastes_by_user_id(user_id: i32,
     })
 }
 
+/// Anyone may list the public pastes of a user, `GET /users/<user_id>/pastes`
+/// lists all of them for the user and admins. Paged with `before` and
+/// `limit`, see `services::paste::Page`.
+#[get("/users/<user_id>/public-pastes?<page>")]
+pub fn get_public_pastes_by_user_id_paged(user_id: i32,
+                                          page: Page,
+                                          db_pool: State<DBPool>)
+                                          -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        get_conn!(db_pool).and_then(|conn| {
+            call_serv!(paste_serv::get_public_pastes_by_user_id(user_id, &page, &conn))
+        })
+    })
+}
+
+#[get("/users/<user_id>/public-pastes", rank = 2)]
+pub fn get_public_pastes_by_user_id(user_id: i32, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
+    get_public_pastes_by_user_id_paged(user_id, Page::default(), db_pool)
+}
+
+/// Updates are based on a version of the paste, sent as `If-Match` or in
+/// `version`, and refused with the current paste once it has changed since.
 #[put("/users/<user_id>/pastes/<id>", data = "<payload>")]
 pub fn update_paste_by_id(id: i32,
                           user_id: i32,
-                          payload: Form<Paste>,
+                          payload: Form<UpdatePaste>,
+                          if_match: IfMatch,
+                          remote: Option<SocketAddr>,
                           token: Result<UserToken<User>, Error>,
                           db_pool: State<DBPool>)
-                          -> Custom<JSON<Value>> {
-    call_ctrl!(|| {
-        match_or_has_roles!(token, user_id, ["admin"]).and_then(|_| {
+                          -> Response<'static> {
+    etag::respond(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
             let payload = payload.into_inner();
             if payload.user_id != user_id || payload.id != id {
                 return Err(error::badrequest("user_id or paste id doesn't match"));
             }
+            if if_match.0.is_none() && payload.version.is_none() {
+                return Err(error::precondition_required("If-Match header or version required"));
+            }
+            Validator::new()
+                .paste(&payload.data,
+                       payload.encrypted,
+                       payload.cipher.as_ref().map(|cipher| cipher.as_str()),
+                       payload.nonce.as_ref().map(|nonce| nonce.as_str()))
+                .finish()?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                check_edit_access(id, user_id, &token, &conn)?;
+                conn.transaction(|| {
+                    let before = call_serv!(paste_serv::get_paste_by_id(id, &conn))?;
+                    if let Some(ref if_match) = if_match.0 {
+                        if !etag::matches(if_match, &etag::etag(before.version)) {
+                            let changed = "paste was changed in the meantime";
+                            return Err(error::precondition_failed(changed)
+                                           .with_details(json!({"current": before})));
+                        }
+                    }
+                    if payload.version.map_or(false, |version| version != before.version) {
+                        return Err(Error::from(PasteError::Conflict(before)));
+                    }
 
-            get_conn!(db_pool).and_then(|conn| call_serv!(paste_serv::update_paste(payload, &conn)))
+                    let version = before.version;
+                    let saved = call_serv!(paste_serv::update_paste(payload, version, &conn))?;
+                    let event = Event::new(audit::PASTE_UPDATE, audit::TARGET_PASTE, Some(id))
+                        .actor(token.user_id)
+                        .remote(remote)
+                        .before(audit_snapshot(&before))
+                        .after(audit_snapshot(&saved.paste));
+                    call_serv!(audit::record(event, &conn))?;
+                    call_serv!(webhook_serv::enqueue(webhook_serv::PASTE_UPDATED,
+                                                     Some(paste_serv::owner(&saved.paste)),
+                                                     audit_snapshot(&saved.paste),
+                                                     &conn))?;
+                    let version = saved.paste.version;
+                    Ok((saved_response(saved), version))
+                })
+            })
         })
     })
 }
@@ -86,12 +394,107 @@ pub fn update_paste_by_id(id: i32,
 #[delete("/users/<user_id>/pastes/<id>")]
 pub fn delete_paste_by_id(id: i32,
                           user_id: i32,
+                          remote: Option<SocketAddr>,
                           token: Result<UserToken<User>, Error>,
                           db_pool: State<DBPool>)
                           -> Custom<JSON<Value>> {
     call_ctrl!(|| {
-        match_or_has_roles!(token, user_id, ["admin"]).and_then(|_| {
-            get_conn!(db_pool).and_then(|conn| call_serv!(paste_serv::delete_paste(id, &conn)))
+        require_permission!(token, "paste:write").and_then(|token| {
+            get_conn!(db_pool).and_then(|conn| {
+                check_delete_access(id, user_id, &token, &conn)?;
+                let before = match paste_serv::get_paste_by_id(id, &conn) {
+                    Ok(paste) => paste,
+                    Err(DieselError::NotFound) => return Ok(0),
+                    Err(err) => return Err(Error::from(err)),
+                };
+                conn.transaction(|| {
+                    let count = call_serv!(paste_serv::delete_paste(id, &conn))?;
+                    let event = Event::new(audit::PASTE_DELETE, audit::TARGET_PASTE, Some(id))
+                        .actor(token.user_id)
+                        .remote(remote)
+                        .before(audit_snapshot(&before));
+                    call_serv!(audit::record(event, &conn))?;
+                    call_serv!(webhook_serv::enqueue(webhook_serv::PASTE_DELETED,
+                                                     Some(paste_serv::owner(&before)),
+                                                     audit_snapshot(&before),
+                                                     &conn))?;
+                    Ok(count)
+                })
+            })
+        })
+    })
+}
+
+#[get("/users/me/trash")]
+pub fn get_trash(token: Result<UserToken<User>, Error>,
+                 db_pool: State<DBPool>)
+                 -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(paste_serv::get_deleted_pastes_by_user_id(user.user_id, &conn))
+            })
+        })
+    })
+}
+
+#[post("/pastes/<id>/restore")]
+pub fn restore_paste(id: i32,
+                     token: Result<UserToken<User>, Error>,
+                     db_pool: State<DBPool>)
+                     -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(paste_serv::restore_paste(id, user.user_id, &conn))
+            })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct TransferPayload {
+    // slug of the receiving org
+    pub org: String,
+}
+
+/// Hand a personal paste over to an org the author is a member of, from then
+/// on the org decides who may change it.
+#[post("/pastes/<id>/transfer", data = "<payload>")]
+pub fn transfer_paste(id: i32,
+                      payload: Form<TransferPayload>,
+                      remote: Option<SocketAddr>,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            let payload = payload.into_inner();
+            get_conn!(db_pool).and_then(|conn| {
+                let org = call_serv!(org_serv::get_org_by_slug(&payload.org, &conn))?;
+                conn.transaction(|| {
+                    let before = call_serv!(paste_serv::get_paste_by_id(id, &conn))?;
+                    match paste_serv::owner(&before) {
+                        Owner::User(owner_id) if token.match_user_id(owner_id) => (),
+                        Owner::User(_) => return Err(permission_denied()),
+                        Owner::Org(_) => {
+                            return Err(error::badrequest("paste already belongs to an org"))
+                        }
+                    }
+                    if call_serv!(org_serv::get_role(org.id, token.user_id, &conn))?.is_none() {
+                        return Err(permission_denied());
+                    }
+
+                    let paste = call_serv!(paste_serv::transfer_paste(id, org.id, &conn))?;
+                    let event = Event::new(audit::PASTE_TRANSFER, audit::TARGET_PASTE, Some(id))
+                        .actor(token.user_id)
+                        .remote(remote)
+                        .before(audit_snapshot(&before))
+                        .after(audit_snapshot(&paste));
+                    call_serv!(audit::record(event, &conn))?;
+                    Ok(paste)
+                })
+            })
         })
     })
 }
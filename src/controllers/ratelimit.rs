@@ -0,0 +1,11 @@
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use helpers::error;
+
+// requests over the limit are rerouted here by `helpers::ratelimit::RateLimiter`
+#[get("/rate-limited/<group>")]
+pub fn rate_limited(group: String) -> Custom<JSON<Value>> {
+    let msg = format!("{} rate limit exceeded", group);
+    Custom::from(error::too_many_requests(&msg).with_details(json!({"group": group})))
+}
@@ -0,0 +1,97 @@
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::report as report_serv;
+use services::paste as paste_serv;
+
+use helpers::guard::{Moderation, Require, User, UserToken};
+use helpers::validation::{Validator, REASON_MAX_LEN};
+use helpers::error;
+use self::error::Error;
+
+#[derive(FromForm)]
+pub struct ReasonPayload {
+    pub reason: String,
+}
+
+#[post("/pastes/<id>/report", data = "<payload>")]
+pub fn report_paste(id: i32,
+                    payload: Form<ReasonPayload>,
+                    token: Result<UserToken<User>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|user| {
+            let payload = payload.into_inner();
+            Validator::new()
+                .text("reason", &payload.reason, REASON_MAX_LEN)
+                .finish()?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(report_serv::create_report(id, user.user_id, &payload.reason, &conn))
+            })
+        })
+    })
+}
+
+#[get("/reports")]
+pub fn get_open_reports(token: Result<Require<Moderation>, Error>,
+                        db_pool: State<DBPool>)
+                        -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token
+            .and_then(|_| get_conn!(db_pool))
+            .and_then(|conn| call_serv!(report_serv::get_open_reports(&conn)))
+    })
+}
+
+/// Hide the reported paste, the reason is shown with the 451 response.
+#[post("/reports/<id>/resolve", data = "<payload>")]
+pub fn resolve_report(id: i32,
+                      payload: Form<ReasonPayload>,
+                      token: Result<Require<Moderation>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token.and_then(|admin| {
+            let payload = payload.into_inner();
+            Validator::new()
+                .text("reason", &payload.reason, REASON_MAX_LEN)
+                .finish()?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(report_serv::resolve_report(id, admin.user_id, &payload.reason, &conn))
+            })
+        })
+    })
+}
+
+#[post("/reports/<id>/dismiss")]
+pub fn dismiss_report(id: i32,
+                      token: Result<Require<Moderation>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token.and_then(|admin| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(report_serv::dismiss_report(id, admin.user_id, &conn)))
+        })
+    })
+}
+
+#[post("/pastes/<id>/unhide")]
+pub fn unhide_paste(id: i32,
+                    token: Result<Require<Moderation>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token
+            .and_then(|_| get_conn!(db_pool))
+            .and_then(|conn| call_serv!(paste_serv::unhide_paste(id, &conn)))
+    })
+}
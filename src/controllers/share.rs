@@ -0,0 +1,141 @@
+use std::net::SocketAddr;
+
+use diesel::Connection;
+use diesel::pg::PgConnection;
+
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::paste as paste_serv;
+use services::share as share_serv;
+use services::share::Grantee;
+use services::audit::{self, Event};
+use models::paste::Paste;
+
+use helpers::guard::{User, UserToken};
+use helpers::error;
+use self::error::{Error, ErrorKind};
+
+/// Only the owner and paste admins decide who a paste is shared with.
+fn owned_paste(id: i32, token: &UserToken<User>, conn: &PgConnection) -> Result<Paste, Error> {
+    let paste = call_serv!(paste_serv::get_paste_by_id(id, conn))?;
+    if !token.match_user_id(paste.user_id) && !token.has_permission("paste:admin") {
+        return Err(error::forbidden("permission denied").with_kind(ErrorKind::PermissionDenied));
+    }
+    Ok(paste)
+}
+
+#[derive(FromForm)]
+pub struct SharePayload {
+    // either user_id or group_id
+    pub user_id: Option<i32>,
+    pub group_id: Option<i32>,
+    // read or edit
+    pub access: String,
+}
+
+#[post("/pastes/<id>/shares", data = "<payload>")]
+pub fn share_paste(id: i32,
+                   payload: Form<SharePayload>,
+                   remote: Option<SocketAddr>,
+                   token: Result<UserToken<User>, Error>,
+                   db_pool: State<DBPool>)
+                   -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            let payload = payload.into_inner();
+            let grantee = match (payload.user_id, payload.group_id) {
+                (Some(user_id), None) => Grantee::User(user_id),
+                (None, Some(group_id)) => Grantee::Group(group_id),
+                _ => return Err(error::badrequest("either user_id or group_id is required")),
+            };
+            if !share_serv::ACCESS_LEVELS.contains(&payload.access.as_str()) {
+                return Err(error::badrequest(&format!("invalid access {}", payload.access)));
+            }
+
+            get_conn!(db_pool).and_then(|conn| {
+                let paste = owned_paste(id, &token, &conn)?;
+                if grantee == Grantee::User(paste.user_id) {
+                    return Err(error::badrequest("cannot share a paste with its owner"));
+                }
+                conn.transaction(|| {
+                    let share = call_serv!(share_serv::share_paste(id,
+                                                                   grantee,
+                                                                   &payload.access,
+                                                                   token.user_id,
+                                                                   &conn))?;
+                    let event = Event::new(audit::PASTE_SHARE, audit::TARGET_PASTE, Some(id))
+                        .actor(token.user_id)
+                        .remote(remote)
+                        .after(json!(share));
+                    call_serv!(audit::record(event, &conn))?;
+                    Ok(share)
+                })
+            })
+        })
+    })
+}
+
+#[get("/pastes/<id>/shares")]
+pub fn get_shares(id: i32,
+                  token: Result<UserToken<User>, Error>,
+                  db_pool: State<DBPool>)
+                  -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|token| {
+            get_conn!(db_pool).and_then(|conn| {
+                owned_paste(id, &token, &conn)?;
+                call_serv!(share_serv::get_shares(id, &conn))
+            })
+        })
+    })
+}
+
+#[delete("/pastes/<id>/shares/<share_id>")]
+pub fn unshare_paste(id: i32,
+                     share_id: i32,
+                     remote: Option<SocketAddr>,
+                     token: Result<UserToken<User>, Error>,
+                     db_pool: State<DBPool>)
+                     -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            get_conn!(db_pool).and_then(|conn| {
+                owned_paste(id, &token, &conn)?;
+                let before = match call_serv!(share_serv::get_shares(id, &conn))?
+                          .into_iter()
+                          .find(|share| share.id == share_id) {
+                    Some(share) => share,
+                    None => return Ok(0),
+                };
+                conn.transaction(|| {
+                    let count = call_serv!(share_serv::unshare_paste(id, share_id, &conn))?;
+                    let event = Event::new(audit::PASTE_UNSHARE, audit::TARGET_PASTE, Some(id))
+                        .actor(token.user_id)
+                        .remote(remote)
+                        .before(json!(before));
+                    call_serv!(audit::record(event, &conn))?;
+                    Ok(count)
+                })
+            })
+        })
+    })
+}
+
+/// Pastes of other users shared with me, directly or through my groups.
+#[get("/users/me/shared")]
+pub fn get_shared_pastes(token: Result<UserToken<User>, Error>,
+                         db_pool: State<DBPool>)
+                         -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|user| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(share_serv::get_shared_pastes(user.user_id, &conn)))
+        })
+    })
+}
@@ -0,0 +1,59 @@
+use std::net::SocketAddr;
+
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use controllers::paste::read_paste;
+use services::star as star_serv;
+
+use helpers::guard::{User, UserToken, PastePassword};
+use helpers::throttle::LoginThrottle;
+use helpers::error::Error;
+
+/// Pastes are starred by whoever may read them.
+#[post("/pastes/<id>/star")]
+pub fn star_paste(id: i32,
+                  password: PastePassword,
+                  remote: Option<SocketAddr>,
+                  token: Result<UserToken<User>, Error>,
+                  login_throttle: State<LoginThrottle>,
+                  db_pool: State<DBPool>)
+                  -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            get_conn!(db_pool).and_then(|conn| {
+                read_paste(id, password.0, Some(&token), remote, &login_throttle, &conn)?;
+                call_serv!(star_serv::star_paste(token.user_id, id, &conn))
+            })
+        })
+    })
+}
+
+#[delete("/pastes/<id>/star")]
+pub fn unstar_paste(id: i32,
+                    token: Result<UserToken<User>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:write").and_then(|token| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(star_serv::unstar_paste(token.user_id, id, &conn)))
+        })
+    })
+}
+
+#[get("/users/me/stars")]
+pub fn get_my_stars(token: Result<UserToken<User>, Error>,
+                    db_pool: State<DBPool>)
+                    -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "paste:read").and_then(|user| {
+            get_conn!(db_pool)
+                .and_then(|conn| call_serv!(star_serv::get_starred_pastes(user.user_id, &conn)))
+        })
+    })
+}
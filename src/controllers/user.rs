@@ -1,3 +1,8 @@
+use std::net::SocketAddr;
+
+use diesel::Connection;
+use diesel::result::Error as DieselError;
+
 use rocket::State;
 use rocket::request::Form;
 use rocket::http::Status;
@@ -7,16 +12,22 @@ use rocket_contrib::{JSON, Value};
 use DBPool;
 
 use services::user as user_serv;
+use services::mfa as mfa_serv;
+use services::audit::{self, Event};
+use services::webhook as webhook_serv;
 
+use controllers::account;
+use helpers::mailer::MailerState;
 use helpers::guard::{User, Admin, UserToken};
+use helpers::validation::{self, Validator};
 use helpers::error;
-use self::error::Error;
+use self::error::{Error, ErrorKind};
 
 
 #[get("/users/me")]
 pub fn me(token: Result<UserToken<User>, Error>, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
     call_ctrl!(|| {
-        token
+        require_permission!(token, "user:read")
             .and_then(|user| get_conn!(db_pool).and_then(|conn| Ok((user, conn))))
             .and_then(|(user, conn)| call_serv!(user_serv::get_user_by_id(user.user_id, &conn)))
     })
@@ -43,21 +54,53 @@ pub struct UserPayload {
 
 
 #[post("/users", data = "<payload>")]
-pub fn create_user(payload: Form<UserPayload>, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
+pub fn create_user(payload: Form<UserPayload>,
+                   remote: Option<SocketAddr>,
+                   mailer: State<MailerState>,
+                   db_pool: State<DBPool>)
+                   -> Custom<JSON<Value>> {
     call_ctrl!(|| {
         let payload = payload.into_inner();
         if payload.password != payload.confirm_password {
-            return Err(error::badrequest("password mismatch"));
+            return Err(error::badrequest("password mismatch")
+                           .with_kind(ErrorKind::PasswordMismatch));
         }
 
-        // TODO: validation
+        Validator::new()
+            .username("username", &payload.username)
+            .email("email", &payload.email)
+            .password("password", &payload.password, &payload.username)
+            .finish()?;
+
         let new_user = user_serv::NewUser {
             username: &payload.username,
             email: &payload.email,
             password: &payload.password,
         };
 
-        get_conn!(db_pool).and_then(|conn| call_serv!(user_serv::create_user(&new_user, &conn)))
+        get_conn!(db_pool)
+            .and_then(|conn| {
+                conn.transaction(|| {
+                    let user = call_serv!(user_serv::create_user(&new_user, &conn))?;
+                    let event = Event::new(audit::USER_CREATE, audit::TARGET_USER, Some(user.id))
+                        .actor(user.id)
+                        .remote(remote)
+                        .after(json!({"id": user.id, "username": user.username}));
+                    call_serv!(audit::record(event, &conn))?;
+                    // no email, receivers don't need it
+                    let data = json!({"id": user.id, "username": user.username});
+                    call_serv!(webhook_serv::enqueue(webhook_serv::USER_CREATED,
+                                                     None,
+                                                     data,
+                                                     &conn))?;
+                    Ok(user)
+                })
+            })
+            .and_then(|user| {
+                // not fatal, another mail can be requested at /users/me/verification
+                let _ = account::send_verification(&user, &mailer);
+                Ok(user)
+            })
     })
 }
 
@@ -67,7 +110,8 @@ pub fn get_user_by_id(id: i32,
                       db_pool: State<DBPool>)
                       -> Custom<JSON<Value>> {
     call_ctrl!(|| {
-        match_or_has_roles!(token, id, ["admin"])
+        let token = require_permission!(token, "user:read");
+        match_or_has_permission!(token, id, "user:admin")
             .and_then(|_| get_conn!(db_pool))
             .and_then(|conn| call_serv!(user_serv::get_user_by_id(id, &conn)))
     })
@@ -84,17 +128,29 @@ pub struct UpdatePayload {
 #[put("/users/<id>", data = "<payload>")]
 pub fn update_user_by_id(id: i32,
                          payload: Form<UpdatePayload>,
+                         remote: Option<SocketAddr>,
                          token: Result<UserToken<User>, Error>,
                          db_pool: State<DBPool>)
                          -> Custom<JSON<Value>> {
     call_ctrl!(|| {
-        match_or_has_roles!(token, id, ["admin"]).and_then(|_| {
+        let token = require_permission!(token, "user:write");
+        match_or_has_permission!(token, id, "user:admin").and_then(|token| {
             let payload = payload.into_inner();
             if payload.password.as_ref().is_some() &&
                (payload.confirm_password.as_ref().is_none() ||
                 payload.confirm_password.as_ref().unwrap() != payload.password.as_ref().unwrap()) {
-                return Err(error::badrequest("password mismatch"));
+                return Err(error::badrequest("password mismatch")
+                               .with_kind(ErrorKind::PasswordMismatch));
+            }
+
+            let mut validator = Validator::new();
+            if let Some(ref username) = payload.username {
+                validator.username("username", username);
+            }
+            if let Some(ref email) = payload.email {
+                validator.email("email", email);
             }
+            validator.finish()?;
 
             let updated_user = user_serv::UpdatedUser {
                 username: payload.username.as_ref().map(|name| name.as_ref()),
@@ -106,20 +162,219 @@ pub fn update_user_by_id(id: i32,
             };
 
             get_conn!(db_pool).and_then(|conn| {
-                call_serv!(user_serv::update_user(id, &updated_user, &conn))
+                conn.transaction(|| {
+                    let before = call_serv!(user_serv::get_user_by_id(id, &conn))?;
+                    if let Some(ref password) = payload.password {
+                        // checked against the username the account ends up with
+                        let username = payload.username.as_ref().unwrap_or(&before.username);
+                        Validator::new()
+                            .password("password", password, username)
+                            .finish()?;
+                    }
+                    let user = call_serv!(user_serv::update_user(id, &updated_user, &conn))?;
+                    let event = Event::new(audit::USER_UPDATE, audit::TARGET_USER, Some(id))
+                        .actor(token.user_id)
+                        .remote(remote)
+                        .changes(&json!(before), &json!(user));
+                    call_serv!(audit::record(event, &conn))?;
+                    Ok(user)
+                })
             })
         })
     })
 }
 
-#[delete("/users/<id>")]
+/// Public, unlike `GET /users/<id>` which includes the email.
+#[get("/users/<id>/profile")]
+pub fn get_profile(id: i32, db_pool: State<DBPool>) -> Custom<JSON<Value>> {
+    call_ctrl!(|| get_conn!(db_pool).and_then(|conn| call_serv!(user_serv::get_profile(id, &conn))))
+}
+
+#[derive(FromForm)]
+pub struct ProfilePayload {
+    // empty to clear
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+#[put("/users/<id>/profile", data = "<payload>")]
+pub fn update_profile(id: i32,
+                      payload: Form<ProfilePayload>,
+                      remote: Option<SocketAddr>,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        let token = require_permission!(token, "user:write");
+        match_or_has_permission!(token, id, "user:admin").and_then(|token| {
+            let payload = payload.into_inner();
+            let mut validator = Validator::new();
+            if let Some(ref display_name) = payload.display_name {
+                if !display_name.is_empty() {
+                    validator.text("display_name", display_name, validation::DISPLAY_NAME_MAX_LEN);
+                }
+            }
+            if let Some(ref bio) = payload.bio {
+                if !bio.is_empty() {
+                    validator.text("bio", bio, validation::BIO_MAX_LEN);
+                }
+            }
+            if let Some(ref avatar_url) = payload.avatar_url {
+                if !avatar_url.is_empty() {
+                    // loaded by the browsers of whoever views the profile
+                    validator
+                        .url("avatar_url", avatar_url)
+                        .check("avatar_url",
+                               avatar_url.starts_with("https://"),
+                               "must be an https url");
+                }
+            }
+            validator.finish()?;
+
+            let updated = user_serv::UpdatedProfile {
+                display_name: payload.display_name.as_ref().map(|name| name.as_ref()),
+                bio: payload.bio.as_ref().map(|bio| bio.as_ref()),
+                avatar_url: payload.avatar_url.as_ref().map(|url| url.as_ref()),
+            };
+
+            get_conn!(db_pool).and_then(|conn| {
+                conn.transaction(|| {
+                    let before = call_serv!(user_serv::get_profile(id, &conn))?;
+                    let profile = call_serv!(user_serv::update_profile(id, &updated, &conn))?;
+                    let event = Event::new(audit::USER_UPDATE, audit::TARGET_USER, Some(id))
+                        .actor(token.user_id)
+                        .remote(remote)
+                        .changes(&json!(before), &json!(profile));
+                    call_serv!(audit::record(event, &conn))?;
+                    Ok(profile)
+                })
+            })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct DeleteOptions {
+    // restrict, cascade or reassign
+    pub mode: String,
+}
+
+#[delete("/users/<id>?<options>")]
+pub fn delete_user_by_id_with_options(id: i32,
+                                      options: DeleteOptions,
+                                      remote: Option<SocketAddr>,
+                                      token: Result<UserToken<User>, Error>,
+                                      db_pool: State<DBPool>)
+                                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        let mode = user_serv::DeleteMode::from_name(&options.mode)
+            .ok_or(error::badrequest(&format!("invalid delete mode {}", options.mode)))?;
+        delete_user(id, mode, remote, token, db_pool)
+    })
+}
+
+#[delete("/users/<id>", rank = 2)]
 pub fn delete_user_by_id(id: i32,
+                         remote: Option<SocketAddr>,
                          token: Result<UserToken<User>, Error>,
                          db_pool: State<DBPool>)
                          -> Custom<JSON<Value>> {
+    call_ctrl!(|| delete_user(id, user_serv::DeleteMode::Restrict, remote, token, db_pool))
+}
+
+fn delete_user(id: i32,
+               mode: user_serv::DeleteMode,
+               remote: Option<SocketAddr>,
+               token: Result<UserToken<User>, Error>,
+               db_pool: State<DBPool>)
+               -> Result<usize, Error> {
+    let token = require_permission!(token, "user:write");
+    match_or_has_permission!(token, id, "user:admin").and_then(|token| {
+        get_conn!(db_pool).and_then(|conn| {
+            let before = match user_serv::get_user_by_id(id, &conn) {
+                Ok(user) => user,
+                Err(DieselError::NotFound) => return Ok(0),
+                Err(err) => return Err(Error::from(err)),
+            };
+            conn.transaction(|| {
+                let mode_name = mode.name();
+                let count = call_serv!(user_serv::delete_user(id, mode, &conn))?;
+                let event = Event::new(audit::USER_DELETE, audit::TARGET_USER, Some(id))
+                    .actor(token.user_id)
+                    .remote(remote)
+                    .before(json!({"id": before.id, "username": before.username}))
+                    .after(json!({"mode": mode_name}));
+                call_serv!(audit::record(event, &conn))?;
+                Ok(count)
+            })
+        })
+    })
+}
+
+#[post("/users/<id>/unlock")]
+pub fn unlock_user_by_id(id: i32,
+                         token: Result<UserToken<Admin>, Error>,
+                         db_pool: State<DBPool>)
+                         -> Custom<JSON<Value>> {
     call_ctrl!(|| {
-        match_or_has_roles!(token, id, ["admin"])
+        token
+            .and_then(|_| get_conn!(db_pool))
+            .and_then(|conn| call_serv!(user_serv::unlock_user(id, &conn)))
+    })
+}
+
+#[post("/users/me/2fa")]
+pub fn enroll_mfa(token: Result<UserToken<User>, Error>,
+                  db_pool: State<DBPool>)
+                  -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write")
+            .and_then(|user| get_conn!(db_pool).and_then(|conn| Ok((user, conn))))
+            .and_then(|(user, conn)| {
+                let user = call_serv!(user_serv::get_user_by_id(user.user_id, &conn))?;
+                if user.totp_enabled {
+                    return Err(error::badrequest("2fa already enabled"));
+                }
+                call_serv!(mfa_serv::enroll(user.id, &conn))
+            })
+    })
+}
+
+#[derive(FromForm)]
+pub struct MfaConfirmPayload {
+    pub code: String,
+}
+
+#[post("/users/me/2fa/confirm", data = "<payload>")]
+pub fn confirm_mfa(payload: Form<MfaConfirmPayload>,
+                   token: Result<UserToken<User>, Error>,
+                   db_pool: State<DBPool>)
+                   -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write")
+            .and_then(|user| get_conn!(db_pool).and_then(|conn| Ok((user, conn))))
+            .and_then(|(user, conn)| {
+                let user = call_serv!(user_serv::get_user_by_id(user.user_id, &conn))?;
+                if user.totp_enabled {
+                    return Err(error::badrequest("2fa already enabled"));
+                }
+                if !call_serv!(mfa_serv::confirm(&user, &payload.code, &conn))? {
+                    return Err(error::badrequest("wrong 2fa code"));
+                }
+                call_serv!(user_serv::get_user_by_id(user.id, &conn))
+            })
+    })
+}
+
+#[delete("/users/<id>/2fa")]
+pub fn reset_mfa_by_id(id: i32,
+                       token: Result<UserToken<Admin>, Error>,
+                       db_pool: State<DBPool>)
+                       -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token
             .and_then(|_| get_conn!(db_pool))
-            .and_then(|conn| call_serv!(user_serv::delete_user(id, &conn)))
+            .and_then(|conn| call_serv!(mfa_serv::reset(id, &conn)))
     })
 }
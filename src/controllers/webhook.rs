@@ -0,0 +1,174 @@
+use diesel::pg::PgConnection;
+
+use rocket::State;
+use rocket::request::Form;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::{JSON, Value};
+
+use DBPool;
+
+use services::webhook as webhook_serv;
+use services::webhook::Scope;
+use services::org as org_serv;
+use models::webhook::Webhook;
+
+use helpers::guard::{Admin, User, UserToken};
+use helpers::webhook;
+use helpers::validation::Validator;
+use helpers::error;
+use self::error::{Error, ErrorKind};
+
+/// Only whoever registered a webhook and admins see or remove it.
+fn owned_webhook(id: i32, token: &UserToken<User>, conn: &PgConnection) -> Result<Webhook, Error> {
+    let webhook = call_serv!(webhook_serv::get_webhook_by_id(id, conn))?;
+    if !token.match_user_id(webhook.user_id) && !token.has_permission("user:admin") {
+        return Err(error::forbidden("permission denied").with_kind(ErrorKind::PermissionDenied));
+    }
+    Ok(webhook)
+}
+
+#[derive(FromForm)]
+pub struct WebhookPayload {
+    pub url: String,
+    // comma separated, e.g. "paste.created,paste.deleted"
+    pub events: String,
+    // slug of an org to get the events of its pastes instead of personal ones
+    pub org: Option<String>,
+}
+
+fn parse_events(events: &str, global: bool) -> Result<Vec<String>, Error> {
+    let events = events
+        .split(',')
+        .map(|event| event.trim().to_string())
+        .filter(|event| !event.is_empty())
+        .collect::<Vec<String>>();
+    if events.is_empty() {
+        return Err(error::badrequest("at least one event is required"));
+    }
+    if let Some(event) = events
+           .iter()
+           .find(|event| !webhook_serv::EVENTS.contains(&event.as_ref())) {
+        return Err(error::badrequest(&format!("invalid event {}", event)));
+    }
+    if !global && events.iter().any(|event| event == webhook_serv::USER_CREATED) {
+        return Err(error::badrequest("user.created is only sent to global webhooks"));
+    }
+    Ok(events)
+}
+
+/// Personal webhooks get the events of the pastes of their user, org ones
+/// those of the org and can be registered by its owners and maintainers.
+#[post("/webhooks", data = "<payload>")]
+pub fn create_webhook(payload: Form<WebhookPayload>,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            let payload = payload.into_inner();
+            Validator::new().url("url", &payload.url).finish()?;
+            Validator::new()
+                .check("url",
+                       webhook::is_public_target(&payload.url),
+                       "must resolve to a public address")
+                .finish()?;
+            let events = parse_events(&payload.events, false)?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                let scope = match payload.org {
+                    Some(ref slug) => {
+                        let org = call_serv!(org_serv::get_org_by_slug(slug, &conn))?;
+                        match call_serv!(org_serv::get_role(org.id, user.user_id, &conn))? {
+                            Some(ref role) if org_serv::can_manage(role) => Scope::Org(org.id),
+                            _ => {
+                                return Err(error::forbidden("permission denied")
+                                               .with_kind(ErrorKind::PermissionDenied))
+                            }
+                        }
+                    }
+                    None => Scope::User,
+                };
+                call_serv!(webhook_serv::create_webhook(user.user_id,
+                                                        scope,
+                                                        &payload.url,
+                                                        events,
+                                                        &conn))
+            })
+        })
+    })
+}
+
+#[derive(FromForm)]
+pub struct GlobalWebhookPayload {
+    pub url: String,
+    pub events: String,
+}
+
+/// Webhooks getting the events of everyone, including `user.created`.
+#[post("/admin/webhooks", data = "<payload>")]
+pub fn create_global_webhook(payload: Form<GlobalWebhookPayload>,
+                             token: Result<UserToken<Admin>, Error>,
+                             db_pool: State<DBPool>)
+                             -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        token.and_then(|admin| {
+            let payload = payload.into_inner();
+            Validator::new().url("url", &payload.url).finish()?;
+            let events = parse_events(&payload.events, true)?;
+
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(webhook_serv::create_webhook(admin.user_id,
+                                                        Scope::Global,
+                                                        &payload.url,
+                                                        events,
+                                                        &conn))
+            })
+        })
+    })
+}
+
+#[get("/users/me/webhooks")]
+pub fn get_my_webhooks(token: Result<UserToken<User>, Error>,
+                       db_pool: State<DBPool>)
+                       -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:read").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                call_serv!(webhook_serv::get_webhooks_by_user_id(user.user_id, &conn))
+            })
+        })
+    })
+}
+
+#[delete("/webhooks/<id>")]
+pub fn delete_webhook(id: i32,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:write").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                owned_webhook(id, &user, &conn)?;
+                call_serv!(webhook_serv::delete_webhook(id, &conn))
+            })
+        })
+    })
+}
+
+/// Delivery log of a webhook, latest first, with the outcome of the last
+/// attempt of each.
+#[get("/webhooks/<id>/deliveries")]
+pub fn get_deliveries(id: i32,
+                      token: Result<UserToken<User>, Error>,
+                      db_pool: State<DBPool>)
+                      -> Custom<JSON<Value>> {
+    call_ctrl!(|| {
+        require_permission!(token, "user:read").and_then(|user| {
+            get_conn!(db_pool).and_then(|conn| {
+                owned_webhook(id, &user, &conn)?;
+                call_serv!(webhook_serv::get_deliveries_by_webhook_id(id, &conn))
+            })
+        })
+    })
+}
@@ -1,4 +1,5 @@
-use ring::pbkdf2;
+use ring::{digest, pbkdf2};
+use ring::rand::{SecureRandom, SystemRandom};
 use std::convert::From;
 
 use ENV;
@@ -36,6 +37,38 @@ fn salt(username: &str) -> Vec<u8> {
     salt
 }
 
+/// Fast digest for high entropy secrets like api keys, which are looked up by
+/// their digest and don't need a slow salted hash.
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    Vec::from(digest::digest(&digest::SHA256, data).as_ref())
+}
+
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("Fail to generate random bytes");
+    bytes
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+                 ::std::str::from_utf8(pair)
+                     .ok()
+                     .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+             })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,4 +88,15 @@ mod tests {
         assert_eq!(verify_password(&USERNAME, &credential, &WRONG_PASSWORD),
                    false);
     }
+
+    #[test]
+    fn hex_and_sha256() {
+        assert_eq!(to_hex(&[0x00, 0x0f, 0xab]), "000fab");
+        assert_eq!(to_hex(&sha256(b"abc")),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(random_bytes(16).len(), 16);
+        assert_eq!(from_hex("000fab"), Some(vec![0x00, 0x0f, 0xab]));
+        assert_eq!(from_hex("0fa"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
 }
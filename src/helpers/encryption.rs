@@ -0,0 +1,121 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use base64;
+use ring::aead;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use ENV;
+use helpers::env::DataKey;
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, PartialEq)]
+pub enum SealError {
+    UnknownKey(String),
+    Corrupt,
+    Failed,
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SealError::UnknownKey(ref id) => write!(f, "unknown data key {}", id),
+            _ => write!(f, "{}", self.description()),
+        }
+    }
+}
+
+impl StdError for SealError {
+    fn description(&self) -> &str {
+        match *self {
+            SealError::UnknownKey(_) => "unknown data key",
+            SealError::Corrupt => "sealed data is corrupt or was sealed with another key",
+            SealError::Failed => "fail to seal data",
+        }
+    }
+}
+
+/// Key new data is sealed with, `None` while no `DATA_KEYS` are configured.
+pub fn active_key() -> Option<&'static DataKey> {
+    ENV.data_keys.first()
+}
+
+pub fn find_key(id: &str) -> Result<&'static DataKey, SealError> {
+    ENV.data_keys
+        .iter()
+        .find(|key| key.id == id)
+        .ok_or(SealError::UnknownKey(id.to_string()))
+}
+
+/// Base64 of a random nonce followed by the aes-256-gcm ciphertext. The key
+/// id is authenticated as well, so data can't be passed off as sealed with
+/// another key.
+pub fn seal(key: &DataKey, plaintext: &[u8]) -> Result<String, SealError> {
+    let algorithm = &aead::AES_256_GCM;
+    let sealing_key = aead::SealingKey::new(algorithm, &key.key).map_err(|_| SealError::Failed)?;
+
+    let mut sealed = vec![0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut sealed)
+        .map_err(|_| SealError::Failed)?;
+    sealed.extend(plaintext);
+    sealed.extend(vec![0u8; algorithm.tag_len()]);
+
+    let len = {
+        let (nonce, in_out) = sealed.split_at_mut(NONCE_LEN);
+        aead::seal_in_place(&sealing_key,
+                            nonce,
+                            in_out,
+                            algorithm.tag_len(),
+                            key.id.as_bytes())
+                .map_err(|_| SealError::Failed)?
+    };
+    Ok(base64::encode(&sealed[..NONCE_LEN + len]))
+}
+
+pub fn open(key: &DataKey, sealed: &str) -> Result<Vec<u8>, SealError> {
+    let opening_key = aead::OpeningKey::new(&aead::AES_256_GCM, &key.key)
+        .map_err(|_| SealError::Failed)?;
+    let mut sealed = base64::decode(sealed).map_err(|_| SealError::Corrupt)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(SealError::Corrupt);
+    }
+
+    let (nonce, in_out) = sealed.split_at_mut(NONCE_LEN);
+    aead::open_in_place(&opening_key, nonce, 0, in_out, key.id.as_bytes())
+        .map(|plaintext| plaintext.to_vec())
+        .map_err(|_| SealError::Corrupt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, byte: u8) -> DataKey {
+        DataKey {
+            id: id.to_string(),
+            key: vec![byte; 32],
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let key = key("v1", 1);
+        let sealed = seal(&key, b"paste data").unwrap();
+        assert_eq!(open(&key, &sealed), Ok(b"paste data".to_vec()));
+        // fresh nonce every time
+        assert!(seal(&key, b"paste data").unwrap() != sealed);
+
+        assert_eq!(open(&key, "not base64!"), Err(SealError::Corrupt));
+        assert_eq!(open(&key, "AAAA"), Err(SealError::Corrupt));
+    }
+
+    #[test]
+    fn test_open_with_other_key() {
+        let sealed = seal(&key("v1", 1), b"paste data").unwrap();
+        assert_eq!(open(&key("v2", 2), &sealed), Err(SealError::Corrupt));
+        // same key material under another id
+        assert_eq!(open(&key("v2", 1), &sealed), Err(SealError::Corrupt));
+    }
+}
@@ -1,3 +1,4 @@
+use base64;
 use dotenv::dotenv;
 use std::env;
 
@@ -6,6 +7,91 @@ pub struct Env {
     pub digest_salt: String,
     pub jwt_secret: String,
     pub test_expired_token: bool,
+    pub rate_limit_auth: RateLimit,
+    pub rate_limit_read: RateLimit,
+    pub rate_limit_write: RateLimit,
+    pub mail_backend: String,
+    pub mail_from: String,
+    pub mail_file: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub trash_retention_days: i64,
+    pub secret_scan: SecretScan,
+    pub secret_scan_rules: Option<String>,
+    pub secret_scan_entropy: f64,
+    pub data_keys: Vec<DataKey>,
+}
+
+/// What to do with pastes that look like they contain credentials.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SecretScan {
+    Off,
+    /// Save as is, list the findings in the response.
+    Warn,
+    /// Save with the matches replaced by a placeholder.
+    Redact,
+    /// Refuse with a 422.
+    Reject,
+}
+
+/// Key for paste data at rest, `id` is stored along with every row it
+/// sealed so keys can be rotated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataKey {
+    pub id: String,
+    pub key: Vec<u8>,
+}
+
+// 256 bit keys for aes-256-gcm
+const DATA_KEY_LEN: usize = 32;
+
+/// `capacity` requests per `period` seconds, written as "capacity/period".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period: u32,
+}
+
+fn rate_limit(key: &str, default: RateLimit) -> RateLimit {
+    let value = match env::var(key) {
+        Ok(value) => value,
+        Err(_) => return default,
+    };
+    let mut parts = value.splitn(2, '/');
+    let capacity = parts.next().and_then(|capacity| capacity.trim().parse().ok());
+    let period = parts.next().and_then(|period| period.trim().parse().ok());
+    match (capacity, period) {
+        (Some(capacity), Some(period)) if capacity > 0 && period > 0 => {
+            RateLimit { capacity, period }
+        }
+        _ => panic!("{} must be in the form of capacity/seconds", key),
+    }
+}
+
+/// Written as "id:base64key,..." with the active key first, the rest are
+/// only used to open data which wasn't re-encrypted yet.
+fn data_keys(var: &str) -> Vec<DataKey> {
+    let value = match env::var(var) {
+        Ok(value) => value,
+        Err(_) => return Vec::new(),
+    };
+    value
+        .split(',')
+        .map(|entry| {
+            let mut parts = entry.trim().splitn(2, ':');
+            let id = parts.next().unwrap_or("");
+            let key = parts.next().and_then(|key| base64::decode(key).ok());
+            match key {
+                Some(key) if !id.is_empty() && key.len() == DATA_KEY_LEN => {
+                    DataKey {
+                        id: id.to_string(),
+                        key,
+                    }
+                }
+                _ => panic!("{} must be a list of id:key with base64 encoded 32 byte keys", var),
+            }
+        })
+        .collect()
 }
 
 pub fn load() -> Env {
@@ -18,11 +104,71 @@ pub fn load() -> Env {
         Ok(value) => value == "true",
         Err(_) => false,
     };
+    let rate_limit_auth = rate_limit("RATE_LIMIT_AUTH",
+                                     RateLimit {
+                                         capacity: 20,
+                                         period: 60,
+                                     });
+    let rate_limit_read = rate_limit("RATE_LIMIT_READ",
+                                     RateLimit {
+                                         capacity: 300,
+                                         period: 60,
+                                     });
+    let rate_limit_write = rate_limit("RATE_LIMIT_WRITE",
+                                      RateLimit {
+                                          capacity: 60,
+                                          period: 60,
+                                      });
+    let mail_backend = env::var("MAIL_BACKEND").unwrap_or("file".to_string());
+    let mail_from = env::var("MAIL_FROM").unwrap_or("pastebin@localhost".to_string());
+    let mail_file = env::var("MAIL_FILE").unwrap_or("mail.log".to_string());
+    let smtp_host = env::var("SMTP_HOST").unwrap_or("localhost".to_string());
+    let smtp_port = match env::var("SMTP_PORT") {
+        Ok(port) => port.parse().expect("SMTP_PORT must be a port number"),
+        Err(_) => 25,
+    };
+    let trash_retention_days = match env::var("TRASH_RETENTION_DAYS") {
+        Ok(days) => days.parse().expect("TRASH_RETENTION_DAYS must be a number of days"),
+        Err(_) => 30,
+    };
+
+    let secret_scan = match env::var("SECRET_SCAN") {
+        Ok(mode) => {
+            match mode.as_str() {
+                "off" => SecretScan::Off,
+                "warn" => SecretScan::Warn,
+                "redact" => SecretScan::Redact,
+                "reject" => SecretScan::Reject,
+                _ => panic!("SECRET_SCAN must be one of off, warn, redact or reject"),
+            }
+        }
+        Err(_) => SecretScan::Warn,
+    };
+    // extra rules, one "name pattern" per line
+    let secret_scan_rules = env::var("SECRET_SCAN_RULES").ok();
+    let secret_scan_entropy = match env::var("SECRET_SCAN_ENTROPY") {
+        Ok(bits) => bits.parse().expect("SECRET_SCAN_ENTROPY must be bits per character"),
+        Err(_) => 4.5,
+    };
+    let data_keys = data_keys("DATA_KEYS");
 
     Env {
         database_url,
         digest_salt,
         jwt_secret,
         test_expired_token,
+        rate_limit_auth,
+        rate_limit_read,
+        rate_limit_write,
+        mail_backend,
+        mail_from,
+        mail_file,
+        smtp_host,
+        smtp_port,
+        trash_retention_days,
+        secret_scan,
+        secret_scan_rules,
+        secret_scan_entropy,
+        data_keys,
     }
 }
@@ -1,18 +1,108 @@
+use std::collections::BTreeMap;
 use std::fmt;
+use std::io::Cursor;
 use std::error::Error as StdError;
 use std::convert::From;
 
-use rocket::http::Status;
+use rocket::Response;
+use rocket::http::{ContentType, Status};
 use rocket::response::status::Custom;
 use rocket_contrib::{JSON, Value};
 
 use diesel::result::Error as DieselError;
 use diesel::result::DatabaseErrorKind;
 
+/// Stable, machine readable error kind. `msg` is meant for humans and may
+/// change, clients should match on `kind`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    BadRequest,
+    ValidationFailed,
+    PasswordMismatch,
+    InvalidCredentials,
+    Duplicate,
+    ForeignKeyViolation,
+    NotNullViolation,
+    Unauthorized,
+    TokenMissing,
+    TokenInvalid,
+    TokenExpired,
+    Forbidden,
+    PermissionDenied,
+    InsufficientScope,
+    NotFound,
+    Conflict,
+    PreconditionFailed,
+    PreconditionRequired,
+    HiddenByModerator,
+    RateLimited,
+    AccountLocked,
+    AccountSuspended,
+    AccountDeleted,
+    SecretDetected,
+    PastePasswordRequired,
+    Internal,
+}
+
+impl ErrorKind {
+    fn from_code(code: u16) -> ErrorKind {
+        match code {
+            400 => ErrorKind::BadRequest,
+            401 => ErrorKind::Unauthorized,
+            403 => ErrorKind::Forbidden,
+            404 => ErrorKind::NotFound,
+            409 => ErrorKind::Conflict,
+            412 => ErrorKind::PreconditionFailed,
+            422 => ErrorKind::ValidationFailed,
+            428 => ErrorKind::PreconditionRequired,
+            429 => ErrorKind::RateLimited,
+            451 => ErrorKind::HiddenByModerator,
+            _ => ErrorKind::Internal,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Error {
     pub code: u16,
+    pub kind: ErrorKind,
     pub msg: String,
+    // per-field validation errors
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    // filled in by the `RequestId` fairing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl Error {
+    fn new(status: Status, msg: &str) -> Error {
+        Error {
+            code: status.code,
+            kind: ErrorKind::from_code(status.code),
+            msg: msg.to_string(),
+            fields: BTreeMap::new(),
+            details: None,
+            request_id: None,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: ErrorKind) -> Error {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_details(mut self, details: Value) -> Error {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn status(&self) -> Status {
+        Status::from_code(self.code).unwrap_or_else(|| Status::new(self.code, "custom code"))
+    }
 }
 
 impl fmt::Display for Error {
@@ -33,9 +123,19 @@ impl StdError for Error {
 
 impl From<Error> for Custom<JSON<Value>> {
     fn from(err: Error) -> Custom<JSON<Value>> {
-        let status =
-            Status::from_code(err.code).unwrap_or_else(|| Status::new(err.code, "custom code"));
-        Custom(status, JSON(json!(err)))
+        Custom(err.status(), JSON(json!(err)))
+    }
+}
+
+/// For controllers building their own `Response`, same body as the `Custom`
+/// one.
+impl From<Error> for Response<'static> {
+    fn from(err: Error) -> Response<'static> {
+        Response::build()
+            .status(err.status())
+            .header(ContentType::JSON)
+            .sized_body(Cursor::new(json!(err).to_string()))
+            .finalize()
     }
 }
 
@@ -46,14 +146,35 @@ impl From<DieselError> for Error {
         match err {
             DieselError::NotFound => notfound("data not found"),
             DieselError::DatabaseError(kind, info) => {
+                let table_name = info.table_name().unwrap_or("table");
+                // [table_name]_column_[key|fkey]
+                let constraint = info.constraint_name()
+                    .unwrap_or("data")
+                    .trim_left_matches(&format!("{}_", table_name));
+
                 match kind {
                     DatabaseErrorKind::UniqueViolation => {
-                        // [table_name]_column_[key]
-                        let mut name = info.constraint_name().or(Some("data")).unwrap();
-                        let table_name = info.table_name().or(Some("table")).unwrap();
-                        name = name.trim_left_matches(&format!("{}_", table_name));
-                        name = name.trim_right_matches("_key");
+                        let name = constraint.trim_right_matches("_key");
                         badrequest(&format!("duplicate {}", name))
+                            .with_kind(ErrorKind::Duplicate)
+                            .with_details(json!({"table": table_name, "column": name}))
+                    }
+                    DatabaseErrorKind::ForeignKeyViolation => {
+                        let name = constraint.trim_right_matches("_fkey");
+                        badrequest(&format!("referenced {} not found", name))
+                            .with_kind(ErrorKind::ForeignKeyViolation)
+                            .with_details(json!({"table": table_name, "column": name}))
+                    }
+                    // no dedicated kinds in diesel for these, so match the
+                    // postgres messages
+                    _ if info.message().starts_with("null value in column") => {
+                        let column = info.column_name().unwrap_or("data");
+                        badrequest(&format!("missing {}", column))
+                            .with_kind(ErrorKind::NotNullViolation)
+                            .with_details(json!({"table": table_name, "column": column}))
+                    }
+                    _ if info.message().starts_with("could not serialize access") => {
+                        conflict("concurrent update, please retry")
                     }
                     _ => default_error,
                 }
@@ -64,36 +185,127 @@ impl From<DieselError> for Error {
 }
 
 pub fn badrequest(msg: &str) -> Error {
-    Error {
-        code: Status::BadRequest.code,
-        msg: msg.to_string(),
-    }
+    Error::new(Status::BadRequest, msg)
 }
 
 pub fn unauthorized(msg: &str) -> Error {
-    Error {
-        code: Status::Unauthorized.code,
-        msg: msg.to_string(),
-    }
+    Error::new(Status::Unauthorized, msg)
 }
 
 pub fn forbidden(msg: &str) -> Error {
-    Error {
-        code: Status::Forbidden.code,
-        msg: msg.to_string(),
-    }
+    Error::new(Status::Forbidden, msg)
 }
 
 pub fn notfound(msg: &str) -> Error {
-    Error {
-        code: Status::NotFound.code,
-        msg: msg.to_string(),
-    }
+    Error::new(Status::NotFound, msg)
+}
+
+pub fn conflict(msg: &str) -> Error {
+    Error::new(Status::Conflict, msg)
+}
+
+pub fn precondition_failed(msg: &str) -> Error {
+    Error::new(Status::PreconditionFailed, msg)
+}
+
+pub fn unprocessable_entity(msg: &str, fields: BTreeMap<String, Vec<String>>) -> Error {
+    Error { fields, ..Error::new(Status::UnprocessableEntity, msg) }
+}
+
+pub fn precondition_required(msg: &str) -> Error {
+    Error::new(Status::new(428, "Precondition Required"), msg)
+}
+
+pub fn too_many_requests(msg: &str) -> Error {
+    Error::new(Status::TooManyRequests, msg)
+}
+
+pub fn unavailable_for_legal_reasons(msg: &str) -> Error {
+    Error::new(Status::new(451, "Unavailable For Legal Reasons"), msg)
 }
 
 pub fn internal_server_error(msg: &str) -> Error {
-    Error {
-        code: Status::InternalServerError.code,
-        msg: msg.to_string(),
+    Error::new(Status::InternalServerError, msg)
+}
+
+pub fn service_unavailable(msg: &str) -> Error {
+    Error::new(Status::ServiceUnavailable, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::result::DatabaseErrorInformation;
+
+    struct Info {
+        message: &'static str,
+        column: Option<&'static str>,
+        constraint: Option<&'static str>,
+    }
+
+    impl DatabaseErrorInformation for Info {
+        fn message(&self) -> &str {
+            self.message
+        }
+        fn details(&self) -> Option<&str> {
+            None
+        }
+        fn hint(&self) -> Option<&str> {
+            None
+        }
+        fn table_name(&self) -> Option<&str> {
+            Some("pastes")
+        }
+        fn column_name(&self) -> Option<&str> {
+            self.column
+        }
+        fn constraint_name(&self) -> Option<&str> {
+            self.constraint
+        }
+    }
+
+    fn database_error(kind: DatabaseErrorKind, info: Info) -> Error {
+        Error::from(DieselError::DatabaseError(kind, Box::new(info)))
+    }
+
+    #[test]
+    fn test_from_diesel_error() {
+        let err = Error::from(DieselError::NotFound);
+        assert_eq!((err.code, err.kind), (404, ErrorKind::NotFound));
+
+        let err = database_error(DatabaseErrorKind::ForeignKeyViolation,
+                                 Info {
+                                     message: "insert or update violates foreign key constraint",
+                                     column: None,
+                                     constraint: Some("pastes_user_id_fkey"),
+                                 });
+        assert_eq!(err.kind, ErrorKind::ForeignKeyViolation);
+        assert_eq!(err.msg, "referenced user_id not found");
+
+        let err = database_error(DatabaseErrorKind::__Unknown,
+                                 Info {
+                                     message: "null value in column \"data\" violates not-null \
+                                               constraint",
+                                     column: Some("data"),
+                                     constraint: None,
+                                 });
+        assert_eq!(err.kind, ErrorKind::NotNullViolation);
+        assert_eq!(err.details, Some(json!({"table": "pastes", "column": "data"})));
+
+        let err = database_error(DatabaseErrorKind::__Unknown,
+                                 Info {
+                                     message: "could not serialize access due to concurrent \
+                                               update",
+                                     column: None,
+                                     constraint: None,
+                                 });
+        assert_eq!((err.code, err.kind), (409, ErrorKind::Conflict));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let value = json!(badrequest("password mismatch").with_kind(ErrorKind::PasswordMismatch));
+        assert_eq!(value,
+                   json!({"code": 400, "kind": "password_mismatch", "msg": "password mismatch"}));
     }
 }
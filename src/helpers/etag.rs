@@ -0,0 +1,57 @@
+use std::io::Cursor;
+
+use rocket::Response;
+use rocket::http::{ContentType, Status};
+use rocket_contrib::Value;
+
+use helpers::error::Error;
+
+/// Strong validator of a resource version, sent as `ETag` and expected back
+/// in `If-Match`.
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Whether an `If-Match` header lists the etag or is `*`. `If-Match` uses
+/// the strong comparison, so weak validators never match.
+pub fn matches(if_match: &str, etag: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// `call_ctrl!` for controllers returning a single versioned resource along
+/// with its version, which is sent as `ETag`.
+pub fn respond<F>(ctrl_fn: F) -> Response<'static>
+    where F: FnOnce() -> Result<(Value, i32), Error>
+{
+    match ctrl_fn() {
+        Ok((body, version)) => {
+            Response::build()
+                .status(Status::Ok)
+                .header(ContentType::JSON)
+                .raw_header("ETag", etag(version))
+                .sized_body(Cursor::new(body.to_string()))
+                .finalize()
+        }
+        Err(err) => Response::from(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let current = etag(3);
+        assert_eq!(current, "\"3\"");
+        assert!(matches("\"3\"", &current));
+        assert!(matches("\"2\", \"3\"", &current));
+        assert!(matches("*", &current));
+        assert!(!matches("\"2\"", &current));
+        assert!(!matches("W/\"3\"", &current));
+        assert!(!matches("3", &current));
+    }
+}
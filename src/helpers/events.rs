@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::io::{self, Cursor, Read};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use fallible_iterator::FallibleIterator;
+use postgres::{Connection, TlsMode};
+
+use serde_json;
+
+use ENV;
+use services::paste as paste_serv;
+use services::paste::PasteEvent;
+
+const KEEP_ALIVE_SECS: u64 = 15;
+const RECONNECT_SECS: u64 = 5;
+/// Every stream takes up a worker while it is open, so there are only so
+/// many of them.
+pub const MAX_STREAMS: usize = 256;
+pub const MAX_STREAMS_PER_PASTE: usize = 32;
+/// Rocket writes out a chunk once it is full, so every message is padded
+/// to a multiple of it.
+pub const CHUNK_SIZE: u64 = 128;
+
+/// Open streams, in total and per paste.
+#[derive(Default)]
+struct Streams {
+    total: usize,
+    by_paste: HashMap<i32, usize>,
+}
+
+/// Subscribers to changes of pastes, fed by `spawn` from the notifications
+/// of `services::paste`.
+#[derive(Clone, Default)]
+pub struct PasteEvents {
+    subscribers: Arc<Mutex<HashMap<i32, Vec<Sender<PasteEvent>>>>>,
+    streams: Arc<Mutex<Streams>>,
+}
+
+impl PasteEvents {
+    pub fn new() -> PasteEvents {
+        PasteEvents::default()
+    }
+
+    /// `None` while `MAX_STREAMS` or `MAX_STREAMS_PER_PASTE` are open.
+    pub fn subscribe(&self, paste_id: i32) -> Option<EventStream> {
+        {
+            let mut streams = self.streams.lock().unwrap();
+            let open = streams.by_paste.get(&paste_id).cloned().unwrap_or(0);
+            if streams.total >= MAX_STREAMS || open >= MAX_STREAMS_PER_PASTE {
+                return None;
+            }
+            streams.total += 1;
+            streams.by_paste.insert(paste_id, open + 1);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.entry(paste_id).or_insert_with(Vec::new).push(sender);
+        Some(EventStream::new(paste_id, receiver, self.streams.clone()))
+    }
+
+    /// Subscribers which went away are dropped along the way.
+    pub fn publish(&self, event: PasteEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let gone = match subscribers.get_mut(&event.paste_id) {
+            Some(senders) => {
+                senders.retain(|sender| sender.send(event.clone()).is_ok());
+                senders.is_empty()
+            }
+            None => false,
+        };
+        if gone {
+            subscribers.remove(&event.paste_id);
+        }
+    }
+}
+
+fn format_event(event: &PasteEvent) -> String {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    format!("event: {}\ndata: {}\n\n", event.event, data)
+}
+
+/// Put a comment line of spaces in front of `message`, which clients
+/// ignore, so it fills up whole chunks.
+fn pad(message: String) -> String {
+    let chunk = CHUNK_SIZE as usize;
+    let mut padding = (chunk - message.len() % chunk) % chunk;
+    // the shortest comment line is ":\n"
+    if padding == 1 {
+        padding += chunk;
+    }
+    if padding == 0 {
+        return message;
+    }
+    format!(":{}\n{}", " ".repeat(padding - 2), message)
+}
+
+/// `text/event-stream` body of a subscription, ends after the paste was
+/// deleted. Keep-alive comments go out while nothing happens, which is also
+/// how a closed connection is noticed.
+pub struct EventStream {
+    paste_id: i32,
+    events: Receiver<PasteEvent>,
+    pending: Cursor<Vec<u8>>,
+    done: bool,
+    streams: Arc<Mutex<Streams>>,
+}
+
+impl EventStream {
+    fn new(paste_id: i32,
+           events: Receiver<PasteEvent>,
+           streams: Arc<Mutex<Streams>>)
+           -> EventStream {
+        EventStream {
+            paste_id,
+            events,
+            pending: Cursor::new(Vec::new()),
+            done: false,
+            streams,
+        }
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        let mut streams = self.streams.lock().unwrap();
+        streams.total -= 1;
+        let gone = match streams.by_paste.get_mut(&self.paste_id) {
+            Some(open) => {
+                *open -= 1;
+                *open == 0
+            }
+            None => false,
+        };
+        if gone {
+            streams.by_paste.remove(&self.paste_id);
+        }
+    }
+}
+
+impl Read for EventStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.position() as usize == self.pending.get_ref().len() {
+            if self.done {
+                return Ok(0);
+            }
+            let message = match self.events
+                      .recv_timeout(Duration::from_secs(KEEP_ALIVE_SECS)) {
+                Ok(event) => {
+                    self.done = event.event == paste_serv::DELETED;
+                    format_event(&event)
+                }
+                Err(RecvTimeoutError::Timeout) => ": keep-alive\n\n".to_string(),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            };
+            self.pending = Cursor::new(pad(message).into_bytes());
+        }
+        self.pending.read(buf)
+    }
+}
+
+fn listen(events: &PasteEvents) -> Result<(), Box<StdError>> {
+    let conn = Connection::connect(ENV.database_url.as_str(), TlsMode::None)?;
+    conn.execute(&format!("LISTEN {}", paste_serv::EVENTS_CHANNEL), &[])?;
+    let notifications = conn.notifications();
+    let mut iter = notifications.blocking_iter();
+    while let Some(notification) = iter.next()? {
+        match serde_json::from_str::<PasteEvent>(&notification.payload) {
+            Ok(event) => events.publish(event),
+            Err(err) => eprintln!("Fail to parse paste event: {}", err),
+        }
+    }
+    Ok(())
+}
+
+/// Listen for paste notifications on a connection of its own, since the
+/// pooled diesel ones can't receive them, and reconnect when it's lost.
+pub fn spawn(events: PasteEvents) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+                      if let Err(err) = listen(&events) {
+                          eprintln!("Fail to listen for paste events: {}", err);
+                      }
+                      thread::sleep(Duration::from_secs(RECONNECT_SECS));
+                  })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(paste_id: i32, event: &str, version: Option<i32>) -> PasteEvent {
+        PasteEvent {
+            paste_id,
+            event: event.to_string(),
+            version,
+        }
+    }
+
+    #[test]
+    fn test_publish() {
+        let events = PasteEvents::new();
+        let mut stream = events.subscribe(1).unwrap();
+        let other = events.subscribe(2).unwrap();
+        drop(other);
+
+        events.publish(event(1, paste_serv::UPDATED, Some(2)));
+        events.publish(event(2, paste_serv::UPDATED, Some(2)));
+        assert!(!events.subscribers.lock().unwrap().contains_key(&2));
+
+        events.publish(event(1, paste_serv::DELETED, None));
+        let mut body = String::new();
+        stream.read_to_string(&mut body).unwrap();
+        assert_eq!(body.len() % CHUNK_SIZE as usize, 0);
+        let body = body.split('\n')
+            .filter(|line| !line.starts_with(':'))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        assert_eq!(body,
+                   "event: update\ndata: {\"paste_id\":1,\"event\":\"update\",\"version\":2}\n\n\
+                    event: delete\ndata: {\"paste_id\":1,\"event\":\"delete\"}\n\n");
+    }
+
+    #[test]
+    fn test_stream_limits() {
+        let events = PasteEvents::new();
+        let mut streams = (0..MAX_STREAMS_PER_PASTE)
+            .map(|_| events.subscribe(1).unwrap())
+            .collect::<Vec<EventStream>>();
+        assert!(events.subscribe(1).is_none());
+        assert!(events.subscribe(2).is_some());
+
+        streams.pop();
+        assert!(events.subscribe(1).is_some());
+        drop(streams);
+        assert_eq!(events.streams.lock().unwrap().total, 0);
+        assert!(events.streams.lock().unwrap().by_paste.is_empty());
+    }
+
+    #[test]
+    fn test_pad() {
+        for len in 0..CHUNK_SIZE as usize * 2 {
+            let message = format!("{}\n\n", "x".repeat(len));
+            let padded = pad(message.clone());
+            assert_eq!(padded.len() % CHUNK_SIZE as usize, 0);
+            assert!(padded.ends_with(&message));
+        }
+    }
+}
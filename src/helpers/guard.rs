@@ -1,23 +1,33 @@
 use std::marker::PhantomData;
 
-use rocket::Request;
+use rocket::{Request, State};
 use rocket::request::{Outcome, FromRequest};
 use rocket::Outcome::{Success, Failure};
 use rocket::http::Status;
 
 use jwt::{decode, Validation};
 
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use r2d2::PooledConnection;
+use r2d2_diesel::ConnectionManager;
+
 use ENV;
-use services::auth::JwtClaims;
+use DBPool;
+use services::auth::{JwtClaims, IMPERSONATION_SCOPES};
+use services::api_key as api_key_serv;
+use services::permission as permission_serv;
+use services::user as user_serv;
 
 use helpers::error;
-use self::error::Error;
+use self::error::{Error, ErrorKind};
 
 macro_rules! get_claims {
     ($req: expr) => (
         $req.headers()
         .get_one("Authorization")
-        .ok_or((Status::Unauthorized, error::unauthorized("token not found")))
+        .ok_or((Status::Unauthorized,
+                error::unauthorized("token not found").with_kind(ErrorKind::TokenMissing)))
         .and_then(|bearer_token| {
             let mut validation = Validation::default();
             // relax 'exp' validation by 10 seconds, so we can use 'exp' in past
@@ -28,10 +38,13 @@ macro_rules! get_claims {
             decode::<JwtClaims>(&bearer_token.trim_left_matches("Bearer "),
                                 ENV.jwt_secret.as_ref(),
                                 &validation)
-                .or(Err((Status::Unauthorized, error::unauthorized("invalid token"))))
+                .or(Err((Status::Unauthorized,
+                         error::unauthorized("invalid token").with_kind(ErrorKind::TokenInvalid))))
                 .and_then(|data| {
                     if data.claims.is_expired() {
-                        return Err((Status::Unauthorized, error::unauthorized("expired token")));
+                        return Err((Status::Unauthorized,
+                                    error::unauthorized("expired token")
+                                        .with_kind(ErrorKind::TokenExpired)));
                     }
                     Ok(data.claims)
                 })
@@ -39,51 +52,180 @@ macro_rules! get_claims {
     )
 }
 
+// who is behind a bearer token, either a login jwt or an api key
+struct Identity {
+    user_id: i32,
+    username: String,
+    roles: Vec<String>,
+    scopes: Option<Vec<String>>,
+    permissions: Vec<String>,
+    impersonated_by: Option<i32>,
+}
+
+fn bearer_token<'a>(req: &'a Request) -> Option<&'a str> {
+    req.headers()
+        .get_one("Authorization")
+        .map(|bearer_token| bearer_token.trim_left_matches("Bearer "))
+}
+
+fn db_conn(req: &Request)
+           -> Result<PooledConnection<ConnectionManager<PgConnection>>, (Status, Error)> {
+    let pool_error = (Status::InternalServerError,
+                      error::internal_server_error("database connection pool timeout"));
+    let db_pool: State<DBPool> = match State::from_request(req) {
+        Success(db_pool) => db_pool,
+        _ => return Err(pool_error),
+    };
+    db_pool.0.get().or(Err(pool_error))
+}
+
+fn identify(req: &Request) -> Result<Identity, (Status, Error)> {
+    let (user_id, scopes, impersonated_by, from_api_key) = match bearer_token(req) {
+        Some(token) if api_key_serv::is_api_key(token) => {
+            let conn = db_conn(req)?;
+            let key_error = error::unauthorized("invalid api key")
+                .with_kind(ErrorKind::TokenInvalid);
+            api_key_serv::authenticate(token, &conn)
+                .or(Err((Status::Unauthorized, key_error)))
+                .map(|(api_key, user)| (user.id, Some(api_key.scopes), None, true))?
+        }
+        _ => {
+            match get_claims!(req) {
+                Ok(claims) => {
+                    let scopes = claims
+                        .impersonated_by
+                        .map(|_| IMPERSONATION_SCOPES.iter().map(|s| s.to_string()).collect());
+                    (claims.user_id, scopes, claims.impersonated_by, false)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    };
+
+    // tokens outlive deletion, suspension and role changes, so the account
+    // is checked on every request
+    let conn = db_conn(req)?;
+    let deleted = (Status::Unauthorized,
+                   error::unauthorized("account deleted").with_kind(ErrorKind::AccountDeleted));
+    let account = match user_serv::get_account(user_id, &conn) {
+        Ok(account) => account,
+        Err(DieselError::NotFound) => return Err(deleted),
+        Err(_) => {
+            return Err((Status::InternalServerError,
+                        error::internal_server_error("fail to load user")))
+        }
+    };
+    if account.deleted {
+        return Err(deleted);
+    }
+    if account.suspended {
+        return Err((Status::Forbidden,
+                    error::forbidden("account suspended").with_kind(ErrorKind::AccountSuspended)));
+    }
+    // api keys never carry elevated roles
+    let roles = if from_api_key {
+        vec!["user".to_owned()]
+    } else {
+        account.roles
+    };
+    let username = account.username;
+
+    let permissions = permission_serv::get_permissions_by_roles(&roles, &conn)
+        .or(Err((Status::InternalServerError,
+                 error::internal_server_error("fail to load permissions"))))?;
+
+    Ok(Identity {
+           user_id,
+           username,
+           roles,
+           scopes,
+           permissions,
+           impersonated_by,
+       })
+}
+
+/// A permission as listed in the `role_permissions` table, usable as
+/// `UserToken<P>` (or `Require<P>`) request guard.
+pub trait Permission {
+    fn name() -> &'static str;
+}
+
+macro_rules! permissions {
+    ($($perm: ident => $name: expr),*) => (
+        $(
+            pub enum $perm {}
+
+            impl Permission for $perm {
+                fn name() -> &'static str {
+                    $name
+                }
+            }
+        )*
+    )
+}
+
+permissions! {
+    PasteRead => "paste:read",
+    PasteWrite => "paste:write",
+    PasteAdmin => "paste:admin",
+    UserRead => "user:read",
+    UserWrite => "user:write",
+    Admin => "user:admin",
+    Moderation => "moderation"
+}
+
+// any authenticated user
 pub enum User {}
-pub enum Admin {}
 
 pub struct UserToken<Perm> {
     pub user_id: i32,
     pub username: String,
     roles: Vec<String>,
+    // None for login tokens, which are not restricted
+    scopes: Option<Vec<String>>,
+    permissions: Vec<String>,
+    // admin behind an impersonation token
+    pub impersonated_by: Option<i32>,
 
     perm: PhantomData<Perm>,
 }
 
+pub type Require<Perm> = UserToken<Perm>;
+
+fn user_token<Perm>(identity: Identity) -> UserToken<Perm> {
+    UserToken {
+        user_id: identity.user_id,
+        username: identity.username,
+        roles: identity.roles,
+        scopes: identity.scopes,
+        permissions: identity.permissions,
+        impersonated_by: identity.impersonated_by,
+        perm: PhantomData,
+    }
+}
+
 impl<'a, 'r> FromRequest<'a, 'r> for UserToken<User> {
     type Error = Error;
 
     fn from_request(req: &'a Request<'r>) -> Outcome<Self, Self::Error> {
-        match get_claims!(req) {
-            Ok(claims) => {
-                Success(UserToken {
-                            user_id: claims.user_id,
-                            username: claims.username,
-                            roles: claims.roles,
-                            perm: PhantomData,
-                        })
-            }
+        match identify(req) {
+            Ok(identity) => Success(user_token(identity)),
             Err(err) => Failure(err),
         }
     }
 }
 
-impl<'a, 'r> FromRequest<'a, 'r> for UserToken<Admin> {
+impl<'a, 'r, Perm: Permission> FromRequest<'a, 'r> for UserToken<Perm> {
     type Error = Error;
 
     fn from_request(req: &'a Request<'r>) -> Outcome<Self, Self::Error> {
-        match get_claims!(req) {
-            Ok(claims) => {
-                if !claims.roles.contains(&"admin".to_owned()) {
-                    return Failure((Status::Forbidden, error::forbidden("permission denied")));
+        match identify(req) {
+            Ok(identity) => {
+                let token: UserToken<Perm> = user_token(identity);
+                if let Err(err) = token.check_permission(Perm::name()) {
+                    return Failure((Status::Forbidden, err));
                 }
-
-                Success(UserToken {
-                            user_id: claims.user_id,
-                            username: claims.username,
-                            roles: claims.roles,
-                            perm: PhantomData,
-                        })
+                Success(token)
             }
             Err(err) => Failure(err),
         }
@@ -98,4 +240,106 @@ impl<Perm> UserToken<Perm> {
     pub fn has_role(&self, role: &str) -> bool {
         self.roles.contains(&role.to_owned())
     }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .map(|scopes| scopes.contains(&scope.to_owned()))
+            .unwrap_or(true)
+    }
+
+    /// Granted by one of the roles, and within the scopes of an api key.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.check_permission(permission).is_ok()
+    }
+
+    pub fn check_permission(&self, permission: &str) -> Result<(), Error> {
+        if !self.permissions.contains(&permission.to_owned()) {
+            return Err(error::forbidden("permission denied")
+                           .with_kind(ErrorKind::PermissionDenied));
+        }
+        if !self.has_scope(permission) {
+            return Err(error::forbidden("insufficient scope")
+                           .with_kind(ErrorKind::InsufficientScope));
+        }
+        Ok(())
+    }
+}
+
+/// Password of a password protected paste from the `X-Paste-Password`
+/// header, checked by the handler since only it knows the paste.
+pub struct PastePassword(pub Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for PastePassword {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        Success(PastePassword(req.headers().get_one("X-Paste-Password").map(String::from)))
+    }
+}
+
+/// `If-Match` header of conditional updates, see `helpers::etag`.
+pub struct IfMatch(pub Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfMatch {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        Success(IfMatch(req.headers().get_one("If-Match").map(String::from)))
+    }
+}
+
+/// User id behind a valid bearer token or api key, for code outside of
+/// request guards, e.g. fairings.
+pub fn token_user_id(req: &Request) -> Option<i32> {
+    match bearer_token(req) {
+        Some(token) if api_key_serv::is_api_key(token) => {
+            db_conn(req)
+                .ok()
+                .and_then(|conn| api_key_serv::get_user_id(token, &conn).ok())
+        }
+        _ => get_claims!(req).ok().map(|claims| claims.user_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(permissions: Vec<&str>, scopes: Option<Vec<&str>>) -> UserToken<User> {
+        let to_strings = |values: Vec<&str>| -> Vec<String> {
+            values.into_iter().map(String::from).collect()
+        };
+        UserToken {
+            user_id: 1,
+            username: "test".to_string(),
+            roles: vec!["user".to_string()],
+            scopes: scopes.map(&to_strings),
+            permissions: to_strings(permissions),
+            impersonated_by: None,
+            perm: PhantomData,
+        }
+    }
+
+    #[test]
+    fn test_check_permission() {
+        let login_token = token(vec!["paste:read", "paste:write"], None);
+        assert!(login_token.has_permission("paste:write"));
+        let err = login_token.check_permission("moderation").unwrap_err();
+        assert_eq!(err.msg, "permission denied");
+        assert_eq!(err.kind, ErrorKind::PermissionDenied);
+
+        let api_key_token = token(vec!["paste:read", "paste:write"], Some(vec!["paste:read"]));
+        assert!(api_key_token.has_permission("paste:read"));
+        let err = api_key_token.check_permission("paste:write").unwrap_err();
+        assert_eq!(err.msg, "insufficient scope");
+        assert_eq!(err.kind, ErrorKind::InsufficientScope);
+    }
+
+    #[test]
+    fn test_permission_names() {
+        assert_eq!(PasteWrite::name(), "paste:write");
+        assert_eq!(Admin::name(), "user:admin");
+        assert_eq!(Moderation::name(), "moderation");
+    }
 }
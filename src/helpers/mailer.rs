@@ -0,0 +1,186 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+
+use ENV;
+
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub trait Mailer {
+    fn send(&self, mail: &Mail) -> io::Result<()>;
+}
+
+/// Managed mailer, picked by `MAIL_BACKEND`.
+pub struct MailerState(Box<Mailer + Send + Sync>);
+
+impl MailerState {
+    pub fn send(&self, mail: &Mail) -> io::Result<()> {
+        self.0.send(mail)
+    }
+}
+
+pub fn from_env() -> MailerState {
+    match ENV.mail_backend.as_str() {
+        "smtp" => {
+            MailerState(Box::new(SmtpMailer {
+                                     host: ENV.smtp_host.clone(),
+                                     port: ENV.smtp_port,
+                                     from: ENV.mail_from.clone(),
+                                 }))
+        }
+        "file" => MailerState(Box::new(FileMailer { path: PathBuf::from(&ENV.mail_file) })),
+        backend => panic!("unknown MAIL_BACKEND {}, should be smtp or file", backend),
+    }
+}
+
+/// Plain SMTP without authentication or TLS, meant for a local relay.
+pub struct SmtpMailer {
+    pub host: String,
+    pub port: u16,
+    pub from: String,
+}
+
+/// Line breaks in a header value would start new headers, or smtp commands
+/// in an address.
+fn check_header(value: &str) -> io::Result<()> {
+    if value.contains(|c| c == '\r' || c == '\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "line break in mail header"));
+    }
+    Ok(())
+}
+
+impl SmtpMailer {
+    fn expect(reader: &mut BufRead, code: &str) -> io::Result<()> {
+        // multiline replies are "250-...", the last line is "250 ..."
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            if !line.starts_with(code) {
+                return Err(io::Error::new(io::ErrorKind::Other,
+                                          format!("unexpected smtp reply: {}", line.trim())));
+            }
+            if line.len() < 4 || &line[3..4] != "-" {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, mail: &Mail) -> io::Result<()> {
+        check_header(&self.from)?;
+        check_header(&mail.to)?;
+        check_header(&mail.subject)?;
+
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        SmtpMailer::expect(&mut reader, "220")?;
+        write!(stream, "EHLO localhost\r\n")?;
+        SmtpMailer::expect(&mut reader, "250")?;
+        write!(stream, "MAIL FROM:<{}>\r\n", self.from)?;
+        SmtpMailer::expect(&mut reader, "250")?;
+        write!(stream, "RCPT TO:<{}>\r\n", mail.to)?;
+        SmtpMailer::expect(&mut reader, "250")?;
+        write!(stream, "DATA\r\n")?;
+        SmtpMailer::expect(&mut reader, "354")?;
+
+        write!(stream,
+               "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n",
+               self.from,
+               mail.to,
+               mail.subject)?;
+        for line in mail.body.lines() {
+            // dot stuffing, a single "." ends the message
+            if line.starts_with('.') {
+                write!(stream, ".")?;
+            }
+            write!(stream, "{}\r\n", line)?;
+        }
+        write!(stream, ".\r\n")?;
+        SmtpMailer::expect(&mut reader, "250")?;
+
+        write!(stream, "QUIT\r\n")
+    }
+}
+
+/// Appends mails to a file, for development and tests.
+pub struct FileMailer {
+    pub path: PathBuf,
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        write!(file,
+               "To: {}\nSubject: {}\n\n{}\n\n",
+               mail.to,
+               mail.subject,
+               mail.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Read;
+
+    #[test]
+    fn test_file_mailer() {
+        let path = env::temp_dir().join("rocket-pastebin-test-file-mailer.log");
+        let _ = fs::remove_file(&path);
+        let mailer = FileMailer { path: path.clone() };
+        let mail = Mail {
+            to: "test@example.com".to_string(),
+            subject: "subject".to_string(),
+            body: "body".to_string(),
+        };
+
+        mailer.send(&mail).unwrap();
+        mailer.send(&mail).unwrap();
+
+        let mut content = String::new();
+        fs::File::open(&path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content.matches("To: test@example.com\n").count(), 2);
+        assert!(content.contains("Subject: subject\n\nbody\n"));
+    }
+
+    #[test]
+    fn test_smtp_header_injection() {
+        // refused before connecting
+        let mailer = SmtpMailer {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            from: "noreply@example.com".to_string(),
+        };
+        let mail = Mail {
+            to: "test@example.com>\r\nRCPT TO:<victim@example.com".to_string(),
+            subject: "subject".to_string(),
+            body: "body".to_string(),
+        };
+        let err = mailer.send(&mail).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let mail = Mail {
+            to: "test@example.com".to_string(),
+            subject: "subject\nBcc: victim@example.com".to_string(),
+            body: "body".to_string(),
+        };
+        let err = mailer.send(&mail).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -1,6 +1,20 @@
 #[macro_use]
 pub mod db;
 pub mod digest;
+pub mod encryption;
 pub mod env;
 pub mod error;
+pub mod etag;
+pub mod events;
 pub mod guard;
+pub mod mailer;
+pub mod purge;
+pub mod ratelimit;
+pub mod request_id;
+pub mod reseal;
+pub mod secret_scan;
+pub mod throttle;
+pub mod totp;
+pub mod validation;
+pub mod views;
+pub mod webhook;
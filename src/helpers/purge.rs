@@ -0,0 +1,37 @@
+use std::thread;
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+
+use time;
+
+use ENV;
+use services::auth::DAY;
+use services::paste as paste_serv;
+use services::user as user_serv;
+
+const PURGE_INTERVAL_SECS: u64 = 60 * 60;
+
+fn purge(conn: &PgConnection) {
+    let before = time::get_time().sec - ENV.trash_retention_days * DAY;
+    if let Err(err) = paste_serv::purge_deleted_pastes(before, conn) {
+        eprintln!("Fail to purge deleted pastes: {}", err);
+    }
+    if let Err(err) = user_serv::purge_deleted_users(before, conn) {
+        eprintln!("Fail to purge deleted users: {}", err);
+    }
+}
+
+/// Permanently delete soft deleted pastes and users once they're in the
+/// trash for longer than `TRASH_RETENTION_DAYS`, checked hourly.
+pub fn spawn(pool: Pool<ConnectionManager<PgConnection>>) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+                      match pool.get() {
+                          Ok(conn) => purge(&conn),
+                          Err(err) => eprintln!("Fail to purge trash: {}", err),
+                      }
+                      thread::sleep(Duration::from_secs(PURGE_INTERVAL_SECS));
+                  })
+}
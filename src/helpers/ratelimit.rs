@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rocket::{Request, Response, Data};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Method;
+
+use time;
+
+use ENV;
+use helpers::env::RateLimit;
+use helpers::guard;
+
+// requests over the limit are rerouted here, see `controllers::ratelimit`
+pub const RATE_LIMITED_PATH: &str = "/rate-limited";
+// how often buckets which filled up again are dropped
+const SWEEP_INTERVAL_SECS: f64 = 60.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RouteGroup {
+    Auth,
+    Read,
+    Write,
+}
+
+impl RouteGroup {
+    fn classify(method: Method, path: &str) -> RouteGroup {
+        match method {
+            Method::Post if path == "/login" || path.starts_with("/login/") => RouteGroup::Auth,
+            Method::Get | Method::Head | Method::Options => RouteGroup::Read,
+            _ => RouteGroup::Write,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RouteGroup::Auth => "auth",
+            RouteGroup::Read => "read",
+            RouteGroup::Write => "write",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<RouteGroup> {
+        match name {
+            "auth" => Some(RouteGroup::Auth),
+            "read" => Some(RouteGroup::Read),
+            "write" => Some(RouteGroup::Write),
+            _ => None,
+        }
+    }
+
+    fn limit(&self) -> RateLimit {
+        match *self {
+            RouteGroup::Auth => ENV.rate_limit_auth,
+            RouteGroup::Read => ENV.rate_limit_read,
+            RouteGroup::Write => ENV.rate_limit_write,
+        }
+    }
+}
+
+pub struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated_at: f64,
+}
+
+impl Bucket {
+    pub fn new(limit: RateLimit, now: f64) -> Bucket {
+        Bucket {
+            limit,
+            tokens: limit.capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    fn rate(&self) -> f64 {
+        self.limit.capacity as f64 / self.limit.period as f64
+    }
+
+    fn refill(&mut self, now: f64) {
+        let elapsed = (now - self.updated_at).max(0.0);
+        self.tokens = (self.tokens + elapsed * self.rate()).min(self.limit.capacity as f64);
+        self.updated_at = now;
+    }
+
+    /// Full by `now`, so the same as a new bucket.
+    fn is_full(&self, now: f64) -> bool {
+        let elapsed = (now - self.updated_at).max(0.0);
+        self.tokens + elapsed * self.rate() >= self.limit.capacity as f64
+    }
+
+    pub fn take(&mut self, now: f64) -> bool {
+        self.refill(now);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.tokens.floor() as u32
+    }
+
+    /// Seconds until the bucket is full again.
+    pub fn reset_after(&self) -> u32 {
+        ((self.limit.capacity as f64 - self.tokens) / self.rate()).ceil() as u32
+    }
+
+    /// Seconds until the next request is allowed.
+    pub fn retry_after(&self) -> u32 {
+        if self.tokens >= 1.0 {
+            return 0;
+        }
+        ((1.0 - self.tokens) / self.rate()).ceil() as u32
+    }
+}
+
+/// Token bucket rate limiter, keyed by the user id behind the bearer token
+/// or api key, or the client ip for anonymous requests.
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<(RouteGroup, String), Bucket>>,
+    swept_at: Mutex<f64>,
+}
+
+impl RateLimiter {
+    pub fn new() -> RateLimiter {
+        RateLimiter {
+            buckets: Mutex::new(HashMap::new()),
+            swept_at: Mutex::new(time::precise_time_s()),
+        }
+    }
+
+    /// Drop the buckets of clients which stayed away until theirs filled up,
+    /// at most once every `SWEEP_INTERVAL_SECS`.
+    fn sweep(&self, now: f64) {
+        let mut swept_at = self.swept_at.lock().unwrap();
+        if now - *swept_at < SWEEP_INTERVAL_SECS {
+            return;
+        }
+        *swept_at = now;
+        self.buckets.lock().unwrap().retain(|_, bucket| !bucket.is_full(now));
+    }
+
+    fn key(request: &Request) -> String {
+        if let Some(user_id) = guard::token_user_id(request) {
+            return format!("user:{}", user_id);
+        }
+        match request.remote() {
+            Some(addr) => format!("ip:{}", addr.ip()),
+            None => "anonymous".to_string(),
+        }
+    }
+}
+
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate Limiter",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        let group = RouteGroup::classify(request.method(), request.uri().path());
+        let key = RateLimiter::key(request);
+        let now = time::precise_time_s();
+        self.sweep(now);
+
+        let allowed = {
+            let mut buckets = self.buckets.lock().unwrap();
+            buckets
+                .entry((group, key))
+                .or_insert_with(|| Bucket::new(group.limit(), now))
+                .take(now)
+        };
+
+        if !allowed {
+            request.set_method(Method::Get);
+            request.set_uri(format!("{}/{}", RATE_LIMITED_PATH, group.name()));
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let path = request.uri().path();
+        let limited_group = path.trim_left_matches(RATE_LIMITED_PATH)
+            .trim_left_matches('/');
+        let (group, limited) = if path.starts_with(RATE_LIMITED_PATH) {
+            match RouteGroup::from_name(limited_group) {
+                Some(group) => (group, true),
+                None => return,
+            }
+        } else {
+            (RouteGroup::classify(request.method(), path), false)
+        };
+
+        let buckets = self.buckets.lock().unwrap();
+        let bucket = match buckets.get(&(group, RateLimiter::key(request))) {
+            Some(bucket) => bucket,
+            None => return,
+        };
+
+        response.set_raw_header("X-RateLimit-Limit", bucket.limit.capacity.to_string());
+        response.set_raw_header("X-RateLimit-Remaining", bucket.remaining().to_string());
+        response.set_raw_header("X-RateLimit-Reset", bucket.reset_after().to_string());
+        if limited {
+            response.set_raw_header("Retry-After", bucket.retry_after().to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let limit = RateLimit {
+            capacity: 2,
+            period: 10,
+        };
+        let mut bucket = Bucket::new(limit, 0.0);
+
+        assert!(bucket.take(0.0));
+        assert!(bucket.take(0.0));
+        assert_eq!(bucket.remaining(), 0);
+        assert!(!bucket.take(0.0));
+        assert_eq!(bucket.retry_after(), 5);
+        assert_eq!(bucket.reset_after(), 10);
+
+        // one token every 5 seconds
+        assert!(bucket.take(5.0));
+        assert!(!bucket.take(6.0));
+
+        // never refill over capacity
+        assert!(!bucket.is_full(10.0));
+        assert!(bucket.is_full(1000.0));
+        bucket.refill(1000.0);
+        assert_eq!(bucket.remaining(), 2);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let limiter = RateLimiter::new();
+        let limit = RateLimit {
+            capacity: 2,
+            period: 10,
+        };
+        let now = *limiter.swept_at.lock().unwrap();
+        {
+            let mut buckets = limiter.buckets.lock().unwrap();
+            let mut bucket = Bucket::new(limit, now);
+            bucket.take(now);
+            buckets.insert((RouteGroup::Read, "ip:127.0.0.1".to_string()), bucket);
+            buckets.insert((RouteGroup::Read, "ip:127.0.0.2".to_string()), Bucket::new(limit, now));
+        }
+
+        // not before the interval is over
+        limiter.sweep(now + 1.0);
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 2);
+
+        limiter.sweep(now + SWEEP_INTERVAL_SECS);
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_route_group() {
+        assert_eq!(RouteGroup::classify(Method::Post, "/login"), RouteGroup::Auth);
+        assert_eq!(RouteGroup::classify(Method::Post, "/login/mfa"), RouteGroup::Auth);
+        assert_eq!(RouteGroup::classify(Method::Get, "/pastes/1"), RouteGroup::Read);
+        assert_eq!(RouteGroup::classify(Method::Post, "/pastes"), RouteGroup::Write);
+        assert_eq!(RouteGroup::classify(Method::Delete, "/users/1"), RouteGroup::Write);
+        assert_eq!(RouteGroup::from_name("write"), Some(RouteGroup::Write));
+    }
+}
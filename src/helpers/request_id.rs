@@ -0,0 +1,92 @@
+use std::io::Cursor;
+
+use rocket::{Request, Response, Data};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+
+use serde_json;
+
+use helpers::digest;
+use helpers::error::Error;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const REQUEST_ID_LEN: usize = 8;
+const REQUEST_ID_MAX_LEN: usize = 64;
+
+// reuse the id of a proxy in front of us, as long as it's harmless to echo
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= REQUEST_ID_MAX_LEN &&
+    id.chars()
+        .all(|c| match c {
+                 'a'...'z' | 'A'...'Z' | '0'...'9' | '-' | '_' | '.' => true,
+                 _ => false,
+             })
+}
+
+/// Tags every request with an id, returned as `X-Request-Id` and in the
+/// `request_id` of JSON error bodies, so errors can be found in the logs.
+pub struct RequestId;
+
+impl Fairing for RequestId {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Id",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        let valid = request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .map_or(false, is_valid);
+        if !valid {
+            let id = digest::to_hex(&digest::random_bytes(REQUEST_ID_LEN));
+            request.replace_header(Header::new(REQUEST_ID_HEADER, id));
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let id = match request.headers().get_one(REQUEST_ID_HEADER) {
+            Some(id) => id.to_string(),
+            None => return,
+        };
+        response.set_raw_header(REQUEST_ID_HEADER, id.clone());
+
+        let is_json = response
+            .headers()
+            .get_one("Content-Type")
+            .map_or(false, |content_type| content_type.starts_with("application/json"));
+        if response.status().code < 400 || !is_json {
+            return;
+        }
+
+        let body = match response.take_body().and_then(|body| body.into_string()) {
+            Some(body) => body,
+            None => return,
+        };
+        let body = match serde_json::from_str::<Error>(&body) {
+            Ok(mut err) => {
+                err.request_id = Some(id);
+                serde_json::to_string(&err).unwrap_or(body)
+            }
+            // not one of our errors, pass it through
+            Err(_) => body,
+        };
+        response.set_sized_body(Cursor::new(body));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("3f2a9c1e0b7d4a55"));
+        assert!(is_valid("lb-01.req_42"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("id\r\nSet-Cookie: x=y"));
+        assert!(!is_valid(&"a".repeat(REQUEST_ID_MAX_LEN + 1)));
+    }
+}
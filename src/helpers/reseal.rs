@@ -0,0 +1,47 @@
+use std::thread;
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+
+use services::paste as paste_serv;
+
+const RESEAL_BATCH: i64 = 100;
+const RESEAL_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Pastes which can't be opened are reported and left for the next run,
+/// after their key was put back into `DATA_KEYS`.
+fn reseal(conn: &PgConnection) {
+    let mut after_id = 0;
+    loop {
+        match paste_serv::reseal_pastes(after_id, RESEAL_BATCH, conn) {
+            Ok(resealed) => {
+                for id in resealed.failed {
+                    eprintln!("Fail to re-encrypt paste {}: unknown key or corrupt data", id);
+                }
+                match resealed.last_id {
+                    Some(id) => after_id = id,
+                    None => return,
+                }
+            }
+            Err(err) => {
+                eprintln!("Fail to re-encrypt pastes: {}", err);
+                return;
+            }
+        }
+    }
+}
+
+/// Re-encrypt pastes with the active `DATA_KEYS` key in batches, right after
+/// startup and then hourly. After a rotation the old key has to stay in
+/// `DATA_KEYS` until this is done.
+pub fn spawn(pool: Pool<ConnectionManager<PgConnection>>) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+                      match pool.get() {
+                          Ok(conn) => reseal(&conn),
+                          Err(err) => eprintln!("Fail to re-encrypt pastes: {}", err),
+                      }
+                      thread::sleep(Duration::from_secs(RESEAL_INTERVAL_SECS));
+                  })
+}
@@ -0,0 +1,166 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use time;
+
+use helpers::error;
+use self::error::{Error, ErrorKind};
+
+// failures allowed before the first lockout
+pub const MAX_FAILED_ATTEMPTS: i32 = 5;
+const BASE_LOCKOUT: i64 = 30;
+const MAX_LOCKOUT: i64 = 60 * 60;
+// how often forgotten clients are dropped, see `LoginThrottle::sweep`
+const SWEEP_INTERVAL: i64 = 60;
+
+/// Lockout duration in seconds after `failures` consecutive failed logins,
+/// doubling with every failure past `MAX_FAILED_ATTEMPTS`.
+pub fn backoff(failures: i32) -> i64 {
+    if failures < MAX_FAILED_ATTEMPTS {
+        return 0;
+    }
+    let exp = (failures - MAX_FAILED_ATTEMPTS) as u32;
+    if exp >= 8 {
+        return MAX_LOCKOUT;
+    }
+    cmp::min(BASE_LOCKOUT << exp, MAX_LOCKOUT)
+}
+
+pub fn locked_error(locked_until: i64) -> Error {
+    let retry_after = cmp::max(locked_until - time::get_time().sec, 1);
+    error::too_many_requests(&format!("too many failed login attempts, retry in {} seconds",
+                                      retry_after))
+            .with_kind(ErrorKind::AccountLocked)
+            .with_details(json!({"retry_after": retry_after}))
+}
+
+struct Attempts {
+    failures: i32,
+    failed_at: i64,
+    locked_until: i64,
+}
+
+/// In-memory failed login tracking keyed by client ip, usernames are tracked
+/// in the users table instead. Logins don't reset the failures of their ip,
+/// else logging into an account of one's own would clear the guesses made
+/// against others.
+pub struct LoginThrottle {
+    attempts: Mutex<HashMap<String, Attempts>>,
+    swept_at: Mutex<i64>,
+}
+
+impl LoginThrottle {
+    pub fn new() -> LoginThrottle {
+        LoginThrottle {
+            attempts: Mutex::new(HashMap::new()),
+            swept_at: Mutex::new(time::get_time().sec),
+        }
+    }
+
+    /// Forget clients which are no longer locked out and haven't failed for
+    /// as long as the longest lockout, at most once every `SWEEP_INTERVAL`.
+    fn sweep(&self, now: i64) {
+        let mut swept_at = self.swept_at.lock().unwrap();
+        if now - *swept_at < SWEEP_INTERVAL {
+            return;
+        }
+        *swept_at = now;
+        self.attempts
+            .lock()
+            .unwrap()
+            .retain(|_, attempt| {
+                        attempt.locked_until > now || attempt.failed_at + MAX_LOCKOUT > now
+                    });
+    }
+
+    pub fn check(&self, key: &str) -> Result<(), Error> {
+        let attempts = self.attempts.lock().unwrap();
+        match attempts.get(key) {
+            Some(attempt) if attempt.locked_until > time::get_time().sec => {
+                Err(locked_error(attempt.locked_until))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn fail(&self, key: &str) {
+        let now = time::get_time().sec;
+        self.sweep(now);
+
+        let mut attempts = self.attempts.lock().unwrap();
+        let attempt = attempts
+            .entry(key.to_string())
+            .or_insert(Attempts {
+                           failures: 0,
+                           failed_at: now,
+                           locked_until: 0,
+                       });
+        attempt.failures += 1;
+        attempt.failed_at = now;
+        let lockout = backoff(attempt.failures);
+        if lockout > 0 {
+            attempt.locked_until = now + lockout;
+        }
+    }
+
+    pub fn reset(&self, key: &str) {
+        self.attempts.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(0), 0);
+        assert_eq!(backoff(MAX_FAILED_ATTEMPTS - 1), 0);
+        assert_eq!(backoff(MAX_FAILED_ATTEMPTS), BASE_LOCKOUT);
+        assert_eq!(backoff(MAX_FAILED_ATTEMPTS + 1), BASE_LOCKOUT * 2);
+        assert_eq!(backoff(MAX_FAILED_ATTEMPTS + 100), MAX_LOCKOUT);
+    }
+
+    #[test]
+    fn test_login_throttle() {
+        let throttle = LoginThrottle::new();
+        const IP: &str = "127.0.0.1";
+
+        for _ in 0..MAX_FAILED_ATTEMPTS - 1 {
+            throttle.fail(IP);
+        }
+        assert!(throttle.check(IP).is_ok());
+
+        throttle.fail(IP);
+        let err = throttle.check(IP).unwrap_err();
+        assert_eq!(err.code, 429);
+        assert!(throttle.check("127.0.0.2").is_ok());
+
+        throttle.reset(IP);
+        assert!(throttle.check(IP).is_ok());
+    }
+
+    #[test]
+    fn test_sweep() {
+        let throttle = LoginThrottle::new();
+        let now = *throttle.swept_at.lock().unwrap();
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            throttle.fail("127.0.0.1");
+        }
+        throttle.fail("127.0.0.2");
+
+        // failed lately
+        throttle.sweep(now + SWEEP_INTERVAL);
+        assert_eq!(throttle.attempts.lock().unwrap().len(), 2);
+
+        // not before the interval is over
+        let later = now + 2 * MAX_LOCKOUT;
+        *throttle.swept_at.lock().unwrap() = later - 1;
+        throttle.sweep(later);
+        assert_eq!(throttle.attempts.lock().unwrap().len(), 2);
+
+        throttle.sweep(later + SWEEP_INTERVAL);
+        assert!(throttle.attempts.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+use ring::{digest, hmac};
+
+use time;
+
+use helpers::digest::{random_bytes, to_hex};
+
+const SECRET_LEN: usize = 20;
+const RECOVERY_CODE_LEN: usize = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+pub const STEP: i64 = 30;
+const DIGITS: u32 = 6;
+// accepted clock drift in steps
+const SKEW: i64 = 1;
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub fn generate_secret() -> Vec<u8> {
+    random_bytes(SECRET_LEN)
+}
+
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| to_hex(&random_bytes(RECOVERY_CODE_LEN)))
+        .collect()
+}
+
+/// Key uri understood by authenticator apps, usually rendered as QR code.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    format!(concat!("otpauth://totp/{issuer}:{account}",
+                    "?secret={secret}&issuer={issuer}&digits={digits}&period={period}"),
+            issuer = issuer,
+            account = account,
+            secret = base32_encode(secret),
+            digits = DIGITS,
+            period = STEP)
+}
+
+// RFC 4226
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let key = hmac::SigningKey::new(&digest::SHA1, secret);
+    let mut msg = [0u8; 8];
+    for i in 0..8 {
+        msg[i] = (counter >> (8 * (7 - i))) as u8;
+    }
+    let signature = hmac::sign(&key, &msg);
+    let hash = signature.as_ref();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = ((hash[offset] as u32 & 0x7f) << 24) | ((hash[offset + 1] as u32) << 16) |
+                 ((hash[offset + 2] as u32) << 8) | (hash[offset + 3] as u32);
+    binary % 10u32.pow(DIGITS)
+}
+
+// RFC 6238
+fn totp_at(secret: &[u8], timestamp: i64) -> u32 {
+    hotp(secret, (timestamp / STEP) as u64)
+}
+
+/// Time step `code` was generated for, if it is valid at `timestamp`.
+pub fn step_at(secret: &[u8], code: &str, timestamp: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let code = match code.parse::<u32>() {
+        Ok(code) => code,
+        Err(_) => return None,
+    };
+
+    (-SKEW..SKEW + 1)
+        .map(|skew| timestamp / STEP + skew)
+        .find(|&step| totp_at(secret, step * STEP) == code)
+}
+
+pub fn verify_at(secret: &[u8], code: &str, timestamp: i64) -> bool {
+    step_at(secret, code, timestamp).is_some()
+}
+
+/// Code at `timestamp`, zero padded.
+pub fn generate_at(secret: &[u8], timestamp: i64) -> String {
+    format!("{:01$}", totp_at(secret, timestamp), DIGITS as usize)
+}
+
+pub fn generate(secret: &[u8]) -> String {
+    generate_at(secret, time::get_time().sec)
+}
+
+/// Time step of a currently valid `code`, codes should only be accepted
+/// for steps after the last accepted one.
+pub fn step(secret: &[u8], code: &str) -> Option<i64> {
+    step_at(secret, code, time::get_time().sec)
+}
+
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, truncated to 6 digits
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_totp() {
+        assert_eq!(totp_at(SECRET, 59), 287082);
+        assert_eq!(totp_at(SECRET, 1111111109), 81804);
+        assert_eq!(totp_at(SECRET, 2000000000), 279037);
+
+        assert_eq!(verify_at(SECRET, "287082", 59), true);
+        assert_eq!(verify_at(SECRET, "081804", 1111111109), true);
+        // previous step is still accepted
+        assert_eq!(verify_at(SECRET, "287082", 59 + STEP), true);
+        assert_eq!(verify_at(SECRET, "287082", 59 + 2 * STEP), false);
+        assert_eq!(verify_at(SECRET, "28708", 59), false);
+        assert_eq!(verify_at(SECRET, "abcdef", 59), false);
+
+        assert_eq!(step_at(SECRET, "287082", 59), Some(1));
+        assert_eq!(step_at(SECRET, "287082", 59 + STEP), Some(1));
+        assert_eq!(step_at(SECRET, "287082", 59 + 2 * STEP), None);
+    }
+
+    #[test]
+    fn test_base32_encode() {
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_encode(SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|code| code.len() == RECOVERY_CODE_LEN * 2));
+        assert_eq!(generate_secret().len(), SECRET_LEN);
+    }
+}
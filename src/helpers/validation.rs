@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use hyper::Url;
+
+use helpers::error;
+use self::error::Error;
+
+// users.username is VARCHAR(16)
+const USERNAME_MIN_LEN: usize = 3;
+const USERNAME_MAX_LEN: usize = 16;
+const EMAIL_MAX_LEN: usize = 254;
+const PASSWORD_MIN_LEN: usize = 8;
+const PASSWORD_MAX_LEN: usize = 128;
+pub const PASTE_MAX_LEN: usize = 512 * 1024;
+pub const REASON_MAX_LEN: usize = 1000;
+pub const COMMENT_MAX_LEN: usize = 10000;
+pub const DISPLAY_NAME_MAX_LEN: usize = 50;
+pub const BIO_MAX_LEN: usize = 500;
+pub const GROUP_NAME_MAX_LEN: usize = 100;
+pub const ORG_NAME_MAX_LEN: usize = 100;
+// orgs.slug is VARCHAR(32)
+const SLUG_MIN_LEN: usize = 3;
+const SLUG_MAX_LEN: usize = 32;
+const URL_MAX_LEN: usize = 2000;
+// base64url of the largest plaintext plus the 16 byte gcm tag
+pub const CIPHERTEXT_MAX_LEN: usize = ((PASTE_MAX_LEN + 16) * 4 + 2) / 3;
+// supported ciphers of encrypted pastes with the base64url length of their
+// nonce
+pub const CIPHERS: &[(&str, usize)] = &[("aes-256-gcm", 16)];
+
+/// False for loopback, private, link-local (cloud metadata services among
+/// them), multicast and other reserved addresses, which urls given by users
+/// must not point at.
+pub fn is_public_ip(ip: &IpAddr) -> bool {
+    match *ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() ||
+              ip.is_unspecified() || ip.is_broadcast() || ip.is_multicast() ||
+              octets[0] == 0 || octets[0] >= 240 ||
+              // shared address space of carrier grade NAT, 100.64.0.0/10
+              (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4() {
+                return is_public_ip(&IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            // unique local fc00::/7 and link-local fe80::/10
+            !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() ||
+              first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Host of an absolute url, without the brackets of IPv6 addresses.
+pub fn url_host(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| {
+                      url.host_str()
+                          .map(|host| host.trim_matches(|c| c == '[' || c == ']').to_lowercase())
+                  })
+}
+
+/// Only tells private hosts by their name or address, those resolving to
+/// a private address are not caught without a lookup.
+fn is_private_host(host: &str) -> bool {
+    host == "localhost" || host.ends_with(".localhost") ||
+    host.parse::<IpAddr>().map_or(false, |ip| !is_public_ip(&ip))
+}
+
+/// Collects per-field errors of a payload, so all of them are reported at
+/// once as a 422.
+pub struct Validator {
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+impl Validator {
+    pub fn new() -> Validator {
+        Validator { fields: BTreeMap::new() }
+    }
+
+    pub fn check(&mut self, field: &str, valid: bool, msg: &str) -> &mut Validator {
+        if !valid {
+            self.fields
+                .entry(field.to_string())
+                .or_insert_with(Vec::new)
+                .push(msg.to_string());
+        }
+        self
+    }
+
+    pub fn username(&mut self, field: &str, username: &str) -> &mut Validator {
+        let len = username.chars().count();
+        self.check(field,
+                   len >= USERNAME_MIN_LEN && len <= USERNAME_MAX_LEN,
+                   &format!("must be {} to {} characters",
+                            USERNAME_MIN_LEN,
+                            USERNAME_MAX_LEN))
+            .check(field,
+                   username.chars().all(|c| match c {
+                                            'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' => true,
+                                            _ => false,
+                                        }),
+                   "may only contain letters, digits, '_' and '-'")
+    }
+
+    /// Url safe names like org slugs, lowercase so they can't be confused.
+    pub fn slug(&mut self, field: &str, slug: &str) -> &mut Validator {
+        let len = slug.chars().count();
+        self.check(field,
+                   len >= SLUG_MIN_LEN && len <= SLUG_MAX_LEN,
+                   &format!("must be {} to {} characters", SLUG_MIN_LEN, SLUG_MAX_LEN))
+            .check(field,
+                   slug.chars().all(|c| match c {
+                                        'a'...'z' | '0'...'9' | '-' => true,
+                                        _ => false,
+                                    }) && !slug.starts_with('-'),
+                   "may only contain lowercase letters, digits and '-'")
+    }
+
+    pub fn email(&mut self, field: &str, email: &str) -> &mut Validator {
+        self.check(field, is_email(email), "invalid email address")
+            .check(field,
+                   email.len() <= EMAIL_MAX_LEN,
+                   &format!("must be at most {} characters", EMAIL_MAX_LEN))
+    }
+
+    pub fn password(&mut self, field: &str, password: &str, username: &str) -> &mut Validator {
+        let len = password.chars().count();
+        self.check(field,
+                   len >= PASSWORD_MIN_LEN && len <= PASSWORD_MAX_LEN,
+                   &format!("must be {} to {} characters",
+                            PASSWORD_MIN_LEN,
+                            PASSWORD_MAX_LEN))
+            .check(field,
+                   password.to_lowercase() != username.to_lowercase(),
+                   "must not be the same as username")
+    }
+
+    pub fn paste_data(&mut self, field: &str, data: &str) -> &mut Validator {
+        self.check(field, !data.trim().is_empty(), "must not be empty")
+            .check(field,
+                   data.len() <= PASTE_MAX_LEN,
+                   &format!("must be at most {} bytes", PASTE_MAX_LEN))
+    }
+
+    /// Plain pastes are checked by their text, encrypted ones only by the
+    /// shape of the ciphertext, since the server can't read them.
+    pub fn paste(&mut self,
+                 data: &str,
+                 encrypted: bool,
+                 cipher: Option<&str>,
+                 nonce: Option<&str>)
+                 -> &mut Validator {
+        if !encrypted {
+            return self.paste_data("data", data)
+                       .check("cipher", cipher.is_none(), "only allowed for encrypted pastes")
+                       .check("nonce", nonce.is_none(), "only allowed for encrypted pastes");
+        }
+
+        let nonce_len = cipher.and_then(|cipher| {
+                                            CIPHERS
+                                                .iter()
+                                                .find(|&&(name, _)| name == cipher)
+                                                .map(|&(_, nonce_len)| nonce_len)
+                                        });
+        self.check("data", is_base64url(data), "must be base64url encoded ciphertext")
+            .check("data",
+                   data.len() <= CIPHERTEXT_MAX_LEN,
+                   &format!("must be at most {} bytes", CIPHERTEXT_MAX_LEN))
+            .check("cipher",
+                   nonce_len.is_some(),
+                   &format!("must be one of {}",
+                            CIPHERS
+                                .iter()
+                                .map(|&(name, _)| name)
+                                .collect::<Vec<_>>()
+                                .join(", ")))
+            .check("nonce",
+                   nonce.map_or(false, |nonce| {
+                                    is_base64url(nonce) &&
+                                    nonce_len.map_or(true, |len| nonce.len() == len)
+                                }),
+                   "must be a base64url encoded nonce of the cipher")
+    }
+
+    /// Absolute http or https url, e.g. of a webhook.
+    pub fn url(&mut self, field: &str, url: &str) -> &mut Validator {
+        let host = url.splitn(2, "://").nth(1).unwrap_or("");
+        self.check(field,
+                   (url.starts_with("http://") || url.starts_with("https://")) &&
+                   !host.is_empty() && !host.starts_with('/') &&
+                   !url.chars().any(|c| c.is_whitespace() || c.is_control()),
+                   "must be an http or https url")
+            .check(field,
+                   url.len() <= URL_MAX_LEN,
+                   &format!("must be at most {} characters", URL_MAX_LEN))
+            .check(field,
+                   url_host(url).map_or(true, |host| !is_private_host(&host)),
+                   "must not point at a private address")
+    }
+
+    /// Required free text, e.g. the reason of a report.
+    pub fn text(&mut self, field: &str, text: &str, max_len: usize) -> &mut Validator {
+        self.check(field, !text.trim().is_empty(), "must not be empty")
+            .check(field,
+                   text.chars().count() <= max_len,
+                   &format!("must be at most {} characters", max_len))
+    }
+
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+        Err(error::unprocessable_entity("validation failed", self.fields.clone()))
+    }
+}
+
+// Intentionally loose, deliverability is checked by email verification.
+fn is_email(email: &str) -> bool {
+    let mut parts = email.splitn(2, '@');
+    let (local, domain) = match (parts.next(), parts.next()) {
+        (Some(local), Some(domain)) => (local, domain),
+        _ => return false,
+    };
+
+    !local.is_empty() && !domain.contains('@') && domain.contains('.') &&
+    !domain.starts_with('.') && !domain.ends_with('.') &&
+    !email.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+// unpadded, so it fits in forms and url fragments without escaping
+fn is_base64url(data: &str) -> bool {
+    !data.is_empty() &&
+    data.chars().all(|c| match c {
+                         'a'...'z' | 'A'...'Z' | '0'...'9' | '-' | '_' => true,
+                         _ => false,
+                     })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_username() {
+        assert!(Validator::new().username("username", "test_user-1").finish().is_ok());
+
+        let err = Validator::new()
+            .username("username", "a very long username!")
+            .finish()
+            .unwrap_err();
+        assert_eq!(err.code, 422);
+        assert_eq!(err.fields["username"].len(), 2);
+
+        assert!(Validator::new().username("username", "ab").finish().is_err());
+    }
+
+    #[test]
+    fn test_slug() {
+        assert!(Validator::new().slug("slug", "acme-42").finish().is_ok());
+        assert!(Validator::new().slug("slug", "ab").finish().is_err());
+        assert!(Validator::new().slug("slug", "Acme").finish().is_err());
+        assert!(Validator::new().slug("slug", "-acme").finish().is_err());
+        assert!(Validator::new().slug("slug", &"a".repeat(33)).finish().is_err());
+    }
+
+    #[test]
+    fn test_url() {
+        assert!(Validator::new().url("url", "https://chat.example.com/hooks/1").finish().is_ok());
+        assert!(Validator::new().url("url", "http://203.0.113.7:8080").finish().is_ok());
+        assert!(Validator::new().url("url", "http://127.0.0.1:8080").finish().is_err());
+        assert!(Validator::new().url("url", "http://localhost/hook").finish().is_err());
+        assert!(Validator::new().url("url", "http://10.1.2.3/hook").finish().is_err());
+        assert!(Validator::new().url("url", "http://169.254.169.254/latest").finish().is_err());
+        assert!(Validator::new().url("url", "http://[::1]/hook").finish().is_err());
+        assert!(Validator::new().url("url", "http://[::ffff:192.168.0.1]/").finish().is_err());
+        assert!(Validator::new().url("url", "ftp://example.com").finish().is_err());
+        assert!(Validator::new().url("url", "https://").finish().is_err());
+        assert!(Validator::new().url("url", "https:///path").finish().is_err());
+        assert!(Validator::new().url("url", "https://example.com/a b").finish().is_err());
+    }
+
+    #[test]
+    fn test_email() {
+        assert!(is_email("test@example.com"));
+        assert!(is_email("test+tag@sub.example.com"));
+        assert!(!is_email("test"));
+        assert!(!is_email("@example.com"));
+        assert!(!is_email("test@localhost"));
+        assert!(!is_email("test@example.com."));
+        assert!(!is_email("te st@example.com"));
+        assert!(!is_email("test@exa@mple.com"));
+    }
+
+    #[test]
+    fn test_password() {
+        assert!(Validator::new().password("password", "password", "test").finish().is_ok());
+        assert!(Validator::new().password("password", "short", "test").finish().is_err());
+        assert!(Validator::new()
+                    .password("password", "TestUser", "testuser")
+                    .finish()
+                    .is_err());
+    }
+
+    #[test]
+    fn test_encrypted_paste() {
+        let nonce = Some("AAECAwQFBgcICQoL");
+        assert!(Validator::new()
+                    .paste("c2VjcmV0", true, Some("aes-256-gcm"), nonce)
+                    .finish()
+                    .is_ok());
+
+        let err = Validator::new()
+            .paste("not base64!", true, Some("rot13"), Some("AAEC"))
+            .finish()
+            .unwrap_err();
+        assert_eq!(err.fields["data"], vec!["must be base64url encoded ciphertext"]);
+        assert_eq!(err.fields["cipher"], vec!["must be one of aes-256-gcm"]);
+        assert!(!err.fields.contains_key("nonce"));
+
+        let err = Validator::new()
+            .paste("plain text", false, Some("aes-256-gcm"), None)
+            .finish()
+            .unwrap_err();
+        assert_eq!(err.fields["cipher"], vec!["only allowed for encrypted pastes"]);
+        assert!(Validator::new()
+                    .paste("c2VjcmV0", true, Some("aes-256-gcm"), Some("AAEC"))
+                    .finish()
+                    .is_err());
+    }
+
+    #[test]
+    fn test_multiple_fields() {
+        let err = Validator::new()
+            .username("username", "ok_name")
+            .email("email", "wrong")
+            .paste_data("data", " ")
+            .text("reason", &"x".repeat(REASON_MAX_LEN + 1), REASON_MAX_LEN)
+            .finish()
+            .unwrap_err();
+        assert_eq!(err.msg, "validation failed");
+        assert!(!err.fields.contains_key("username"));
+        assert_eq!(err.fields["email"], vec!["invalid email address"]);
+        assert_eq!(err.fields["data"], vec!["must not be empty"]);
+        assert_eq!(err.fields["reason"], vec!["must be at most 1000 characters"]);
+    }
+}
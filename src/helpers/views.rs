@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+
+use services::paste as paste_serv;
+
+const FLUSH_INTERVAL_SECS: u64 = 60;
+
+/// Views of pastes counted in memory, so reading a paste doesn't write to
+/// the database every time. Views since the last flush are lost when the
+/// server stops.
+#[derive(Clone, Default)]
+pub struct ViewCounter {
+    pending: Arc<Mutex<HashMap<i32, i64>>>,
+}
+
+impl ViewCounter {
+    pub fn new() -> ViewCounter {
+        ViewCounter::default()
+    }
+
+    pub fn record(&self, paste_id: i32) {
+        *self.pending.lock().unwrap().entry(paste_id).or_insert(0) += 1;
+    }
+
+    /// Add the counted views to the pastes and return how many pastes got
+    /// some. Views are kept for the next flush if this one fails.
+    pub fn flush(&self, conn: &PgConnection) -> Result<usize, DieselError> {
+        let views = mem::replace(&mut *self.pending.lock().unwrap(), HashMap::new());
+        if views.is_empty() {
+            return Ok(0);
+        }
+        paste_serv::add_views(&views, conn).map_err(|err| {
+            let mut pending = self.pending.lock().unwrap();
+            for (paste_id, count) in views {
+                *pending.entry(paste_id).or_insert(0) += count;
+            }
+            err
+        })
+    }
+}
+
+/// Flush the views counted by `counter` every minute.
+pub fn spawn(counter: ViewCounter,
+             pool: Pool<ConnectionManager<PgConnection>>)
+             -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+                      thread::sleep(Duration::from_secs(FLUSH_INTERVAL_SECS));
+                      let flushed = pool.get()
+                          .map_err(|err| err.to_string())
+                          .and_then(|conn| counter.flush(&conn).map_err(|err| err.to_string()));
+                      if let Err(err) = flushed {
+                          eprintln!("Fail to flush paste views: {}", err);
+                      }
+                  })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    #[test]
+    fn test_flush() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let paste_id = testdata::recreate().paste.id;
+        let counter = ViewCounter::new();
+
+        counter.record(paste_id);
+        counter.record(paste_id);
+        // pastes which are gone by now don't get in the way
+        counter.record(paste_id + 1);
+        assert_eq!(counter.flush(conn), Ok(1));
+        assert!(counter.pending.lock().unwrap().is_empty());
+        assert_eq!(counter.flush(conn), Ok(0));
+
+        counter.record(paste_id);
+        counter.flush(conn).unwrap();
+        assert_eq!(paste_serv::get_paste_by_id(paste_id, conn).unwrap().views, 3);
+    }
+}
@@ -0,0 +1,259 @@
+use std::net::ToSocketAddrs;
+use std::thread;
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+
+use hyper::{Client, Url};
+use hyper::header::{ContentType, Headers, UserAgent};
+use hyper::net::HttpsConnector;
+use hyper_native_tls::NativeTlsClient;
+
+use ring::{digest, hmac};
+
+use time;
+
+use helpers::digest::to_hex;
+use helpers::validation;
+use models::webhook::{Webhook, WebhookDelivery};
+use services::webhook as webhook_serv;
+
+pub const EVENT_HEADER: &str = "X-Pastebin-Event";
+pub const DELIVERY_HEADER: &str = "X-Pastebin-Delivery";
+pub const SIGNATURE_HEADER: &str = "X-Pastebin-Signature";
+const POLL_INTERVAL_SECS: u64 = 5;
+const DELIVERY_BATCH: usize = 20;
+const TIMEOUT_SECS: u64 = 10;
+// longer than an attempt can take with the timeouts above
+const LEASE_SECS: i64 = 60;
+
+/// `sha256=` and the hex HMAC-SHA256 of the body keyed with the webhook
+/// secret, receivers compute the same to check `X-Pastebin-Signature`.
+pub fn signature(secret: &str, body: &str) -> String {
+    let key = hmac::SigningKey::new(&digest::SHA256, secret.as_bytes());
+    format!("sha256={}", to_hex(hmac::sign(&key, body.as_bytes()).as_ref()))
+}
+
+/// Whether every address the host of `url` resolves to is public, see
+/// `validation::is_public_ip`. Checked when a webhook is registered and
+/// again before each delivery, as the host may be pointed elsewhere since.
+pub fn is_public_target(url: &str) -> bool {
+    let port = Url::parse(url)
+        .ok()
+        .and_then(|url| url.port_or_known_default())
+        .unwrap_or(80);
+    validation::url_host(url)
+        .and_then(|host| (host.as_str(), port).to_socket_addrs().ok())
+        .map(|addrs| addrs.collect::<Vec<_>>())
+        .map_or(false, |addrs| {
+            !addrs.is_empty() && addrs.iter().all(|addr| validation::is_public_ip(&addr.ip()))
+        })
+}
+
+pub fn client() -> Client {
+    let tls = NativeTlsClient::new().expect("Fail to initialize tls for webhooks");
+    let mut client = Client::with_connector(HttpsConnector::new(tls));
+    client.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECS)));
+    client.set_write_timeout(Some(Duration::from_secs(TIMEOUT_SECS)));
+    client
+}
+
+/// Status code the receiver answered with.
+fn post(client: &Client, hook: &Webhook, delivery: &WebhookDelivery) -> Result<u16, String> {
+    let mut headers = Headers::new();
+    headers.set(ContentType::json());
+    headers.set(UserAgent("rocket-pastebin-webhook".to_string()));
+    headers.set_raw(EVENT_HEADER, vec![delivery.event.clone().into_bytes()]);
+    headers.set_raw(DELIVERY_HEADER, vec![delivery.id.to_string().into_bytes()]);
+    headers.set_raw(SIGNATURE_HEADER,
+                    vec![signature(&hook.secret, &delivery.payload).into_bytes()]);
+    client
+        .post(hook.url.as_str())
+        .headers(headers)
+        .body(delivery.payload.as_str())
+        .send()
+        .map(|response| response.status.to_u16())
+        .map_err(|err| err.to_string())
+}
+
+/// Attempt a batch of the deliveries which are due and return how many were
+/// attempted. Anything but a 2xx answer is retried, see
+/// `services::webhook::record_failure`.
+pub fn deliver_due(client: &Client, conn: &PgConnection) -> usize {
+    let now = time::get_time().sec;
+    let due = match webhook_serv::get_due_deliveries(now, DELIVERY_BATCH as i64, conn) {
+        Ok(due) => due,
+        Err(err) => {
+            eprintln!("Fail to load webhook deliveries: {}", err);
+            return 0;
+        }
+    };
+
+    let mut attempted = 0;
+    for (delivery, hook) in due {
+        match webhook_serv::claim_delivery(&delivery, now + LEASE_SECS, conn) {
+            Ok(true) => attempted += 1,
+            Ok(false) => continue,
+            Err(err) => {
+                eprintln!("Fail to claim webhook delivery {}: {}", delivery.id, err);
+                continue;
+            }
+        }
+        // global webhooks are registered by admins, who may use internal
+        // receivers
+        let posted = if hook.global || is_public_target(&hook.url) {
+            post(client, &hook, &delivery)
+        } else {
+            Err("url does not resolve to a public address".to_string())
+        };
+        let recorded = match posted {
+            Ok(code) if code >= 200 && code < 300 => {
+                webhook_serv::record_success(delivery.id, code as i32, conn)
+            }
+            Ok(code) => {
+                let error = format!("unexpected status {}", code);
+                webhook_serv::record_failure(&delivery, Some(code as i32), &error, conn)
+            }
+            Err(err) => webhook_serv::record_failure(&delivery, None, &err, conn),
+        };
+        if let Err(err) = recorded {
+            eprintln!("Fail to record webhook delivery {}: {}", delivery.id, err);
+        }
+    }
+    attempted
+}
+
+/// Deliver queued webhook events, polling for due ones every few seconds
+/// and working through backlogs batch by batch.
+pub fn spawn(pool: Pool<ConnectionManager<PgConnection>>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let client = client();
+        loop {
+            match pool.get() {
+                Ok(conn) => while deliver_due(&client, &conn) == DELIVERY_BATCH {},
+                Err(err) => eprintln!("Fail to deliver webhooks: {}", err),
+            }
+            thread::sleep(Duration::from_secs(POLL_INTERVAL_SECS));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use DB_POOL;
+
+    use services::paste::Owner;
+    use services::webhook::Scope;
+    use tests::helpers::testdata;
+
+    // head and body of a request
+    fn read_request(stream: &mut TcpStream) -> (String, String) {
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" || line.is_empty() {
+                break;
+            }
+            head.push_str(&line);
+        }
+        let len = head.lines()
+            .find(|line| line.to_lowercase().starts_with("content-length:"))
+            .and_then(|line| line["content-length:".len()..].trim().parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).unwrap();
+        (head, String::from_utf8(body).unwrap())
+    }
+
+    /// Answer a single request with `status` and hand it back.
+    fn receive(listener: TcpListener,
+               status: &'static str)
+               -> thread::JoinHandle<(String, String)> {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_request(&mut stream);
+            write!(stream,
+                   "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                   status)
+                    .unwrap();
+            request
+        })
+    }
+
+    #[test]
+    fn test_signature() {
+        // RFC 4231, test case 2
+        assert_eq!(signature("Jefe", "what do ya want for nothing?"),
+                   "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn test_is_public_target() {
+        assert!(is_public_target("https://203.0.113.7/hook"));
+        assert!(!is_public_target("http://127.0.0.1:8080/hook"));
+        assert!(!is_public_target("http://[fd00::1]/hook"));
+        assert!(!is_public_target("not a url"));
+    }
+
+    #[test]
+    fn test_deliver_due() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let events = vec![webhook_serv::PASTE_CREATED.to_string()];
+        let owner = Some(Owner::User(user_id));
+
+        // personal webhooks don't reach internal addresses
+        let personal =
+            webhook_serv::create_webhook(user_id, Scope::User, &url, events.clone(), conn)
+                .unwrap();
+        webhook_serv::enqueue(webhook_serv::PASTE_CREATED, owner, json!({"id": 0}), conn).unwrap();
+        assert_eq!(deliver_due(&client(), conn), 1);
+        let delivery = webhook_serv::get_deliveries_by_webhook_id(personal.webhook.id, conn)
+            .unwrap()
+            .remove(0);
+        assert_eq!(delivery.attempts, 1);
+        assert!(delivery.last_error.unwrap().contains("public address"));
+        webhook_serv::delete_webhook(personal.webhook.id, conn).unwrap();
+
+        let created = webhook_serv::create_webhook(user_id, Scope::Global, &url, events, conn)
+            .unwrap();
+        let hook_id = created.webhook.id;
+
+        webhook_serv::enqueue(webhook_serv::PASTE_CREATED, owner, json!({"id": 1}), conn).unwrap();
+        let receiver = receive(listener.try_clone().unwrap(), "204 No Content");
+        assert_eq!(deliver_due(&client(), conn), 1);
+        let (head, body) = receiver.join().unwrap();
+
+        let delivery = webhook_serv::get_deliveries_by_webhook_id(hook_id, conn).unwrap().remove(0);
+        assert_eq!(delivery.status, webhook_serv::DELIVERED);
+        assert_eq!(delivery.response_code, Some(204));
+        assert_eq!(body, delivery.payload);
+        assert!(head.contains(&format!("{}: {}\r\n",
+                                       SIGNATURE_HEADER,
+                                       signature(&created.secret, &body))));
+        assert!(head.contains(&format!("{}: {}\r\n", EVENT_HEADER, delivery.event)));
+
+        // retried later on errors
+        webhook_serv::enqueue(webhook_serv::PASTE_CREATED, owner, json!({"id": 2}), conn).unwrap();
+        let receiver = receive(listener, "500 Internal Server Error");
+        assert_eq!(deliver_due(&client(), conn), 1);
+        receiver.join().unwrap();
+
+        let delivery = webhook_serv::get_deliveries_by_webhook_id(hook_id, conn).unwrap().remove(0);
+        assert_eq!(delivery.status, webhook_serv::PENDING);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.response_code, Some(500));
+        assert!(delivery.next_attempt_at > time::get_time().sec);
+        assert_eq!(deliver_due(&client(), conn), 0);
+    }
+}
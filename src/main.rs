@@ -2,11 +2,17 @@
 #![plugin(rocket_codegen)]
 extern crate rocket;
 extern crate rand;
+extern crate base64;
+extern crate regex;
 #[macro_use]
 extern crate diesel;
 #[macro_use]
 extern crate diesel_codegen;
 extern crate dotenv;
+extern crate fallible_iterator;
+extern crate hyper;
+extern crate hyper_native_tls;
+extern crate postgres;
 extern crate r2d2;
 extern crate r2d2_diesel;
 #[macro_use]
@@ -33,12 +39,25 @@ mod controllers;
 mod tests;
 
 use controllers::auth;
+use controllers::account;
 use controllers::user;
 use controllers::paste;
+use controllers::api_key;
+use controllers::admin;
+use controllers::ratelimit;
+use controllers::report;
+use controllers::group;
+use controllers::share;
+use controllers::org;
+use controllers::webhook;
+use controllers::comment;
+use controllers::star;
 
 lazy_static! {
     pub static ref ENV: helpers::env::Env = helpers::env::load();
     pub static ref DB_POOL: Pool<ConnectionManager<PgConnection>> = helpers::db::create_db_pool();
+    pub static ref PASTE_EVENTS: helpers::events::PasteEvents = helpers::events::PasteEvents::new();
+    pub static ref PASTE_VIEWS: helpers::views::ViewCounter = helpers::views::ViewCounter::new();
 }
 pub struct DBPool(Pool<ConnectionManager<PgConnection>>);
 
@@ -46,21 +65,100 @@ fn rocket() -> rocket::Rocket {
     rocket::ignite()
         .mount("/",
                routes![auth::login,
+                       auth::login_mfa,
+                       account::resend_verification,
+                       account::verify_email,
+                       account::forgot_password,
+                       account::reset_password,
                        user::me,
                        user::get_users,
                        user::create_user,
                        user::get_user_by_id,
+                       user::get_profile,
+                       user::update_profile,
                        user::update_user_by_id,
                        user::delete_user_by_id,
+                       user::delete_user_by_id_with_options,
+                       user::unlock_user_by_id,
+                       user::enroll_mfa,
+                       user::confirm_mfa,
+                       user::reset_mfa_by_id,
                        paste::get_pastes,
                        paste::create_paste,
                        paste::get_paste_by_id,
+                       paste::unlock_paste,
+                       paste::get_paste_events,
                        paste::update_paste_by_id,
                        paste::delete_paste_by_id,
-                       paste::get_pastes_by_user_id])
+                       paste::get_pastes_by_user_id,
+                       paste::get_public_pastes_by_user_id,
+                       paste::get_public_pastes_by_user_id_paged,
+                       paste::get_trash,
+                       paste::restore_paste,
+                       paste::transfer_paste,
+                       api_key::create_api_key,
+                       api_key::get_api_keys,
+                       api_key::revoke_api_key,
+                       admin::grant_role,
+                       admin::revoke_role,
+                       admin::suspend_user,
+                       admin::unsuspend_user,
+                       admin::impersonate_user,
+                       admin::get_audit_events_with_filter,
+                       admin::get_audit_events,
+                       ratelimit::rate_limited,
+                       report::report_paste,
+                       report::get_open_reports,
+                       report::resolve_report,
+                       report::dismiss_report,
+                       report::unhide_paste,
+                       group::create_group,
+                       group::get_my_groups,
+                       group::delete_group,
+                       group::get_members,
+                       group::add_member,
+                       group::remove_member,
+                       share::share_paste,
+                       share::get_shares,
+                       share::unshare_paste,
+                       share::get_shared_pastes,
+                       org::create_org,
+                       org::delete_org,
+                       org::get_my_orgs,
+                       org::get_org_pastes,
+                       org::get_members,
+                       org::invite,
+                       org::get_my_invitations,
+                       org::accept_invitation,
+                       org::decline_invitation,
+                       org::set_role,
+                       org::remove_member,
+                       webhook::create_webhook,
+                       webhook::create_global_webhook,
+                       webhook::get_my_webhooks,
+                       webhook::delete_webhook,
+                       webhook::get_deliveries,
+                       comment::get_comments,
+                       comment::create_comment,
+                       comment::update_comment,
+                       comment::delete_comment,
+                       star::star_paste,
+                       star::unstar_paste,
+                       star::get_my_stars])
         .manage(DBPool(DB_POOL.clone()))
+        .manage(helpers::throttle::LoginThrottle::new())
+        .manage(helpers::mailer::from_env())
+        .manage(PASTE_EVENTS.clone())
+        .manage(PASTE_VIEWS.clone())
+        .attach(helpers::request_id::RequestId)
+        .attach(helpers::ratelimit::RateLimiter::new())
 }
 
 pub fn main() {
+    helpers::purge::spawn(DB_POOL.clone());
+    helpers::reseal::spawn(DB_POOL.clone());
+    helpers::events::spawn(PASTE_EVENTS.clone());
+    helpers::webhook::spawn(DB_POOL.clone());
+    helpers::views::spawn(PASTE_VIEWS.clone(), DB_POOL.clone());
     rocket().launch();
 }
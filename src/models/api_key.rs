@@ -0,0 +1,29 @@
+// This is required for NewApiKey
+use models::schema::api_keys;
+use models::user::User;
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, Debug)]
+#[belongs_to(User)]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub key_digest: Vec<u8>,
+    pub scopes: Vec<String>,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name="api_keys"]
+pub struct NewApiKey<'a> {
+    pub user_id: i32,
+    pub name: &'a str,
+    pub prefix: &'a str,
+    pub key_digest: Vec<u8>,
+    pub scopes: Vec<String>,
+    pub created_at: i64,
+}
@@ -0,0 +1,28 @@
+// This is required for NewAuditEvent
+use models::schema::audit_events;
+
+#[derive(Queryable, Debug)]
+pub struct AuditEvent {
+    pub id: i32,
+    pub actor_id: Option<i32>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<i32>,
+    pub ip: Option<String>,
+    pub created_at: i64,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Insertable)]
+#[table_name="audit_events"]
+pub struct NewAuditEvent<'a> {
+    pub actor_id: Option<i32>,
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_id: Option<i32>,
+    pub ip: Option<&'a str>,
+    pub created_at: i64,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
@@ -0,0 +1,31 @@
+// This is required for NewComment
+use models::schema::comments;
+use models::paste::Paste;
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[belongs_to(Paste)]
+pub struct Comment {
+    pub id: i32,
+    pub paste_id: i32,
+    pub user_id: i32,
+    pub body: String,
+    // inclusive range of 1-based lines, both None for the whole paste
+    pub line_start: Option<i32>,
+    pub line_end: Option<i32>,
+    // the paste version the lines refer to
+    pub version: Option<i32>,
+    pub created_at: i64,
+    pub updated_at: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name="comments"]
+pub struct NewComment<'a> {
+    pub paste_id: i32,
+    pub user_id: i32,
+    pub body: &'a str,
+    pub line_start: Option<i32>,
+    pub line_end: Option<i32>,
+    pub version: Option<i32>,
+    pub created_at: i64,
+}
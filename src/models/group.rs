@@ -0,0 +1,36 @@
+// This is required for NewGroup and NewGroupMember
+use models::schema::{groups, group_members};
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[has_many(group_members, foreign_key="group_id")]
+pub struct Group {
+    pub id: i32,
+    pub owner_id: i32,
+    pub name: String,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="groups"]
+pub struct NewGroup<'a> {
+    pub owner_id: i32,
+    pub name: &'a str,
+    pub created_at: i64,
+}
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[belongs_to(Group)]
+pub struct GroupMember {
+    pub id: i32,
+    pub group_id: i32,
+    pub user_id: i32,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="group_members"]
+pub struct NewGroupMember {
+    pub group_id: i32,
+    pub user_id: i32,
+    pub created_at: i64,
+}
@@ -4,3 +4,13 @@ pub mod schema {
 
 pub mod user;
 pub mod paste;
+pub mod api_key;
+pub mod permission;
+pub mod report;
+pub mod audit_event;
+pub mod group;
+pub mod paste_share;
+pub mod org;
+pub mod webhook;
+pub mod comment;
+pub mod star;
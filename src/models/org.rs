@@ -0,0 +1,60 @@
+// This is required for NewOrg, NewOrgMember and NewOrgInvitation
+use models::schema::{orgs, org_members, org_invitations};
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[has_many(org_members, foreign_key="org_id")]
+#[has_many(org_invitations, foreign_key="org_id")]
+pub struct Org {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="orgs"]
+pub struct NewOrg<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+    pub created_at: i64,
+}
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[belongs_to(Org)]
+pub struct OrgMember {
+    pub id: i32,
+    pub org_id: i32,
+    pub user_id: i32,
+    pub role: String,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="org_members"]
+pub struct NewOrgMember<'a> {
+    pub org_id: i32,
+    pub user_id: i32,
+    pub role: &'a str,
+    pub created_at: i64,
+}
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[belongs_to(Org)]
+pub struct OrgInvitation {
+    pub id: i32,
+    pub org_id: i32,
+    pub user_id: i32,
+    pub role: String,
+    pub invited_by: Option<i32>,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="org_invitations"]
+pub struct NewOrgInvitation<'a> {
+    pub org_id: i32,
+    pub user_id: i32,
+    pub role: &'a str,
+    pub invited_by: Option<i32>,
+    pub created_at: i64,
+}
@@ -1,18 +1,79 @@
 // This is required for NewPaste
 use models::schema::pastes;
+use models::schema::reports;
+use models::schema::paste_shares;
+use models::schema::comments;
+use models::schema::stars;
 use models::user::User;
 
-#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug, FromForm)]
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
 #[belongs_to(User)]
+#[has_many(reports, foreign_key="paste_id")]
+#[has_many(paste_shares, foreign_key="paste_id")]
+#[has_many(comments, foreign_key="paste_id")]
+#[has_many(stars, foreign_key="paste_id")]
 pub struct Paste {
     pub id: i32,
     pub user_id: i32,
     pub data: String,
+    pub deleted_at: Option<i64>,
+    pub hidden_by_moderator: bool,
+    pub hidden_reason: Option<String>,
+    // data is ciphertext, see `helpers::validation::Validator::paste`
+    pub encrypted: bool,
+    pub cipher: Option<String>,
+    pub nonce: Option<String>,
+    // always None on pastes handed out by `services::paste`, which opens data
+    #[serde(skip_serializing, skip_deserializing)]
+    pub data_key_id: Option<String>,
+    // see `services::paste::verify_password`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub password_digest: Option<String>,
+    // the owner instead of the author in user_id, see `services::paste::Owner`
+    pub org_id: Option<i32>,
+    // bumped by every update, sent as `ETag`, see `helpers::etag`
+    pub version: i32,
+    // reads, counted in memory and flushed by `helpers::views` now and then
+    pub views: i64,
 }
 
-#[derive(Insertable, FromForm)]
-#[table_name="pastes"]
+#[derive(FromForm)]
 pub struct NewPaste {
     pub user_id: i32,
     pub data: String,
+    pub encrypted: bool,
+    pub cipher: Option<String>,
+    pub nonce: Option<String>,
+    // required to read the paste unless the reader owns it
+    pub password: Option<String>,
+    // created in the org, which the author has to be a member of
+    pub org_id: Option<i32>,
+}
+
+/// `NewPaste` as stored, with data sealed by `data_key_id` and the password
+/// digested.
+#[derive(Insertable)]
+#[table_name="pastes"]
+pub struct NewSealedPaste<'a> {
+    pub user_id: i32,
+    pub data: &'a str,
+    pub encrypted: bool,
+    pub cipher: Option<&'a str>,
+    pub nonce: Option<&'a str>,
+    pub data_key_id: Option<&'a str>,
+    pub password_digest: Option<&'a str>,
+    pub org_id: Option<i32>,
+}
+
+/// Form of `PUT /users/<user_id>/pastes/<id>`.
+#[derive(FromForm)]
+pub struct UpdatePaste {
+    pub id: i32,
+    pub user_id: i32,
+    pub data: String,
+    pub encrypted: bool,
+    pub cipher: Option<String>,
+    pub nonce: Option<String>,
+    // the version the change is based on, unless sent as `If-Match`
+    pub version: Option<i32>,
 }
@@ -0,0 +1,27 @@
+// This is required for NewPasteShare
+use models::schema::paste_shares;
+use models::paste::Paste;
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[belongs_to(Paste)]
+pub struct PasteShare {
+    pub id: i32,
+    pub paste_id: i32,
+    // exactly one of user_id and group_id is set
+    pub user_id: Option<i32>,
+    pub group_id: Option<i32>,
+    pub access: String,
+    pub created_by: Option<i32>,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="paste_shares"]
+pub struct NewPasteShare<'a> {
+    pub paste_id: i32,
+    pub user_id: Option<i32>,
+    pub group_id: Option<i32>,
+    pub access: &'a str,
+    pub created_by: Option<i32>,
+    pub created_at: i64,
+}
@@ -0,0 +1,6 @@
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+pub struct RolePermission {
+    pub id: i32,
+    pub role: String,
+    pub permission: String,
+}
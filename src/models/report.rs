@@ -0,0 +1,25 @@
+// This is required for NewReport
+use models::schema::reports;
+use models::paste::Paste;
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, Debug)]
+#[belongs_to(Paste)]
+pub struct Report {
+    pub id: i32,
+    pub paste_id: i32,
+    pub reporter_id: i32,
+    pub reason: String,
+    pub status: String,
+    pub created_at: i64,
+    pub resolved_by: Option<i32>,
+    pub resolved_at: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name="reports"]
+pub struct NewReport<'a> {
+    pub paste_id: i32,
+    pub reporter_id: i32,
+    pub reason: &'a str,
+    pub created_at: i64,
+}
@@ -0,0 +1,20 @@
+// This is required for NewStar
+use models::schema::stars;
+use models::paste::Paste;
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[belongs_to(Paste)]
+pub struct Star {
+    pub id: i32,
+    pub user_id: i32,
+    pub paste_id: i32,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="stars"]
+pub struct NewStar {
+    pub user_id: i32,
+    pub paste_id: i32,
+    pub created_at: i64,
+}
@@ -1,6 +1,7 @@
 // This is required for NewUser
 use models::schema::users;
 use models::schema::pastes;
+use models::schema::api_keys;
 
 #[derive(Insertable)]
 #[table_name="users"]
@@ -8,14 +9,33 @@ pub struct NewUser<'a> {
     pub username: &'a str,
     pub email: &'a str,
     pub password_digest: Vec<u8>,
+    pub created_at: Option<i64>,
 }
 
 #[derive(Queryable, Associations, Identifiable)]
 #[has_many(pastes, foreign_key="user_id")]
+#[has_many(api_keys, foreign_key="user_id")]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub email: String,
     pub password_digest: Vec<u8>,
     pub roles: Vec<String>,
+    pub last_login_at: Option<i64>,
+    pub failed_attempts: i32,
+    pub locked_until: Option<i64>,
+    pub totp_secret: Option<Vec<u8>>,
+    pub totp_enabled: bool,
+    pub totp_recovery_codes: Vec<Vec<u8>>,
+    pub email_verified: bool,
+    pub deleted_at: Option<i64>,
+    pub suspended_at: Option<i64>,
+    pub suspended_reason: Option<String>,
+    // public profile, see `services::user::Profile`
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    // None for users from before join dates were recorded
+    pub created_at: Option<i64>,
+    pub totp_last_step: Option<i64>,
 }
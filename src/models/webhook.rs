@@ -0,0 +1,57 @@
+// This is required for NewWebhook and NewWebhookDelivery
+use models::schema::{webhooks, webhook_deliveries};
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[has_many(webhook_deliveries, foreign_key="webhook_id")]
+pub struct Webhook {
+    pub id: i32,
+    pub user_id: i32,
+    pub org_id: Option<i32>,
+    pub global: bool,
+    pub url: String,
+    // only returned once, on creation, see `services::webhook::CreatedWebhook`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name="webhooks"]
+pub struct NewWebhook<'a> {
+    pub user_id: i32,
+    pub org_id: Option<i32>,
+    pub global: bool,
+    pub url: &'a str,
+    pub secret: &'a str,
+    pub events: Vec<String>,
+    pub created_at: i64,
+}
+
+#[derive(Queryable, Associations, Identifiable, Serialize, Deserialize, PartialEq, Debug)]
+#[belongs_to(Webhook)]
+#[table_name="webhook_deliveries"]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: i64,
+    pub response_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub delivered_at: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name="webhook_deliveries"]
+pub struct NewWebhookDelivery<'a> {
+    pub webhook_id: i32,
+    pub event: &'a str,
+    pub payload: &'a str,
+    pub status: &'a str,
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+}
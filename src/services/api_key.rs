@@ -0,0 +1,145 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use time;
+
+use helpers::digest;
+use models::schema;
+use models::api_key::{ApiKey, NewApiKey};
+use models::user::User as ModelUser;
+use services::user::User;
+
+use self::schema::api_keys;
+use self::schema::users;
+
+pub const KEY_PREFIX: &str = "pb_";
+const KEY_LEN: usize = 24;
+// "pb_" and the first 8 characters of the random part
+const DISPLAY_PREFIX_LEN: usize = 11;
+
+/// Scopes which can be granted to an api key. Anything that changes the
+/// account itself, `user:write`, needs a login token.
+pub const SCOPES: [&str; 3] = ["paste:read", "paste:write", "user:read"];
+
+#[derive(Serialize, Deserialize)]
+pub struct CreatedApiKey {
+    pub api_key: ApiKey,
+    // only returned once, on creation
+    pub key: String,
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(KEY_PREFIX)
+}
+
+pub fn create_api_key(user_id: i32,
+                      name: &str,
+                      scopes: Vec<String>,
+                      conn: &PgConnection)
+                      -> Result<CreatedApiKey, DieselError> {
+    let key = format!("{}{}", KEY_PREFIX, digest::to_hex(&digest::random_bytes(KEY_LEN)));
+    let new_api_key = NewApiKey {
+        user_id,
+        name,
+        prefix: &key[..DISPLAY_PREFIX_LEN],
+        key_digest: digest::sha256(key.as_bytes()),
+        scopes,
+        created_at: time::get_time().sec,
+    };
+
+    diesel::insert(&new_api_key)
+        .into(api_keys::table)
+        .get_result::<ApiKey>(conn)
+        .and_then(|api_key| Ok(CreatedApiKey { api_key, key }))
+}
+
+pub fn get_api_keys_by_user_id(user_id: i32,
+                               conn: &PgConnection)
+                               -> Result<Vec<ApiKey>, DieselError> {
+    api_keys::table
+        .filter(api_keys::user_id.eq(user_id))
+        .order(api_keys::id)
+        .load::<ApiKey>(conn)
+}
+
+pub fn revoke_api_key(id: i32, user_id: i32, conn: &PgConnection) -> Result<ApiKey, DieselError> {
+    diesel::update(api_keys::table
+                       .filter(api_keys::id.eq(id))
+                       .filter(api_keys::user_id.eq(user_id)))
+            .set(api_keys::revoked_at.eq(Some(time::get_time().sec)))
+            .get_result::<ApiKey>(conn)
+}
+
+/// Owner of an unrevoked api key, without tracking its usage.
+pub fn get_user_id(key: &str, conn: &PgConnection) -> Result<i32, DieselError> {
+    api_keys::table
+        .filter(api_keys::key_digest.eq(digest::sha256(key.as_bytes())))
+        .filter(api_keys::revoked_at.is_null())
+        .select(api_keys::user_id)
+        .first::<i32>(conn)
+}
+
+/// Look up an unrevoked api key and its owner, and track its usage.
+pub fn authenticate(key: &str, conn: &PgConnection) -> Result<(ApiKey, User), DieselError> {
+    let key_digest = digest::sha256(key.as_bytes());
+    let api_key = diesel::update(api_keys::table
+                                     .filter(api_keys::key_digest.eq(key_digest))
+                                     .filter(api_keys::revoked_at.is_null()))
+            .set(api_keys::last_used_at.eq(Some(time::get_time().sec)))
+            .get_result::<ApiKey>(conn)?;
+
+    users::table
+        .find(api_key.user_id)
+        .filter(users::deleted_at.is_null())
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok((api_key, user.into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use tests::helpers::testdata;
+
+    use DB_POOL;
+
+    #[test]
+    fn test_create_api_key() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+
+        let created = create_api_key(user_id, "ci", vec!["paste:write".to_string()], conn)
+            .unwrap();
+        assert!(is_api_key(&created.key));
+        assert!(created.key.starts_with(&created.api_key.prefix));
+        assert_eq!(created.api_key.name, "ci");
+        assert_eq!(created.api_key.scopes, vec!["paste:write"]);
+        assert_eq!(created.api_key.last_used_at, None);
+
+        let api_keys = get_api_keys_by_user_id(user_id, conn).unwrap();
+        assert_eq!(api_keys.len(), 1);
+        assert_eq!(api_keys[0].id, created.api_key.id);
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+        let created = create_api_key(user_id, "ci", vec![], conn).unwrap();
+
+        let (api_key, user) = authenticate(&created.key, conn).unwrap();
+        assert_eq!(user.id, user_id);
+        assert!(api_key.last_used_at.is_some());
+
+        assert!(authenticate("pb_wrongkey", conn).is_err());
+
+        // revoked key of another user is not found
+        assert!(revoke_api_key(api_key.id, -1, conn).is_err());
+        let revoked = revoke_api_key(api_key.id, user_id, conn).unwrap();
+        assert!(revoked.revoked_at.is_some());
+        assert!(authenticate(&created.key, conn).is_err());
+    }
+}
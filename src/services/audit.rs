@@ -0,0 +1,257 @@
+use std::cmp;
+use std::convert::From;
+use std::net::SocketAddr;
+
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde_json::{self, Map, Value};
+
+use time;
+
+use models::schema;
+use models::audit_event::{AuditEvent as ModelAuditEvent, NewAuditEvent};
+
+use self::schema::audit_events;
+
+pub const LOGIN: &str = "login";
+pub const LOGIN_FAILED: &str = "login_failed";
+pub const USER_CREATE: &str = "user.create";
+pub const USER_UPDATE: &str = "user.update";
+pub const USER_DELETE: &str = "user.delete";
+pub const USER_SUSPEND: &str = "user.suspend";
+pub const USER_UNSUSPEND: &str = "user.unsuspend";
+pub const USER_IMPERSONATE: &str = "user.impersonate";
+pub const ROLE_GRANT: &str = "role.grant";
+pub const ROLE_REVOKE: &str = "role.revoke";
+pub const PASTE_UPDATE: &str = "paste.update";
+pub const PASTE_DELETE: &str = "paste.delete";
+pub const PASTE_SHARE: &str = "paste.share";
+pub const PASTE_UNSHARE: &str = "paste.unshare";
+pub const PASTE_TRANSFER: &str = "paste.transfer";
+
+pub const TARGET_USER: &str = "user";
+pub const TARGET_PASTE: &str = "paste";
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+/// Fields only recorded as changed, without their values.
+const REDACTED_FIELDS: &[&str] = &["email"];
+const REDACTED: &str = "[redacted]";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuditEvent {
+    pub id: i32,
+    pub actor_id: Option<i32>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<i32>,
+    pub ip: Option<String>,
+    pub created_at: i64,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl From<ModelAuditEvent> for AuditEvent {
+    fn from(event: ModelAuditEvent) -> AuditEvent {
+        let parse = |json: Option<String>| json.and_then(|json| serde_json::from_str(&json).ok());
+        AuditEvent {
+            id: event.id,
+            actor_id: event.actor_id,
+            action: event.action,
+            target_type: event.target_type,
+            target_id: event.target_id,
+            ip: event.ip,
+            created_at: event.created_at,
+            before: parse(event.before),
+            after: parse(event.after),
+        }
+    }
+}
+
+/// An action to record, `before` and `after` are snapshots of the target.
+pub struct Event<'a> {
+    pub actor_id: Option<i32>,
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_id: Option<i32>,
+    pub ip: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl<'a> Event<'a> {
+    pub fn new(action: &'a str, target_type: &'a str, target_id: Option<i32>) -> Event<'a> {
+        Event {
+            actor_id: None,
+            action: action,
+            target_type: target_type,
+            target_id: target_id,
+            ip: None,
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn actor(mut self, actor_id: i32) -> Event<'a> {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn remote(mut self, remote: Option<SocketAddr>) -> Event<'a> {
+        self.ip = remote.map(|addr| addr.ip().to_string());
+        self
+    }
+
+    pub fn before(mut self, before: Value) -> Event<'a> {
+        self.before = Some(before);
+        self
+    }
+
+    pub fn after(mut self, after: Value) -> Event<'a> {
+        self.after = Some(after);
+        self
+    }
+
+    /// `before` and `after` snapshots of only the fields which changed, and
+    /// the id of the target, from two versions of it serialized to objects.
+    pub fn changes(self, before: &Value, after: &Value) -> Event<'a> {
+        let (mut old, mut new) = (Map::new(), Map::new());
+        if let (Some(before), Some(after)) = (before.as_object(), after.as_object()) {
+            for (field, value) in after {
+                let previous = before.get(field).cloned().unwrap_or(Value::Null);
+                if field != "id" && &previous == value {
+                    continue;
+                }
+                if REDACTED_FIELDS.contains(&field.as_str()) {
+                    old.insert(field.clone(), json!(REDACTED));
+                    new.insert(field.clone(), json!(REDACTED));
+                } else {
+                    old.insert(field.clone(), previous);
+                    new.insert(field.clone(), value.clone());
+                }
+            }
+        }
+        self.before(Value::Object(old)).after(Value::Object(new))
+    }
+}
+
+/// Events can't be changed or deleted afterwards, the table rejects it.
+pub fn record(event: Event, conn: &PgConnection) -> Result<AuditEvent, DieselError> {
+    let new_event = NewAuditEvent {
+        actor_id: event.actor_id,
+        action: event.action,
+        target_type: event.target_type,
+        target_id: event.target_id,
+        ip: event.ip.as_ref().map(|ip| ip.as_str()),
+        created_at: time::get_time().sec,
+        before: event.before.map(|before| before.to_string()),
+        after: event.after.map(|after| after.to_string()),
+    };
+
+    diesel::insert(&new_event)
+        .into(audit_events::table)
+        .get_result::<ModelAuditEvent>(conn)
+        .and_then(|event| Ok(event.into()))
+}
+
+#[derive(FromForm, Default)]
+pub struct AuditFilter {
+    pub actor_id: Option<i32>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<i32>,
+    // unix timestamps, inclusive
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Matching events, newest first.
+pub fn get_events(filter: &AuditFilter,
+                  conn: &PgConnection)
+                  -> Result<Vec<AuditEvent>, DieselError> {
+    let mut query = audit_events::table.into_boxed();
+    if let Some(actor_id) = filter.actor_id {
+        query = query.filter(audit_events::actor_id.eq(actor_id));
+    }
+    if let Some(ref action) = filter.action {
+        query = query.filter(audit_events::action.eq(action.clone()));
+    }
+    if let Some(ref target_type) = filter.target_type {
+        query = query.filter(audit_events::target_type.eq(target_type.clone()));
+    }
+    if let Some(target_id) = filter.target_id {
+        query = query.filter(audit_events::target_id.eq(target_id));
+    }
+    if let Some(since) = filter.since {
+        query = query.filter(audit_events::created_at.ge(since));
+    }
+    if let Some(until) = filter.until {
+        query = query.filter(audit_events::created_at.le(until));
+    }
+    let limit = cmp::max(cmp::min(filter.limit.unwrap_or(DEFAULT_LIMIT), MAX_LIMIT), 1);
+
+    query
+        .order(audit_events::id.desc())
+        .limit(limit)
+        .load::<ModelAuditEvent>(conn)
+        .and_then(|events| Ok(events.into_iter().map(|event| event.into()).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    #[test]
+    fn test_record_and_filter() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+
+        let id = data.user.id;
+        let event = record(Event::new(USER_UPDATE, TARGET_USER, Some(id))
+                               .actor(id)
+                               .remote(Some("127.0.0.1:8000".parse().unwrap()))
+                               .changes(&json!({"id": id, "username": "a", "email": "a@a.com"}),
+                                        &json!({"id": id, "username": "b", "email": "b@a.com"})),
+                           conn)
+                .unwrap();
+        assert_eq!(event.before,
+                   Some(json!({"id": id, "username": "a", "email": REDACTED})));
+        assert_eq!(event.after,
+                   Some(json!({"id": id, "username": "b", "email": REDACTED})));
+
+        record(Event::new(LOGIN_FAILED, TARGET_USER, Some(data.user.id)), conn).unwrap();
+
+        let filter = AuditFilter {
+            target_type: Some(TARGET_USER.to_string()),
+            target_id: Some(id),
+            ..AuditFilter::default()
+        };
+        let events = get_events(&filter, conn).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].action, LOGIN_FAILED);
+
+        let filter = AuditFilter {
+            actor_id: Some(data.user.id),
+            action: Some(USER_UPDATE.to_string()),
+            ..AuditFilter::default()
+        };
+        let events = get_events(&filter, conn).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, event.id);
+        assert_eq!(events[0].ip, Some("127.0.0.1".to_string()));
+
+        // append only
+        assert!(diesel::delete(audit_events::table.find(event.id))
+                    .execute(conn)
+                    .is_err());
+    }
+}
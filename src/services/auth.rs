@@ -1,4 +1,4 @@
-use jwt::{encode, Header};
+use jwt::{encode, decode, Header, Validation};
 use jwt::errors;
 
 use time;
@@ -6,7 +6,13 @@ use time;
 use services::user::User;
 use ENV;
 
-const DAY: i64 = 60 * 60 * 24;
+pub const HOUR: i64 = 60 * 60;
+pub const DAY: i64 = HOUR * 24;
+const MFA_PENDING_TTL: i64 = 60 * 5;
+const IMPERSONATION_TTL: i64 = 60 * 15;
+
+/// Impersonation tokens are for looking, not for changing anything.
+pub const IMPERSONATION_SCOPES: [&str; 2] = ["paste:read", "user:read"];
 
 #[derive(Serialize, Deserialize)]
 pub struct JwtClaims {
@@ -17,6 +23,9 @@ pub struct JwtClaims {
     pub user_id: i32,
     pub username: String,
     pub roles: Vec<String>,
+    // id of the admin, for impersonation tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<i32>,
 }
 
 impl JwtClaims {
@@ -34,8 +43,181 @@ pub fn login(user: &User) -> Result<String, errors::Error> {
         user_id: user.id,
         username: user.username.clone(),
         roles: user.roles.clone(),
+        impersonated_by: None,
+    };
+    let jwt_secret: &str = ENV.jwt_secret.as_ref();
+
+    encode(&Header::default(), &claims, jwt_secret.as_bytes())
+}
+
+/// Short-lived token acting as `user` on behalf of an admin, restricted to
+/// `IMPERSONATION_SCOPES`.
+pub fn impersonate(user: &User, admin_id: i32) -> Result<String, errors::Error> {
+    let now = time::get_time().sec;
+    let claims = JwtClaims {
+        iat: now,
+        exp: now + IMPERSONATION_TTL,
+        user_id: user.id,
+        username: user.username.clone(),
+        roles: user.roles.clone(),
+        impersonated_by: Some(admin_id),
+    };
+    let jwt_secret: &str = ENV.jwt_secret.as_ref();
+
+    encode(&Header::default(), &claims, jwt_secret.as_bytes())
+}
+
+// Issued after a correct password when 2fa is enabled, can only be exchanged
+// for a real token at `/login/mfa`. It lacks `username` and `roles`, so it
+// never decodes as `JwtClaims`.
+#[derive(Serialize, Deserialize)]
+pub struct MfaClaims {
+    pub iat: i64,
+    pub exp: i64,
+    pub user_id: i32,
+    pub mfa_pending: bool,
+}
+
+pub fn mfa_pending(user: &User) -> Result<String, errors::Error> {
+    let now = time::get_time().sec;
+    let claims = MfaClaims {
+        iat: now,
+        exp: now + MFA_PENDING_TTL,
+        user_id: user.id,
+        mfa_pending: true,
+    };
+    let jwt_secret: &str = ENV.jwt_secret.as_ref();
+
+    encode(&Header::default(), &claims, jwt_secret.as_bytes())
+}
+
+/// User id of a valid, unexpired mfa pending token.
+pub fn verify_mfa_pending(token: &str) -> Option<i32> {
+    let jwt_secret: &str = ENV.jwt_secret.as_ref();
+    decode::<MfaClaims>(token, jwt_secret.as_bytes(), &Validation::default())
+        .ok()
+        .and_then(|data| {
+            let now = time::get_time().sec;
+            if !data.claims.mfa_pending || now >= data.claims.exp {
+                return None;
+            }
+            Some(data.claims.user_id)
+        })
+}
+
+pub const VERIFY_EMAIL: &str = "verify_email";
+pub const RESET_PASSWORD: &str = "reset_password";
+
+// Signed tokens sent by mail. `fingerprint` binds the token to the current
+// email or password, so it stops working once either changes.
+#[derive(Serialize, Deserialize)]
+pub struct MailClaims {
+    pub iat: i64,
+    pub exp: i64,
+    pub user_id: i32,
+    pub purpose: String,
+    pub fingerprint: String,
+}
+
+fn mail_fingerprint(user: &User, purpose: &str) -> String {
+    if purpose == RESET_PASSWORD {
+        user.password_fingerprint()
+    } else {
+        user.email.clone()
+    }
+}
+
+pub fn mail_token(user: &User, purpose: &str) -> Result<String, errors::Error> {
+    let now = time::get_time().sec;
+    let ttl = if purpose == RESET_PASSWORD { HOUR } else { 2 * DAY };
+    let claims = MailClaims {
+        iat: now,
+        exp: now + ttl,
+        user_id: user.id,
+        purpose: purpose.to_string(),
+        fingerprint: mail_fingerprint(user, purpose),
     };
     let jwt_secret: &str = ENV.jwt_secret.as_ref();
 
     encode(&Header::default(), &claims, jwt_secret.as_bytes())
 }
+
+/// User id of a valid, unexpired mail token for `purpose`.
+pub fn decode_mail_token(token: &str, purpose: &str) -> Option<i32> {
+    let jwt_secret: &str = ENV.jwt_secret.as_ref();
+    decode::<MailClaims>(token, jwt_secret.as_bytes(), &Validation::default())
+        .ok()
+        .and_then(|data| {
+            let now = time::get_time().sec;
+            if data.claims.purpose != purpose || now >= data.claims.exp {
+                return None;
+            }
+            Some(data.claims.user_id)
+        })
+}
+
+/// Check the token still matches the current state of `user`, see
+/// `MailClaims`.
+pub fn verify_mail_token(token: &str, purpose: &str, user: &User) -> bool {
+    let jwt_secret: &str = ENV.jwt_secret.as_ref();
+    decode::<MailClaims>(token, jwt_secret.as_bytes(), &Validation::default())
+        .map(|data| {
+                 data.claims.user_id == user.id && data.claims.purpose == purpose &&
+                 data.claims.fingerprint == mail_fingerprint(user, purpose)
+             })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use services::user::update_user;
+    use services::user::UpdatedUser;
+    use tests::helpers::testdata;
+
+    use DB_POOL;
+
+    #[test]
+    fn test_mail_token() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user = testdata::recreate().user;
+
+        let token = mail_token(&user, RESET_PASSWORD).unwrap();
+        assert_eq!(decode_mail_token(&token, RESET_PASSWORD), Some(user.id));
+        assert_eq!(decode_mail_token(&token, VERIFY_EMAIL), None);
+        assert_eq!(verify_mail_token(&token, RESET_PASSWORD, &user), true);
+        assert_eq!(verify_mail_token(&token, VERIFY_EMAIL, &user), false);
+
+        // token is bound to the current password
+        let updated_user = UpdatedUser {
+            username: None,
+            email: None,
+            password: Some("newpassword"),
+        };
+        let user = update_user(user.id, &updated_user, conn).unwrap();
+        assert_eq!(verify_mail_token(&token, RESET_PASSWORD, &user), false);
+
+        assert_eq!(decode_mail_token("wrongtoken", VERIFY_EMAIL), None);
+    }
+
+    #[test]
+    fn test_impersonate() {
+        let user = testdata::recreate().user;
+        let token = impersonate(&user, -1).unwrap();
+        let jwt_secret: &str = ENV.jwt_secret.as_ref();
+        let claims = decode::<JwtClaims>(&token, jwt_secret.as_bytes(), &Validation::default())
+            .unwrap()
+            .claims;
+        assert_eq!(claims.user_id, user.id);
+        assert_eq!(claims.impersonated_by, Some(-1));
+        assert_eq!(claims.exp - claims.iat, IMPERSONATION_TTL);
+
+        let token = login(&user).unwrap();
+        let claims = decode::<JwtClaims>(&token, jwt_secret.as_bytes(), &Validation::default())
+            .unwrap()
+            .claims;
+        assert_eq!(claims.impersonated_by, None);
+    }
+}
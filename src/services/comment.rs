@@ -0,0 +1,122 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use time;
+
+use models::schema;
+use models::comment::{Comment, NewComment};
+
+use self::schema::comments;
+
+pub fn create_comment(new_comment: &NewComment,
+                      conn: &PgConnection)
+                      -> Result<Comment, DieselError> {
+    diesel::insert(new_comment)
+        .into(comments::table)
+        .get_result::<Comment>(conn)
+}
+
+/// Oldest first, the way a review thread is read.
+pub fn get_comments_by_paste_id(paste_id: i32,
+                                conn: &PgConnection)
+                                -> Result<Vec<Comment>, DieselError> {
+    comments::table
+        .filter(comments::paste_id.eq(paste_id))
+        .order(comments::id)
+        .load::<Comment>(conn)
+}
+
+pub fn get_comment(paste_id: i32, id: i32, conn: &PgConnection) -> Result<Comment, DieselError> {
+    comments::table
+        .filter(comments::id.eq(id))
+        .filter(comments::paste_id.eq(paste_id))
+        .get_result::<Comment>(conn)
+}
+
+/// Only the body changes, the lines a comment is about stay where they are.
+pub fn update_comment(id: i32, body: &str, conn: &PgConnection) -> Result<Comment, DieselError> {
+    diesel::update(comments::table.find(id))
+        .set((comments::body.eq(body), comments::updated_at.eq(Some(time::get_time().sec))))
+        .get_result::<Comment>(conn)
+}
+
+pub fn delete_comment(id: i32, conn: &PgConnection) -> Result<usize, DieselError> {
+    diesel::delete(comments::table.find(id)).execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use services::paste as paste_serv;
+    use tests::helpers::testdata;
+
+    fn new_comment<'a>(paste_id: i32, user_id: i32, body: &'a str) -> NewComment<'a> {
+        NewComment {
+            paste_id,
+            user_id,
+            body,
+            line_start: None,
+            line_end: None,
+            version: None,
+            created_at: time::get_time().sec,
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let (paste_id, user_id) = (data.paste.id, data.user_alt.id);
+
+        let first = create_comment(&new_comment(paste_id, user_id, "looks good"), conn).unwrap();
+        let line = NewComment {
+            line_start: Some(1),
+            line_end: Some(1),
+            version: Some(1),
+            ..new_comment(paste_id, user_id, "typo here")
+        };
+        let second = create_comment(&line, conn).unwrap();
+        assert_eq!(second.line_start, Some(1));
+        assert_eq!(get_comments_by_paste_id(paste_id, conn), Ok(vec![first, second]));
+
+        // both ends of a line range or none
+        let open_range = NewComment {
+            line_start: Some(2),
+            ..new_comment(paste_id, user_id, "from here on")
+        };
+        assert!(create_comment(&open_range, conn).is_err());
+
+        let comment_id = get_comments_by_paste_id(paste_id, conn).unwrap()[1].id;
+        let edited = update_comment(comment_id, "typo fixed", conn).unwrap();
+        assert_eq!(edited.body, "typo fixed");
+        assert_eq!(edited.line_end, Some(1));
+        assert!(edited.updated_at.is_some());
+        assert_eq!(get_comment(paste_id, comment_id, conn), Ok(edited));
+        assert_eq!(get_comment(paste_id + 1, comment_id, conn),
+                   Err(DieselError::NotFound));
+
+        assert_eq!(delete_comment(comment_id, conn), Ok(1));
+        assert_eq!(get_comments_by_paste_id(paste_id, conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_purged_with_paste() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let paste_id = data.paste.id;
+        create_comment(&new_comment(paste_id, data.user.id, "note to self"), conn).unwrap();
+
+        // still there while the paste is in the trash
+        paste_serv::delete_paste(paste_id, conn).unwrap();
+        assert_eq!(get_comments_by_paste_id(paste_id, conn).unwrap().len(), 1);
+
+        paste_serv::purge_deleted_pastes(time::get_time().sec + 1, conn).unwrap();
+        assert!(get_comments_by_paste_id(paste_id, conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use time;
+
+use models::schema;
+use models::group::{Group, NewGroup, GroupMember, NewGroupMember};
+
+use self::schema::groups;
+use self::schema::group_members;
+
+/// The owner is the first member of a new group.
+pub fn create_group(owner_id: i32, name: &str, conn: &PgConnection) -> Result<Group, DieselError> {
+    conn.transaction(|| {
+        let new_group = NewGroup {
+            owner_id,
+            name,
+            created_at: time::get_time().sec,
+        };
+        let group = diesel::insert(&new_group)
+            .into(groups::table)
+            .get_result::<Group>(conn)?;
+        add_member(group.id, owner_id, conn)?;
+        Ok(group)
+    })
+}
+
+pub fn get_group_by_id(id: i32, conn: &PgConnection) -> Result<Group, DieselError> {
+    groups::table.find(id).get_result::<Group>(conn)
+}
+
+/// Groups the user is a member of, including the ones they own.
+pub fn get_groups_by_user_id(user_id: i32, conn: &PgConnection) -> Result<Vec<Group>, DieselError> {
+    let group_ids = get_group_ids_by_user_id(user_id, conn)?;
+    groups::table
+        .filter(groups::id.eq_any(group_ids))
+        .order(groups::id)
+        .load::<Group>(conn)
+}
+
+pub fn get_group_ids_by_user_id(user_id: i32,
+                                conn: &PgConnection)
+                                -> Result<Vec<i32>, DieselError> {
+    group_members::table
+        .filter(group_members::user_id.eq(user_id))
+        .select(group_members::group_id)
+        .load::<i32>(conn)
+}
+
+pub fn is_member(group_id: i32, user_id: i32, conn: &PgConnection) -> Result<bool, DieselError> {
+    group_members::table
+        .filter(group_members::group_id.eq(group_id))
+        .filter(group_members::user_id.eq(user_id))
+        .count()
+        .get_result::<i64>(conn)
+        .map(|count| count > 0)
+}
+
+pub fn get_members(group_id: i32, conn: &PgConnection) -> Result<Vec<GroupMember>, DieselError> {
+    group_members::table
+        .filter(group_members::group_id.eq(group_id))
+        .order(group_members::id)
+        .load::<GroupMember>(conn)
+}
+
+pub fn add_member(group_id: i32,
+                  user_id: i32,
+                  conn: &PgConnection)
+                  -> Result<GroupMember, DieselError> {
+    let new_member = NewGroupMember {
+        group_id,
+        user_id,
+        created_at: time::get_time().sec,
+    };
+    diesel::insert(&new_member)
+        .into(group_members::table)
+        .get_result::<GroupMember>(conn)
+}
+
+pub fn remove_member(group_id: i32,
+                     user_id: i32,
+                     conn: &PgConnection)
+                     -> Result<usize, DieselError> {
+    diesel::delete(group_members::table
+                       .filter(group_members::group_id.eq(group_id))
+                       .filter(group_members::user_id.eq(user_id)))
+            .execute(conn)
+}
+
+/// Pastes shared with the group aren't shared with its members anymore.
+pub fn delete_group(id: i32, conn: &PgConnection) -> Result<usize, DieselError> {
+    diesel::delete(groups::table.find(id)).execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    #[test]
+    fn test_group_members() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+
+        let group = create_group(data.user.id, "team", conn).unwrap();
+        assert!(is_member(group.id, data.user.id, conn).unwrap());
+        assert!(!is_member(group.id, data.user_alt.id, conn).unwrap());
+        // names are unique per owner
+        assert!(create_group(data.user.id, "team", conn).is_err());
+        assert!(create_group(data.user_alt.id, "team", conn).is_ok());
+
+        add_member(group.id, data.user_alt.id, conn).unwrap();
+        assert!(add_member(group.id, data.user_alt.id, conn).is_err());
+        assert_eq!(get_members(group.id, conn).unwrap().len(), 2);
+        assert_eq!(get_groups_by_user_id(data.user_alt.id, conn).unwrap().len(), 2);
+
+        assert_eq!(remove_member(group.id, data.user_alt.id, conn), Ok(1));
+        assert_eq!(get_groups_by_user_id(data.user_alt.id, conn).unwrap().len(), 1);
+
+        assert_eq!(delete_group(group.id, conn), Ok(1));
+        assert_eq!(get_group_by_id(group.id, conn), Err(DieselError::NotFound));
+        assert!(get_group_ids_by_user_id(data.user.id, conn).unwrap().is_empty());
+    }
+}
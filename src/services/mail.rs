@@ -0,0 +1,25 @@
+use helpers::mailer::Mail;
+use services::user::User;
+
+pub fn verification_mail(user: &User, token: &str) -> Mail {
+    Mail {
+        to: user.email.clone(),
+        subject: "Verify your email".to_string(),
+        body: format!("Hi {},\n\nconfirm your email by posting the token below to \
+                       /users/verify.\n\ntoken: {}\n",
+                      user.username,
+                      token),
+    }
+}
+
+pub fn password_reset_mail(user: &User, token: &str) -> Mail {
+    Mail {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!("Hi {},\n\nreset your password by posting the token below to \
+                       /password/reset within an hour. Ignore this mail if you didn't ask \
+                       for it.\n\ntoken: {}\n",
+                      user.username,
+                      token),
+    }
+}
@@ -0,0 +1,164 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use helpers::digest;
+use helpers::totp;
+use models::schema;
+use models::user::User as ModelUser;
+use services::user::User;
+
+use self::schema::users;
+
+const ISSUER: &str = "rocket-pastebin";
+
+// recovery codes are salted with the user id, which unlike the username
+// never changes
+fn recovery_salt(user_id: i32) -> String {
+    format!("recovery:{}", user_id)
+}
+
+/// Accept a totp code for `user` once, a code seen before is refused even
+/// while it is still valid.
+fn accept_code(user: &User, code: &str, conn: &PgConnection) -> Result<bool, DieselError> {
+    let step = match user.totp_secret.as_ref().and_then(|secret| totp::step(secret, code)) {
+        Some(step) => step,
+        None => return Ok(false),
+    };
+    diesel::update(users::table
+                       .find(user.id)
+                       .filter(users::totp_last_step
+                                   .is_null()
+                                   .or(users::totp_last_step.lt(step))))
+            .set(users::totp_last_step.eq(Some(step)))
+            .execute(conn)
+            .map(|updated| updated == 1)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Enrollment {
+    pub otpauth_uri: String,
+    pub secret: String,
+    pub recovery_codes: Vec<String>,
+}
+
+/// Generate a new totp secret and recovery codes, 2fa stays disabled until
+/// the first code is confirmed.
+pub fn enroll(user_id: i32, conn: &PgConnection) -> Result<Enrollment, DieselError> {
+    let user = users::table.find(user_id).get_result::<ModelUser>(conn)?;
+    let secret = totp::generate_secret();
+    let recovery_codes = totp::generate_recovery_codes();
+    let recovery_digests = recovery_codes
+        .iter()
+        .map(|code| digest::digest_password(&recovery_salt(user.id), code))
+        .collect::<Vec<Vec<u8>>>();
+
+    diesel::update(users::table.find(user_id))
+        .set((users::totp_secret.eq(Some(secret.clone())),
+              users::totp_enabled.eq(false),
+              users::totp_recovery_codes.eq(recovery_digests),
+              users::totp_last_step.eq(None::<i64>)))
+        .execute(conn)?;
+
+    Ok(Enrollment {
+           otpauth_uri: totp::otpauth_uri(ISSUER, &user.username, &secret),
+           secret: totp::base32_encode(&secret),
+           recovery_codes,
+       })
+}
+
+/// Enable 2fa if `code` matches the pending secret.
+pub fn confirm(user: &User, code: &str, conn: &PgConnection) -> Result<bool, DieselError> {
+    if !accept_code(user, code, conn)? {
+        return Ok(false);
+    }
+
+    diesel::update(users::table.find(user.id))
+        .set(users::totp_enabled.eq(true))
+        .execute(conn)
+        .and(Ok(true))
+}
+
+/// Check a totp code or a recovery code, recovery codes can only be used once.
+pub fn verify(user: &User, code: &str, conn: &PgConnection) -> Result<bool, DieselError> {
+    if !user.totp_enabled {
+        return Ok(false);
+    }
+    if accept_code(user, code, conn)? {
+        return Ok(true);
+    }
+
+    let code = code.trim();
+    let position = user.totp_recovery_codes
+        .iter()
+        .position(|recovery| digest::verify_password(&recovery_salt(user.id), recovery, code));
+    match position {
+        Some(position) => {
+            let mut remaining = user.totp_recovery_codes.clone();
+            remaining.remove(position);
+            diesel::update(users::table.find(user.id))
+                .set(users::totp_recovery_codes.eq(remaining))
+                .execute(conn)
+                .and(Ok(true))
+        }
+        None => Ok(false),
+    }
+}
+
+pub fn reset(user_id: i32, conn: &PgConnection) -> Result<User, DieselError> {
+    diesel::update(users::table.find(user_id))
+        .set((users::totp_secret.eq(None::<Vec<u8>>),
+              users::totp_enabled.eq(false),
+              users::totp_recovery_codes.eq(Vec::<Vec<u8>>::new()),
+              users::totp_last_step.eq(None::<i64>)))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use services::user::get_user_by_id;
+    use tests::helpers::testdata;
+
+    use DB_POOL;
+
+    #[test]
+    fn test_enroll_and_verify() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+
+        let enrollment = enroll(user_id, conn).unwrap();
+        assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+        assert!(enrollment.otpauth_uri.contains(&enrollment.secret));
+
+        // not enabled before confirmation
+        let user = get_user_by_id(user_id, conn).unwrap();
+        assert_eq!(user.totp_enabled, false);
+        assert_eq!(verify(&user, &enrollment.recovery_codes[0], conn), Ok(false));
+
+        assert_eq!(confirm(&user, "000000x", conn), Ok(false));
+        let code = totp::generate(user.totp_secret.as_ref().unwrap());
+        assert_eq!(confirm(&user, &code, conn), Ok(true));
+
+        let user = get_user_by_id(user_id, conn).unwrap();
+        assert_eq!(user.totp_enabled, true);
+        // a code is only accepted once
+        assert_eq!(verify(&user, &code, conn), Ok(false));
+
+        // recovery code is consumed
+        let recovery_code = &enrollment.recovery_codes[0];
+        assert_eq!(verify(&user, recovery_code, conn), Ok(true));
+        let user = get_user_by_id(user_id, conn).unwrap();
+        assert_eq!(verify(&user, recovery_code, conn), Ok(false));
+        assert_eq!(user.totp_recovery_codes.len(),
+                   enrollment.recovery_codes.len() - 1);
+
+        let user = reset(user_id, conn).unwrap();
+        assert_eq!(user.totp_enabled, false);
+        assert_eq!(user.totp_secret, None);
+    }
+}
@@ -1,3 +1,15 @@
 pub mod user;
 pub mod paste;
 pub mod auth;
+pub mod mfa;
+pub mod api_key;
+pub mod permission;
+pub mod mail;
+pub mod report;
+pub mod audit;
+pub mod group;
+pub mod share;
+pub mod org;
+pub mod webhook;
+pub mod comment;
+pub mod star;
@@ -0,0 +1,305 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use time;
+
+use helpers::error::{self, Error};
+use models::schema;
+use models::org::*;
+use models::paste::Paste;
+use services::paste as paste_serv;
+
+use self::schema::orgs;
+use self::schema::org_members;
+use self::schema::org_invitations;
+use self::schema::pastes;
+
+pub const OWNER: &str = "owner";
+pub const MAINTAINER: &str = "maintainer";
+pub const MEMBER: &str = "member";
+pub const ROLES: [&str; 3] = [OWNER, MAINTAINER, MEMBER];
+
+/// Owners and maintainers manage the pastes and members of an org, members
+/// only their own pastes.
+pub fn can_manage(role: &str) -> bool {
+    role == OWNER || role == MAINTAINER
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DeleteError {
+    Database(DieselError),
+    /// The org still owns these pastes.
+    Blocked(Vec<i32>),
+}
+
+impl From<DieselError> for DeleteError {
+    fn from(err: DieselError) -> DeleteError {
+        DeleteError::Database(err)
+    }
+}
+
+impl From<DeleteError> for Error {
+    fn from(err: DeleteError) -> Error {
+        match err {
+            DeleteError::Database(err) => Error::from(err),
+            DeleteError::Blocked(paste_ids) => {
+                error::conflict("org still owns pastes").with_details(json!({"pastes": paste_ids}))
+            }
+        }
+    }
+}
+
+/// The creator becomes the first owner.
+pub fn create_org(slug: &str,
+                  name: &str,
+                  owner_id: i32,
+                  conn: &PgConnection)
+                  -> Result<Org, DieselError> {
+    conn.transaction(|| {
+        let new_org = NewOrg {
+            slug,
+            name,
+            created_at: time::get_time().sec,
+        };
+        let org = diesel::insert(&new_org)
+            .into(orgs::table)
+            .get_result::<Org>(conn)?;
+        add_member(org.id, owner_id, OWNER, conn)?;
+        Ok(org)
+    })
+}
+
+pub fn get_org_by_slug(slug: &str, conn: &PgConnection) -> Result<Org, DieselError> {
+    orgs::table
+        .filter(orgs::slug.eq(slug))
+        .get_result::<Org>(conn)
+}
+
+pub fn get_orgs_by_user_id(user_id: i32, conn: &PgConnection) -> Result<Vec<Org>, DieselError> {
+    let org_ids = org_members::table
+        .filter(org_members::user_id.eq(user_id))
+        .select(org_members::org_id)
+        .load::<i32>(conn)?;
+    orgs::table
+        .filter(orgs::id.eq_any(org_ids))
+        .order(orgs::id)
+        .load::<Org>(conn)
+}
+
+/// Role of the user in the org, `None` for non-members.
+pub fn get_role(org_id: i32,
+                user_id: i32,
+                conn: &PgConnection)
+                -> Result<Option<String>, DieselError> {
+    org_members::table
+        .filter(org_members::org_id.eq(org_id))
+        .filter(org_members::user_id.eq(user_id))
+        .select(org_members::role)
+        .load::<String>(conn)
+        .map(|roles| roles.into_iter().next())
+}
+
+pub fn get_members(org_id: i32, conn: &PgConnection) -> Result<Vec<OrgMember>, DieselError> {
+    org_members::table
+        .filter(org_members::org_id.eq(org_id))
+        .order(org_members::id)
+        .load::<OrgMember>(conn)
+}
+
+fn add_member(org_id: i32,
+              user_id: i32,
+              role: &str,
+              conn: &PgConnection)
+              -> Result<OrgMember, DieselError> {
+    let new_member = NewOrgMember {
+        org_id,
+        user_id,
+        role,
+        created_at: time::get_time().sec,
+    };
+    diesel::insert(&new_member)
+        .into(org_members::table)
+        .get_result::<OrgMember>(conn)
+}
+
+/// Whether the user is the only owner left, who can't leave or be demoted.
+pub fn is_last_owner(org_id: i32, user_id: i32, conn: &PgConnection) -> Result<bool, DieselError> {
+    let owners = org_members::table
+        .filter(org_members::org_id.eq(org_id))
+        .filter(org_members::role.eq(OWNER))
+        .select(org_members::user_id)
+        .load::<i32>(conn)?;
+    Ok(owners == vec![user_id])
+}
+
+pub fn set_role(org_id: i32,
+                user_id: i32,
+                role: &str,
+                conn: &PgConnection)
+                -> Result<OrgMember, DieselError> {
+    diesel::update(org_members::table
+                       .filter(org_members::org_id.eq(org_id))
+                       .filter(org_members::user_id.eq(user_id)))
+            .set(org_members::role.eq(role))
+            .get_result::<OrgMember>(conn)
+}
+
+/// Pastes the user authored in the org stay with the org.
+pub fn remove_member(org_id: i32, user_id: i32, conn: &PgConnection) -> Result<usize, DieselError> {
+    diesel::delete(org_members::table
+                       .filter(org_members::org_id.eq(org_id))
+                       .filter(org_members::user_id.eq(user_id)))
+            .execute(conn)
+}
+
+pub fn invite(org_id: i32,
+              user_id: i32,
+              role: &str,
+              invited_by: i32,
+              conn: &PgConnection)
+              -> Result<OrgInvitation, DieselError> {
+    let new_invitation = NewOrgInvitation {
+        org_id,
+        user_id,
+        role,
+        invited_by: Some(invited_by),
+        created_at: time::get_time().sec,
+    };
+    diesel::insert(&new_invitation)
+        .into(org_invitations::table)
+        .get_result::<OrgInvitation>(conn)
+}
+
+pub fn get_invitations_by_user_id(user_id: i32,
+                                  conn: &PgConnection)
+                                  -> Result<Vec<OrgInvitation>, DieselError> {
+    org_invitations::table
+        .filter(org_invitations::user_id.eq(user_id))
+        .order(org_invitations::id)
+        .load::<OrgInvitation>(conn)
+}
+
+/// Join the org with the invited role, only the invited user can.
+pub fn accept_invitation(id: i32,
+                         user_id: i32,
+                         conn: &PgConnection)
+                         -> Result<OrgMember, DieselError> {
+    conn.transaction(|| {
+        let invitation = org_invitations::table
+            .find(id)
+            .filter(org_invitations::user_id.eq(user_id))
+            .get_result::<OrgInvitation>(conn)?;
+        diesel::delete(org_invitations::table.find(id)).execute(conn)?;
+        add_member(invitation.org_id, user_id, &invitation.role, conn)
+    })
+}
+
+pub fn decline_invitation(id: i32,
+                          user_id: i32,
+                          conn: &PgConnection)
+                          -> Result<usize, DieselError> {
+    diesel::delete(org_invitations::table
+                       .find(id)
+                       .filter(org_invitations::user_id.eq(user_id)))
+            .execute(conn)
+}
+
+/// Refused while the org owns pastes, they have to be deleted or handed
+/// back first. Pastes in the trash go along with the org, as do members,
+/// invitations and webhooks.
+pub fn delete_org(id: i32, conn: &PgConnection) -> Result<usize, DeleteError> {
+    conn.transaction(|| {
+        // new pastes check the org row for their foreign key, so locking it
+        // keeps them out until the org is gone
+        conn.execute(&format!("SELECT 1 FROM orgs WHERE id = {} FOR UPDATE", id))?;
+        let paste_ids = pastes::table
+            .filter(pastes::org_id.eq(id))
+            .filter(pastes::deleted_at.is_null())
+            .select(pastes::id)
+            .order(pastes::id)
+            .load::<i32>(conn)?;
+        if !paste_ids.is_empty() {
+            return Err(DeleteError::Blocked(paste_ids));
+        }
+
+        let trashed = diesel::delete(pastes::table.filter(pastes::org_id.eq(id)))
+            .get_results::<Paste>(conn)?
+            .iter()
+            .map(|paste| paste.id)
+            .collect::<Vec<i32>>();
+        paste_serv::notify_deleted(&trashed, conn)?;
+        diesel::delete(orgs::table.find(id))
+            .execute(conn)
+            .map_err(DeleteError::from)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    #[test]
+    fn test_membership() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let (owner, other) = (data.user.id, data.user_alt.id);
+
+        let org = create_org("acme", "Acme", owner, conn).unwrap();
+        assert!(create_org("acme", "Acme again", other, conn).is_err());
+        assert_eq!(get_org_by_slug("acme", conn).unwrap(), org);
+        assert_eq!(get_role(org.id, owner, conn), Ok(Some(OWNER.to_string())));
+        assert_eq!(get_role(org.id, other, conn), Ok(None));
+        assert_eq!(is_last_owner(org.id, owner, conn), Ok(true));
+
+        let invitation = invite(org.id, other, MAINTAINER, owner, conn).unwrap();
+        assert!(invite(org.id, other, MEMBER, owner, conn).is_err());
+        assert_eq!(get_invitations_by_user_id(other, conn).unwrap().len(), 1);
+        // only the invited user accepts
+        assert_eq!(accept_invitation(invitation.id, owner, conn).err(),
+                   Some(DieselError::NotFound));
+        let member = accept_invitation(invitation.id, other, conn).unwrap();
+        assert_eq!(member.role, MAINTAINER);
+        assert!(get_invitations_by_user_id(other, conn).unwrap().is_empty());
+        assert_eq!(get_orgs_by_user_id(other, conn).unwrap(), vec![org]);
+
+        set_role(member.org_id, other, OWNER, conn).unwrap();
+        assert_eq!(is_last_owner(member.org_id, owner, conn), Ok(false));
+        assert_eq!(remove_member(member.org_id, other, conn), Ok(1));
+        assert_eq!(get_members(member.org_id, conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_delete_org() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let org = create_org("acme", "Acme", data.user.id, conn).unwrap();
+        paste_serv::transfer_paste(data.paste.id, org.id, conn).unwrap();
+
+        assert_eq!(delete_org(org.id, conn), Err(DeleteError::Blocked(vec![data.paste.id])));
+        paste_serv::delete_paste(data.paste.id, conn).unwrap();
+        assert_eq!(delete_org(org.id, conn), Ok(1));
+        assert_eq!(get_org_by_slug("acme", conn).err(), Some(DieselError::NotFound));
+        assert!(paste_serv::get_deleted_pastes_by_user_id(data.user.id, conn)
+                    .unwrap()
+                    .is_empty());
+    }
+
+    #[test]
+    fn test_decline_invitation() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+
+        let org = create_org("acme", "Acme", data.user.id, conn).unwrap();
+        let invitation = invite(org.id, data.user_alt.id, MEMBER, data.user.id, conn).unwrap();
+        assert_eq!(decline_invitation(invitation.id, data.user.id, conn), Ok(0));
+        assert_eq!(decline_invitation(invitation.id, data.user_alt.id, conn), Ok(1));
+        assert_eq!(get_role(org.id, data.user_alt.id, conn), Ok(None));
+    }
+}
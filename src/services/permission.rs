@@ -0,0 +1,69 @@
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use models::schema;
+use models::permission::RolePermission;
+
+use self::schema::role_permissions;
+
+/// Union of the permissions granted to `roles`.
+pub fn get_permissions_by_roles(roles: &[String],
+                                conn: &PgConnection)
+                                -> Result<Vec<String>, DieselError> {
+    role_permissions::table
+        .filter(role_permissions::role.eq_any(roles.to_vec()))
+        .load::<RolePermission>(conn)
+        .and_then(|role_permissions| {
+            let mut permissions = role_permissions
+                .into_iter()
+                .map(|role_permission| role_permission.permission)
+                .collect::<Vec<String>>();
+            permissions.sort();
+            permissions.dedup();
+            Ok(permissions)
+        })
+}
+
+/// Roles which can be granted to users.
+pub fn get_roles(conn: &PgConnection) -> Result<Vec<String>, DieselError> {
+    role_permissions::table
+        .select(role_permissions::role)
+        .distinct()
+        .order(role_permissions::role)
+        .load::<String>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    #[test]
+    fn test_get_permissions_by_roles() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+
+        let permissions = get_permissions_by_roles(&["user".to_owned()], conn).unwrap();
+        assert!(permissions.contains(&"paste:write".to_owned()));
+        assert!(!permissions.contains(&"user:admin".to_owned()));
+
+        assert_eq!(get_roles(conn).unwrap(), vec!["admin", "moderator", "user"]);
+
+        let permissions = get_permissions_by_roles(&["user".to_owned(), "admin".to_owned()],
+                                                   conn)
+                .unwrap();
+        assert!(permissions.contains(&"paste:write".to_owned()));
+        assert!(permissions.contains(&"user:admin".to_owned()));
+        // no duplicates for permissions granted by several roles
+        assert_eq!(permissions
+                       .iter()
+                       .filter(|permission| *permission == "moderation")
+                       .count(),
+                   1);
+
+        assert_eq!(get_permissions_by_roles(&["unknown".to_owned()], conn),
+                   Ok(vec![]));
+    }
+}
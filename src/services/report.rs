@@ -0,0 +1,136 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use time;
+
+use models::schema;
+use models::report::{Report, NewReport};
+use services::paste as paste_serv;
+
+use self::schema::reports;
+
+pub const OPEN: &str = "open";
+pub const RESOLVED: &str = "resolved";
+pub const DISMISSED: &str = "dismissed";
+
+pub fn create_report(paste_id: i32,
+                     reporter_id: i32,
+                     reason: &str,
+                     conn: &PgConnection)
+                     -> Result<Report, DieselError> {
+    // soft deleted pastes can't be reported
+    paste_serv::get_paste_by_id(paste_id, conn)?;
+
+    let new_report = NewReport {
+        paste_id,
+        reporter_id,
+        reason,
+        created_at: time::get_time().sec,
+    };
+    diesel::insert(&new_report)
+        .into(reports::table)
+        .get_result::<Report>(conn)
+}
+
+/// Moderation queue, oldest first.
+pub fn get_open_reports(conn: &PgConnection) -> Result<Vec<Report>, DieselError> {
+    reports::table
+        .filter(reports::status.eq(OPEN))
+        .order(reports::created_at)
+        .load::<Report>(conn)
+}
+
+fn close_report(id: i32,
+                status: &str,
+                moderator_id: i32,
+                conn: &PgConnection)
+                -> Result<Report, DieselError> {
+    diesel::update(reports::table
+                       .filter(reports::id.eq(id))
+                       .filter(reports::status.eq(OPEN)))
+            .set((reports::status.eq(status),
+                  reports::resolved_by.eq(Some(moderator_id)),
+                  reports::resolved_at.eq(Some(time::get_time().sec))))
+            .get_result::<Report>(conn)
+}
+
+/// Hide the reported paste with `reason`, which closes all open reports of
+/// the paste.
+pub fn resolve_report(id: i32,
+                      moderator_id: i32,
+                      reason: &str,
+                      conn: &PgConnection)
+                      -> Result<Report, DieselError> {
+    conn.transaction(|| {
+        let report = close_report(id, RESOLVED, moderator_id, conn)?;
+        paste_serv::hide_paste(report.paste_id, reason, conn)?;
+        diesel::update(reports::table
+                           .filter(reports::paste_id.eq(report.paste_id))
+                           .filter(reports::status.eq(OPEN)))
+                .set((reports::status.eq(RESOLVED),
+                      reports::resolved_by.eq(Some(moderator_id)),
+                      reports::resolved_at.eq(report.resolved_at)))
+                .execute(conn)?;
+        Ok(report)
+    })
+}
+
+pub fn dismiss_report(id: i32,
+                      moderator_id: i32,
+                      conn: &PgConnection)
+                      -> Result<Report, DieselError> {
+    close_report(id, DISMISSED, moderator_id, conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    #[test]
+    fn test_resolve_report() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+
+        let report = create_report(data.paste.id, data.user_alt.id, "spam", conn).unwrap();
+        assert_eq!(report.status, OPEN);
+        // one open report per reporter
+        assert!(create_report(data.paste.id, data.user_alt.id, "spam", conn).is_err());
+        create_report(data.paste.id, data.user.id, "still spam", conn).unwrap();
+        assert_eq!(get_open_reports(conn).unwrap().len(), 2);
+
+        let report = resolve_report(report.id, data.user.id, "spam", conn).unwrap();
+        assert_eq!(report.status, RESOLVED);
+        assert_eq!(report.resolved_by, Some(data.user.id));
+        assert!(get_open_reports(conn).unwrap().is_empty());
+
+        let paste = paste_serv::get_paste_by_id(data.paste.id, conn).unwrap();
+        assert!(paste.hidden_by_moderator);
+        assert_eq!(paste.hidden_reason, Some("spam".to_string()));
+
+        // closed reports can't be dismissed anymore
+        assert_eq!(dismiss_report(report.id, data.user.id, conn).err(),
+                   Some(DieselError::NotFound));
+    }
+
+    #[test]
+    fn test_dismiss_report() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+
+        let report = create_report(data.paste.id, data.user_alt.id, "meh", conn).unwrap();
+        let report = dismiss_report(report.id, data.user.id, conn).unwrap();
+        assert_eq!(report.status, DISMISSED);
+        assert!(!paste_serv::get_paste_by_id(data.paste.id, conn).unwrap().hidden_by_moderator);
+
+        // a dismissed report doesn't keep the user from reporting again
+        assert!(create_report(data.paste.id, data.user_alt.id, "meh", conn).is_ok());
+        assert!(create_report(-1, data.user_alt.id, "meh", conn).is_err());
+    }
+}
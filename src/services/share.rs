@@ -0,0 +1,202 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use time;
+
+use models::schema;
+use models::paste::Paste;
+use models::paste_share::{PasteShare, NewPasteShare};
+use services::group as group_serv;
+use services::paste as paste_serv;
+
+use self::schema::pastes;
+use self::schema::paste_shares;
+
+pub const READ: &str = "read";
+pub const EDIT: &str = "edit";
+pub const ACCESS_LEVELS: [&str; 2] = [READ, EDIT];
+
+/// Who a paste is shared with, either a user or a group.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Grantee {
+    User(i32),
+    Group(i32),
+}
+
+/// Access levels which include `access`, edit access can read as well.
+fn granting(access: &str) -> Vec<String> {
+    match access {
+        READ => vec![READ.to_string(), EDIT.to_string()],
+        _ => vec![access.to_string()],
+    }
+}
+
+/// Grant `access` on a paste, replacing what the grantee had before.
+pub fn share_paste(paste_id: i32,
+                   grantee: Grantee,
+                   access: &str,
+                   created_by: i32,
+                   conn: &PgConnection)
+                   -> Result<PasteShare, DieselError> {
+    let (user_id, group_id) = match grantee {
+        Grantee::User(user_id) => (Some(user_id), None),
+        Grantee::Group(group_id) => (None, Some(group_id)),
+    };
+    conn.transaction(|| {
+        // soft deleted pastes can't be shared
+        paste_serv::get_paste_by_id(paste_id, conn)?;
+        let existing = paste_shares::table.filter(paste_shares::paste_id.eq(paste_id));
+        match grantee {
+            Grantee::User(user_id) => {
+                diesel::delete(existing.filter(paste_shares::user_id.eq(user_id))).execute(conn)?
+            }
+            Grantee::Group(group_id) => {
+                diesel::delete(existing.filter(paste_shares::group_id.eq(group_id))).execute(conn)?
+            }
+        };
+
+        let new_share = NewPasteShare {
+            paste_id,
+            user_id,
+            group_id,
+            access,
+            created_by: Some(created_by),
+            created_at: time::get_time().sec,
+        };
+        diesel::insert(&new_share)
+            .into(paste_shares::table)
+            .get_result::<PasteShare>(conn)
+    })
+}
+
+pub fn get_shares(paste_id: i32, conn: &PgConnection) -> Result<Vec<PasteShare>, DieselError> {
+    paste_shares::table
+        .filter(paste_shares::paste_id.eq(paste_id))
+        .order(paste_shares::id)
+        .load::<PasteShare>(conn)
+}
+
+pub fn unshare_paste(paste_id: i32, id: i32, conn: &PgConnection) -> Result<usize, DieselError> {
+    diesel::delete(paste_shares::table
+                       .filter(paste_shares::id.eq(id))
+                       .filter(paste_shares::paste_id.eq(paste_id)))
+            .execute(conn)
+}
+
+/// Whether the paste of `owner_id` is shared with the user, directly or
+/// through one of their groups, with at least `access`.
+pub fn has_access(paste_id: i32,
+                  owner_id: i32,
+                  user_id: i32,
+                  access: &str,
+                  conn: &PgConnection)
+                  -> Result<bool, DieselError> {
+    let owned = pastes::table
+        .find(paste_id)
+        .filter(pastes::user_id.eq(owner_id))
+        .count()
+        .get_result::<i64>(conn)?;
+    if owned == 0 {
+        return Ok(false);
+    }
+
+    let group_ids = group_serv::get_group_ids_by_user_id(user_id, conn)?
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<Option<i32>>>();
+    paste_shares::table
+        .filter(paste_shares::paste_id.eq(paste_id))
+        .filter(paste_shares::user_id
+                    .eq(user_id)
+                    .or(paste_shares::group_id.eq_any(group_ids)))
+        .filter(paste_shares::access.eq_any(granting(access)))
+        .count()
+        .get_result::<i64>(conn)
+        .map(|count| count > 0)
+}
+
+/// Pastes of other users shared with the user, directly or through one of
+/// their groups. Hidden pastes are left out, only their owner still sees
+/// them.
+pub fn get_shared_pastes(user_id: i32, conn: &PgConnection) -> Result<Vec<Paste>, DieselError> {
+    let group_ids = group_serv::get_group_ids_by_user_id(user_id, conn)?
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<Option<i32>>>();
+    let paste_ids = paste_shares::table
+        .filter(paste_shares::user_id
+                    .eq(user_id)
+                    .or(paste_shares::group_id.eq_any(group_ids)))
+        .select(paste_shares::paste_id)
+        .load::<i32>(conn)?;
+
+    paste_serv::get_pastes_by_ids(paste_ids, conn).map(|pastes| {
+        pastes
+            .into_iter()
+            .filter(|paste| paste.user_id != user_id && !paste.hidden_by_moderator)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    #[test]
+    fn test_share_with_user() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let (paste, owner, other) = (&data.paste, data.user.id, data.user_alt.id);
+
+        assert_eq!(has_access(paste.id, owner, other, READ, conn), Ok(false));
+        assert!(get_shared_pastes(other, conn).unwrap().is_empty());
+
+        share_paste(paste.id, Grantee::User(other), READ, owner, conn).unwrap();
+        assert_eq!(has_access(paste.id, owner, other, READ, conn), Ok(true));
+        assert_eq!(has_access(paste.id, owner, other, EDIT, conn), Ok(false));
+        // the owner in the url has to match
+        assert_eq!(has_access(paste.id, other, other, READ, conn), Ok(false));
+        let shared = get_shared_pastes(other, conn).unwrap();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].data, paste.data);
+
+        // sharing again replaces the access
+        let share = share_paste(paste.id, Grantee::User(other), EDIT, owner, conn).unwrap();
+        assert_eq!(get_shares(paste.id, conn).unwrap(), vec![share]);
+        assert_eq!(has_access(paste.id, owner, other, READ, conn), Ok(true));
+        assert_eq!(has_access(paste.id, owner, other, EDIT, conn), Ok(true));
+
+        let share = get_shares(paste.id, conn).unwrap().remove(0);
+        assert_eq!(unshare_paste(-1, share.id, conn), Ok(0));
+        assert_eq!(unshare_paste(paste.id, share.id, conn), Ok(1));
+        assert_eq!(has_access(paste.id, owner, other, READ, conn), Ok(false));
+    }
+
+    #[test]
+    fn test_share_with_group() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let (paste, owner, other) = (&data.paste, data.user.id, data.user_alt.id);
+
+        let group = group_serv::create_group(owner, "team", conn).unwrap();
+        share_paste(paste.id, Grantee::Group(group.id), EDIT, owner, conn).unwrap();
+        assert_eq!(has_access(paste.id, owner, other, EDIT, conn), Ok(false));
+
+        group_serv::add_member(group.id, other, conn).unwrap();
+        assert_eq!(has_access(paste.id, owner, other, EDIT, conn), Ok(true));
+        assert_eq!(get_shared_pastes(other, conn).unwrap().len(), 1);
+        // own pastes aren't listed as shared
+        assert!(get_shared_pastes(owner, conn).unwrap().is_empty());
+
+        group_serv::delete_group(group.id, conn).unwrap();
+        assert_eq!(has_access(paste.id, owner, other, READ, conn), Ok(false));
+        assert!(get_shares(paste.id, conn).unwrap().is_empty());
+    }
+}
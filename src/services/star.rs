@@ -0,0 +1,98 @@
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use time;
+
+use models::schema;
+use models::paste::Paste;
+use models::star::{Star, NewStar};
+use services::paste as paste_serv;
+
+use self::schema::stars;
+
+/// Starring a paste twice keeps the first star.
+pub fn star_paste(user_id: i32, paste_id: i32, conn: &PgConnection) -> Result<Star, DieselError> {
+    conn.transaction(|| {
+        let mut existing = stars::table
+            .filter(stars::user_id.eq(user_id))
+            .filter(stars::paste_id.eq(paste_id))
+            .load::<Star>(conn)?;
+        if let Some(star) = existing.pop() {
+            return Ok(star);
+        }
+
+        let new_star = NewStar {
+            user_id,
+            paste_id,
+            created_at: time::get_time().sec,
+        };
+        diesel::insert(&new_star)
+            .into(stars::table)
+            .get_result::<Star>(conn)
+    })
+}
+
+pub fn unstar_paste(user_id: i32,
+                    paste_id: i32,
+                    conn: &PgConnection)
+                    -> Result<usize, DieselError> {
+    diesel::delete(stars::table
+                       .filter(stars::user_id.eq(user_id))
+                       .filter(stars::paste_id.eq(paste_id)))
+            .execute(conn)
+}
+
+/// Pastes the user starred, latest star first. Pastes in the trash are left
+/// out, as are hidden ones and password protected pastes of others, which
+/// take the password on every read.
+pub fn get_starred_pastes(user_id: i32, conn: &PgConnection) -> Result<Vec<Paste>, DieselError> {
+    let paste_ids = stars::table
+        .filter(stars::user_id.eq(user_id))
+        .order(stars::id.desc())
+        .select(stars::paste_id)
+        .load::<i32>(conn)?;
+
+    let mut pastes = paste_serv::get_pastes_by_ids(paste_ids.clone(), conn)?
+        .into_iter()
+        .filter(|paste| {
+                    !paste.hidden_by_moderator &&
+                    (paste.user_id == user_id || !paste_serv::is_protected(paste))
+                })
+        .collect::<Vec<Paste>>();
+    pastes.sort_by_key(|paste| paste_ids.iter().position(|&id| id == paste.id));
+    Ok(pastes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    #[test]
+    fn test_stars() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let (paste_id, user_id) = (data.paste.id, data.user_alt.id);
+
+        assert!(get_starred_pastes(user_id, conn).unwrap().is_empty());
+        let star = star_paste(user_id, paste_id, conn).unwrap();
+        assert_eq!(star_paste(user_id, paste_id, conn), Ok(star));
+        let starred = get_starred_pastes(user_id, conn).unwrap();
+        assert_eq!(starred.len(), 1);
+        assert_eq!(starred[0].id, paste_id);
+        // stars are per user
+        assert!(get_starred_pastes(data.user.id, conn).unwrap().is_empty());
+
+        paste_serv::hide_paste(paste_id, "spam", conn).unwrap();
+        assert!(get_starred_pastes(user_id, conn).unwrap().is_empty());
+
+        assert_eq!(unstar_paste(user_id, paste_id, conn), Ok(1));
+        assert_eq!(unstar_paste(user_id, paste_id, conn), Ok(0));
+    }
+}
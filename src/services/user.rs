@@ -6,11 +6,18 @@ use diesel::result::Error as DieselError;
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
 
+use time;
+
 use helpers::digest;
+use helpers::error::{self, Error};
+use helpers::throttle;
 use models::schema;
+use models::paste::Paste;
 use models::user::{User as ModelUser, NewUser as ModelNewUser};
+use services::paste as paste_serv;
 
 use self::schema::users;
+use self::schema::pastes;
 
 #[derive(Serialize, Deserialize)]
 pub struct User {
@@ -18,8 +25,28 @@ pub struct User {
     pub username: String,
     pub email: String,
     pub roles: Vec<String>,
+    pub last_login_at: Option<i64>,
+    #[serde(skip_serializing, default)]
+    pub failed_attempts: i32,
+    #[serde(skip_serializing, default)]
+    pub locked_until: Option<i64>,
+    pub totp_enabled: bool,
+    pub email_verified: bool,
+    pub deleted_at: Option<i64>,
+    pub suspended_at: Option<i64>,
+    pub suspended_reason: Option<String>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: Option<i64>,
     #[serde(skip_serializing, skip_deserializing)]
     password_digest: Vec<u8>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub totp_secret: Option<Vec<u8>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub totp_recovery_codes: Vec<Vec<u8>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub totp_last_step: Option<i64>,
 }
 
 impl From<ModelUser> for User {
@@ -29,7 +56,22 @@ impl From<ModelUser> for User {
             username: user.username,
             email: user.email,
             roles: user.roles,
+            last_login_at: user.last_login_at,
+            failed_attempts: user.failed_attempts,
+            locked_until: user.locked_until,
+            totp_enabled: user.totp_enabled,
+            email_verified: user.email_verified,
+            deleted_at: user.deleted_at,
+            suspended_at: user.suspended_at,
+            suspended_reason: user.suspended_reason,
+            display_name: user.display_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            created_at: user.created_at,
             password_digest: user.password_digest,
+            totp_secret: user.totp_secret,
+            totp_recovery_codes: user.totp_recovery_codes,
+            totp_last_step: user.totp_last_step,
         }
     }
 }
@@ -38,6 +80,22 @@ impl User {
     pub fn verify_password(&self, attempted_password: &str) -> bool {
         digest::verify_password(&self.username, &self.password_digest, attempted_password)
     }
+
+    /// Changes whenever the password does, so password reset tokens carrying
+    /// it can only be used once.
+    pub fn password_fingerprint(&self) -> String {
+        digest::to_hex(&digest::sha256(&self.password_digest)[..8])
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked_until
+            .map(|locked_until| locked_until > time::get_time().sec)
+            .unwrap_or(false)
+    }
 }
 
 pub struct NewUser<'a> {
@@ -57,6 +115,7 @@ pub fn create_user<'a>(user: &'a NewUser, conn: &'a PgConnection) -> Result<User
         username: user.username,
         email: &user.email.to_lowercase(),
         password_digest: digest::digest_password(user.username, user.password),
+        created_at: Some(time::get_time().sec),
     };
 
     diesel::insert(&new_user)
@@ -69,22 +128,30 @@ pub fn update_user<'a>(id: i32,
                        updated_user: &'a UpdatedUser,
                        conn: &'a PgConnection)
                        -> Result<User, DieselError> {
-    let mut user = users::table.find(id).get_result::<ModelUser>(conn)?;
+    let mut user = users::table
+        .find(id)
+        .filter(users::deleted_at.is_null())
+        .get_result::<ModelUser>(conn)?;
 
     if updated_user.username.is_some() {
         user.username = updated_user.username.unwrap().into();
     }
     if updated_user.email.is_some() {
-        user.email = updated_user.email.unwrap().to_lowercase();
+        let email = updated_user.email.unwrap().to_lowercase();
+        if email != user.email {
+            user.email_verified = false;
+        }
+        user.email = email;
     }
     if updated_user.password.is_some() {
         user.password_digest = digest::digest_password(user.username.as_ref(),
                                                        updated_user.password.unwrap());
     }
 
-    diesel::update(users::table.find(id))
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
         .set((users::username.eq(user.username),
               users::email.eq(user.email),
+              users::email_verified.eq(user.email_verified),
               users::password_digest.eq(user.password_digest)))
         .get_result::<ModelUser>(conn)
         .and_then(|user| Ok(user.into()))
@@ -93,6 +160,15 @@ pub fn update_user<'a>(id: i32,
 pub fn get_user_by_id(id: i32, conn: &PgConnection) -> Result<User, DieselError> {
     users::table
         .find(id)
+        .filter(users::deleted_at.is_null())
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+pub fn get_user_by_email(email: &str, conn: &PgConnection) -> Result<User, DieselError> {
+    users::table
+        .filter(users::email.eq(email.to_lowercase()))
+        .filter(users::deleted_at.is_null())
         .get_result::<ModelUser>(conn)
         .and_then(|user| Ok(user.into()))
 }
@@ -100,6 +176,7 @@ pub fn get_user_by_id(id: i32, conn: &PgConnection) -> Result<User, DieselError>
 pub fn get_user_by_name(username: &str, conn: &PgConnection) -> Result<User, DieselError> {
     users::table
         .filter(users::username.eq(username))
+        .filter(users::deleted_at.is_null())
         .get_result::<ModelUser>(conn)
         .and_then(|user| Ok(user.into()))
 }
@@ -107,13 +184,359 @@ pub fn get_user_by_name(username: &str, conn: &PgConnection) -> Result<User, Die
 // TODO: paging
 pub fn get_user_list(conn: &PgConnection) -> Result<Vec<User>, DieselError> {
     users::table
+        .filter(users::deleted_at.is_null())
         .limit(20)
         .load::<ModelUser>(conn)
         .and_then(|users| Ok(users.into_iter().map(|user| user.into()).collect()))
 }
 
-pub fn delete_user(id: i32, conn: &PgConnection) -> Result<usize, DieselError> {
-    diesel::delete(users::table.filter(users::id.eq(id))).execute(conn)
+/// What anyone gets to see of a user, no email or account state.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Profile {
+    pub id: i32,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: Option<i64>,
+}
+
+impl From<ModelUser> for Profile {
+    fn from(user: ModelUser) -> Profile {
+        Profile {
+            id: user.id,
+            username: user.username,
+            display_name: user.display_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// Fields left at None stay the same, empty ones are cleared.
+pub struct UpdatedProfile<'a> {
+    pub display_name: Option<&'a str>,
+    pub bio: Option<&'a str>,
+    pub avatar_url: Option<&'a str>,
+}
+
+fn updated_field(updated: Option<&str>, current: Option<String>) -> Option<String> {
+    match updated {
+        Some("") => None,
+        Some(value) => Some(value.to_string()),
+        None => current,
+    }
+}
+
+pub fn get_profile(id: i32, conn: &PgConnection) -> Result<Profile, DieselError> {
+    users::table
+        .find(id)
+        .filter(users::deleted_at.is_null())
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+pub fn update_profile(id: i32,
+                      updated: &UpdatedProfile,
+                      conn: &PgConnection)
+                      -> Result<Profile, DieselError> {
+    let user = users::table
+        .find(id)
+        .filter(users::deleted_at.is_null())
+        .get_result::<ModelUser>(conn)?;
+
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set((users::display_name.eq(updated_field(updated.display_name, user.display_name)),
+              users::bio.eq(updated_field(updated.bio, user.bio)),
+              users::avatar_url.eq(updated_field(updated.avatar_url, user.avatar_url))))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+/// What happens to the personal pastes of a deleted user, pastes owned by an
+/// org stay with it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeleteMode {
+    /// Refuse while the user still owns pastes.
+    Restrict,
+    /// Move the pastes to the trash along with the user.
+    Cascade,
+    /// Hand the pastes over to the ghost user.
+    Reassign,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DeleteError {
+    Database(DieselError),
+    /// Restrict mode and the user still owns these pastes.
+    Blocked(Vec<i32>),
+}
+
+impl From<DieselError> for DeleteError {
+    fn from(err: DieselError) -> DeleteError {
+        DeleteError::Database(err)
+    }
+}
+
+impl From<DeleteError> for Error {
+    fn from(err: DeleteError) -> Error {
+        match err {
+            DeleteError::Database(err) => Error::from(err),
+            DeleteError::Blocked(paste_ids) => {
+                error::conflict("user still owns pastes").with_details(json!({"pastes": paste_ids}))
+            }
+        }
+    }
+}
+
+impl DeleteMode {
+    pub fn from_name(name: &str) -> Option<DeleteMode> {
+        match name {
+            "restrict" => Some(DeleteMode::Restrict),
+            "cascade" => Some(DeleteMode::Cascade),
+            "reassign" => Some(DeleteMode::Reassign),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DeleteMode::Restrict => "restrict",
+            DeleteMode::Cascade => "cascade",
+            DeleteMode::Reassign => "reassign",
+        }
+    }
+}
+
+/// Owner of the pastes of deleted users. The brackets can't pass username
+/// validation, so it never clashes with a real account, and it can't log in
+/// with an empty password digest.
+pub const GHOST_USERNAME: &str = "[deleted]";
+const GHOST_EMAIL: &str = "deleted@invalid";
+
+pub fn get_ghost_user(conn: &PgConnection) -> Result<User, DieselError> {
+    match get_user_by_name(GHOST_USERNAME, conn) {
+        Err(DieselError::NotFound) => {}
+        result => return result,
+    }
+
+    let ghost = ModelNewUser {
+        username: GHOST_USERNAME,
+        email: GHOST_EMAIL,
+        password_digest: vec![],
+        created_at: Some(time::get_time().sec),
+    };
+    diesel::insert(&ghost)
+        .into(users::table)
+        .get_result::<ModelUser>(conn)?;
+    diesel::update(users::table.filter(users::username.eq(GHOST_USERNAME)))
+        .set(users::roles.eq(Vec::<String>::new()))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+/// Ids of the pastes which keep a user from being deleted in restrict mode,
+/// pastes in the trash are purged along with the user and org pastes stay
+/// with their org.
+pub fn get_delete_blockers(id: i32, conn: &PgConnection) -> Result<Vec<i32>, DieselError> {
+    pastes::table
+        .filter(pastes::user_id.eq(id))
+        .filter(pastes::org_id.is_null())
+        .filter(pastes::deleted_at.is_null())
+        .select(pastes::id)
+        .order(pastes::id)
+        .load::<i32>(conn)
+}
+
+/// Soft delete, the user is purged with `purge_deleted_users` once the trash
+/// retention is over.
+pub fn delete_user(id: i32, mode: DeleteMode, conn: &PgConnection) -> Result<usize, DeleteError> {
+    let now = time::get_time().sec;
+
+    conn.transaction(|| {
+        match mode {
+            DeleteMode::Restrict => {
+                // new pastes check the user row for their foreign key, so
+                // locking it keeps them out until the blockers are counted
+                conn.execute(&format!("SELECT 1 FROM users WHERE id = {} FOR UPDATE", id))?;
+                let paste_ids = get_delete_blockers(id, conn)?;
+                if !paste_ids.is_empty() {
+                    return Err(DeleteError::Blocked(paste_ids));
+                }
+            }
+            DeleteMode::Cascade => {
+                let ids = diesel::update(pastes::table
+                                             .filter(pastes::user_id.eq(id))
+                                             .filter(pastes::org_id.is_null())
+                                             .filter(pastes::deleted_at.is_null()))
+                        .set(pastes::deleted_at.eq(Some(now)))
+                        .get_results::<Paste>(conn)?
+                        .iter()
+                        .map(|paste| paste.id)
+                        .collect::<Vec<i32>>();
+                paste_serv::notify_deleted(&ids, conn)?;
+            }
+            DeleteMode::Reassign => {
+                let ghost = get_ghost_user(conn)?;
+                diesel::update(pastes::table
+                                   .filter(pastes::user_id.eq(id))
+                                   .filter(pastes::org_id.is_null())
+                                   .filter(pastes::deleted_at.is_null()))
+                        .set(pastes::user_id.eq(ghost.id))
+                        .execute(conn)?;
+            }
+        }
+        diesel::update(users::table
+                           .filter(users::id.eq(id))
+                           .filter(users::deleted_at.is_null()))
+                .set(users::deleted_at.eq(Some(now)))
+                .execute(conn)
+                .map_err(DeleteError::from)
+    })
+}
+
+/// Permanently delete users soft deleted before `before`, along with their
+/// personal pastes. Pastes owned by an org stay with it, authored by the
+/// ghost user from then on. Api keys are removed by ON DELETE CASCADE.
+pub fn purge_deleted_users(before: i64, conn: &PgConnection) -> Result<usize, DieselError> {
+    conn.transaction(|| {
+        let ids = users::table
+            .filter(users::deleted_at.lt(before))
+            .select(users::id)
+            .load::<i32>(conn)?;
+        let org_pastes = pastes::table
+            .filter(pastes::user_id.eq_any(ids.clone()))
+            .filter(pastes::org_id.is_not_null())
+            .count()
+            .get_result::<i64>(conn)?;
+        if org_pastes > 0 {
+            let ghost = get_ghost_user(conn)?;
+            diesel::update(pastes::table
+                               .filter(pastes::user_id.eq_any(ids.clone()))
+                               .filter(pastes::org_id.is_not_null()))
+                    .set(pastes::user_id.eq(ghost.id))
+                    .execute(conn)?;
+        }
+        let paste_ids = diesel::delete(pastes::table
+                                           .filter(pastes::user_id.eq_any(ids.clone()))
+                                           .filter(pastes::org_id.is_null()))
+                .get_results::<Paste>(conn)?
+            .iter()
+            .map(|paste| paste.id)
+            .collect::<Vec<i32>>();
+        paste_serv::notify_deleted(&paste_ids, conn)?;
+        diesel::delete(users::table.filter(users::id.eq_any(ids))).execute(conn)
+    })
+}
+
+pub fn verify_email(id: i32, conn: &PgConnection) -> Result<User, DieselError> {
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set(users::email_verified.eq(true))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+pub fn record_login_success(id: i32, conn: &PgConnection) -> Result<User, DieselError> {
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set((users::last_login_at.eq(Some(time::get_time().sec)),
+              users::failed_attempts.eq(0),
+              users::locked_until.eq(None::<i64>)))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+/// Increase failed attempts of user and lock the account once backoff kicks
+/// in, see `helpers::throttle::backoff`. Attempts are counted in the update
+/// itself, so concurrent failures are all counted.
+pub fn record_login_failure(id: i32, conn: &PgConnection) -> Result<User, DieselError> {
+    let user = diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set(users::failed_attempts.eq(users::failed_attempts + 1))
+        .get_result::<ModelUser>(conn)?;
+    let lockout = throttle::backoff(user.failed_attempts);
+    if lockout == 0 {
+        return Ok(user.into());
+    }
+
+    diesel::update(users::table.find(id))
+        .set(users::locked_until.eq(Some(time::get_time().sec + lockout)))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+pub fn unlock_user(id: i32, conn: &PgConnection) -> Result<User, DieselError> {
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set((users::failed_attempts.eq(0), users::locked_until.eq(None::<i64>)))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+/// Roles are read from the account on every request, so changes apply to
+/// tokens already handed out. Api keys never carry roles besides "user".
+pub fn grant_role(id: i32, role: &str, conn: &PgConnection) -> Result<User, DieselError> {
+    let mut roles = get_user_by_id(id, conn)?.roles;
+    if !roles.contains(&role.to_owned()) {
+        roles.push(role.to_owned());
+    }
+    update_roles(id, roles, conn)
+}
+
+pub fn revoke_role(id: i32, role: &str, conn: &PgConnection) -> Result<User, DieselError> {
+    let roles = get_user_by_id(id, conn)?
+        .roles
+        .into_iter()
+        .filter(|granted| granted != role)
+        .collect();
+    update_roles(id, roles, conn)
+}
+
+fn update_roles(id: i32, roles: Vec<String>, conn: &PgConnection) -> Result<User, DieselError> {
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set(users::roles.eq(roles))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+pub fn suspend_user(id: i32, reason: &str, conn: &PgConnection) -> Result<User, DieselError> {
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set((users::suspended_at.eq(Some(time::get_time().sec)),
+              users::suspended_reason.eq(Some(reason))))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+pub fn unsuspend_user(id: i32, conn: &PgConnection) -> Result<User, DieselError> {
+    diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+        .set((users::suspended_at.eq(None::<i64>), users::suspended_reason.eq(None::<String>)))
+        .get_result::<ModelUser>(conn)
+        .and_then(|user| Ok(user.into()))
+}
+
+/// State of the account behind a token, checked by the request guards on
+/// every request. Roles are taken from here rather than from the token, so
+/// granting or revoking one takes effect right away.
+#[derive(Debug, PartialEq)]
+pub struct Account {
+    pub username: String,
+    pub roles: Vec<String>,
+    pub suspended: bool,
+    pub deleted: bool,
+}
+
+/// Soft deleted users are included, unknown ones are not found.
+pub fn get_account(id: i32, conn: &PgConnection) -> Result<Account, DieselError> {
+    users::table
+        .find(id)
+        .select((users::username, users::roles, users::suspended_at, users::deleted_at))
+        .get_result::<(String, Vec<String>, Option<i64>, Option<i64>)>(conn)
+        .map(|(username, roles, suspended_at, deleted_at)| {
+                 Account {
+                     username,
+                     roles,
+                     suspended: suspended_at.is_some(),
+                     deleted: deleted_at.is_some(),
+                 }
+             })
 }
 
 // NOTE: cannot run tests concurrently
@@ -123,10 +546,42 @@ mod tests {
     use super::*;
     use diesel::pg::PgConnection;
 
+    use services::org as org_serv;
+    use services::paste as paste_serv;
     use tests::helpers::testdata;
 
     use DB_POOL;
 
+    #[test]
+    fn test_update_profile() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+
+        let profile = get_profile(user_id, conn).unwrap();
+        assert_eq!(profile.display_name, None);
+        assert!(profile.created_at.is_some());
+
+        let updated = UpdatedProfile {
+            display_name: Some("Test User"),
+            bio: Some("runbooks and such"),
+            avatar_url: None,
+        };
+        let profile = update_profile(user_id, &updated, conn).unwrap();
+        assert_eq!(profile.display_name, Some("Test User".to_string()));
+        assert_eq!(profile.bio, Some("runbooks and such".to_string()));
+
+        // left out fields stay, empty ones are cleared
+        let updated = UpdatedProfile {
+            display_name: None,
+            bio: Some(""),
+            avatar_url: Some("https://example.com/avatar.png"),
+        };
+        let profile = update_profile(user_id, &updated, conn).unwrap();
+        assert_eq!(profile.display_name, Some("Test User".to_string()));
+        assert_eq!(profile.bio, None);
+        assert_eq!(get_profile(user_id, conn), Ok(profile));
+    }
+
     #[test]
     fn test_create_user() {
         let conn: &PgConnection = &DB_POOL.get().unwrap();
@@ -214,6 +669,35 @@ mod tests {
                    true);
     }
 
+    #[test]
+    fn test_get_user_by_email() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user = testdata::recreate().user;
+        let fetched_user = get_user_by_email(&user.email.to_uppercase(), conn).unwrap();
+
+        assert_eq!(fetched_user.id, user.id);
+        assert!(get_user_by_email("nobody@example.com", conn).is_err());
+    }
+
+    #[test]
+    fn test_verify_email() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user = testdata::recreate().user;
+        assert_eq!(user.email_verified, false);
+
+        let user = verify_email(user.id, conn).unwrap();
+        assert_eq!(user.email_verified, true);
+
+        // changing email needs another verification
+        let updated_user = UpdatedUser {
+            username: None,
+            email: Some("changed@example.com"),
+            password: None,
+        };
+        let user = update_user(user.id, &updated_user, conn).unwrap();
+        assert_eq!(user.email_verified, false);
+    }
+
     #[test]
     fn test_delete_user() {
         let conn: &PgConnection = &DB_POOL.get().unwrap();
@@ -227,6 +711,116 @@ mod tests {
 
         testdata::clear();
         let user_id = create_user(&new_user, conn).unwrap().id;
-        assert_eq!(delete_user(user_id, conn), Ok(1));
+        assert_eq!(delete_user(user_id, DeleteMode::Restrict, conn), Ok(1));
+    }
+
+    #[test]
+    fn test_delete_user_with_pastes() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+
+        assert_eq!(get_delete_blockers(data.user.id, conn), Ok(vec![data.paste.id]));
+        assert_eq!(delete_user(data.user.id, DeleteMode::Restrict, conn),
+                   Err(DeleteError::Blocked(vec![data.paste.id])));
+
+        assert_eq!(delete_user(data.user.id, DeleteMode::Reassign, conn), Ok(1));
+        let ghost = get_ghost_user(conn).unwrap();
+        assert!(ghost.roles.is_empty());
+        assert!(!ghost.verify_password(""));
+        assert_eq!(get_delete_blockers(ghost.id, conn), Ok(vec![data.paste.id]));
+
+        assert_eq!(delete_user(ghost.id, DeleteMode::Cascade, conn), Ok(1));
+        assert_eq!(paste_serv::get_paste_by_id(data.paste.id, conn), Err(DieselError::NotFound));
+    }
+
+    #[test]
+    fn test_delete_user_with_org_pastes() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let org = org_serv::create_org("acme", "Acme", data.user_alt.id, conn).unwrap();
+        paste_serv::transfer_paste(data.paste.id, org.id, conn).unwrap();
+
+        // org pastes neither block nor go along with their author
+        assert_eq!(get_delete_blockers(data.user.id, conn), Ok(vec![]));
+        assert_eq!(delete_user(data.user.id, DeleteMode::Cascade, conn), Ok(1));
+        assert!(paste_serv::get_paste_by_id(data.paste.id, conn).is_ok());
+
+        purge_deleted_users(time::get_time().sec + 1, conn).unwrap();
+        let paste = paste_serv::get_paste_by_id(data.paste.id, conn).unwrap();
+        assert_eq!(paste.org_id, Some(org.id));
+        assert_eq!(paste.user_id, get_ghost_user(conn).unwrap().id);
+    }
+
+    #[test]
+    fn test_soft_delete_and_purge() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+
+        assert_eq!(delete_user(data.user.id, DeleteMode::Cascade, conn), Ok(1));
+        // already deleted
+        assert_eq!(delete_user(data.user.id, DeleteMode::Cascade, conn), Ok(0));
+        assert_eq!(get_user_by_id(data.user.id, conn).err(), Some(DieselError::NotFound));
+        assert_eq!(get_user_by_name(&data.user.username, conn).err(),
+                   Some(DieselError::NotFound));
+        assert_eq!(get_user_list(conn).unwrap().len(), 2);
+
+        // not yet over the retention
+        assert_eq!(purge_deleted_users(time::get_time().sec - 60, conn), Ok(0));
+        assert_eq!(purge_deleted_users(time::get_time().sec + 1, conn), Ok(1));
+        let remaining = paste_serv::get_pastes_by_user_id(data.user.id, conn);
+        assert_eq!(remaining, Err(DieselError::NotFound));
+    }
+
+    #[test]
+    fn test_roles_and_suspension() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+
+        let user = grant_role(user_id, "moderator", conn).unwrap();
+        assert_eq!(user.roles, vec!["user", "moderator"]);
+        // granting twice is a no-op
+        let user = grant_role(user_id, "moderator", conn).unwrap();
+        assert_eq!(user.roles, vec!["user", "moderator"]);
+        let user = revoke_role(user_id, "user", conn).unwrap();
+        assert_eq!(user.roles, vec!["moderator"]);
+        assert_eq!(get_account(user_id, conn).unwrap().roles, vec!["moderator"]);
+
+        assert!(!get_account(user_id, conn).unwrap().suspended);
+        let user = suspend_user(user_id, "abuse", conn).unwrap();
+        assert!(user.is_suspended());
+        assert_eq!(user.suspended_reason, Some("abuse".to_string()));
+        assert!(get_account(user_id, conn).unwrap().suspended);
+
+        let user = unsuspend_user(user_id, conn).unwrap();
+        assert!(!user.is_suspended());
+        assert!(!get_account(user_id, conn).unwrap().suspended);
+        assert_eq!(get_account(-1, conn), Err(DieselError::NotFound));
+
+        // deleted accounts are still found, for the guards to reject them
+        delete_user(user_id, DeleteMode::Cascade, conn).unwrap();
+        assert!(get_account(user_id, conn).unwrap().deleted);
+    }
+
+    #[test]
+    fn test_login_lockout() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+
+        for _ in 0..throttle::MAX_FAILED_ATTEMPTS - 1 {
+            let user = record_login_failure(user_id, conn).unwrap();
+            assert_eq!(user.is_locked(), false);
+        }
+        let user = record_login_failure(user_id, conn).unwrap();
+        assert_eq!(user.failed_attempts, throttle::MAX_FAILED_ATTEMPTS);
+        assert_eq!(user.is_locked(), true);
+
+        let user = unlock_user(user_id, conn).unwrap();
+        assert_eq!(user.failed_attempts, 0);
+        assert_eq!(user.is_locked(), false);
+
+        record_login_failure(user_id, conn).unwrap();
+        let user = record_login_success(user_id, conn).unwrap();
+        assert_eq!(user.failed_attempts, 0);
+        assert!(user.last_login_at.is_some());
     }
 }
@@ -0,0 +1,319 @@
+use std::cmp;
+
+use diesel;
+use diesel::result::Error as DieselError;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use rocket_contrib::Value;
+
+use time;
+
+use helpers::digest;
+use models::schema;
+use models::webhook::*;
+use services::org as org_serv;
+use services::paste::Owner;
+
+use self::schema::webhooks;
+use self::schema::webhook_deliveries;
+
+pub const PASTE_CREATED: &str = "paste.created";
+pub const PASTE_UPDATED: &str = "paste.updated";
+pub const PASTE_DELETED: &str = "paste.deleted";
+pub const USER_CREATED: &str = "user.created";
+pub const EVENTS: [&str; 4] = [PASTE_CREATED, PASTE_UPDATED, PASTE_DELETED, USER_CREATED];
+
+pub const PENDING: &str = "pending";
+pub const DELIVERED: &str = "delivered";
+pub const FAILED: &str = "failed";
+
+/// Attempts before a delivery is given up on, about 15 hours with the
+/// backoff of `retry_delay`.
+pub const MAX_ATTEMPTS: i32 = 12;
+const RETRY_BASE_SECS: i64 = 30;
+const RETRY_MAX_SECS: i64 = 6 * 60 * 60;
+const SECRET_LEN: usize = 32;
+const DELIVERY_LOG_LEN: i64 = 50;
+
+/// Whose events a webhook gets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    /// Personal pastes of the user registering it.
+    User,
+    Org(i32),
+    /// Everything, admins only.
+    Global,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreatedWebhook {
+    pub webhook: Webhook,
+    // only returned once, on creation
+    pub secret: String,
+}
+
+pub fn create_webhook(user_id: i32,
+                      scope: Scope,
+                      url: &str,
+                      events: Vec<String>,
+                      conn: &PgConnection)
+                      -> Result<CreatedWebhook, DieselError> {
+    let secret = digest::to_hex(&digest::random_bytes(SECRET_LEN));
+    let new_webhook = NewWebhook {
+        user_id,
+        org_id: match scope {
+            Scope::Org(org_id) => Some(org_id),
+            _ => None,
+        },
+        global: scope == Scope::Global,
+        url,
+        secret: &secret,
+        events,
+        created_at: time::get_time().sec,
+    };
+
+    diesel::insert(&new_webhook)
+        .into(webhooks::table)
+        .get_result::<Webhook>(conn)
+        .and_then(|webhook| Ok(CreatedWebhook { webhook, secret }))
+}
+
+pub fn get_webhook_by_id(id: i32, conn: &PgConnection) -> Result<Webhook, DieselError> {
+    webhooks::table.find(id).get_result::<Webhook>(conn)
+}
+
+pub fn get_webhooks_by_user_id(user_id: i32,
+                               conn: &PgConnection)
+                               -> Result<Vec<Webhook>, DieselError> {
+    webhooks::table
+        .filter(webhooks::user_id.eq(user_id))
+        .order(webhooks::id)
+        .load::<Webhook>(conn)
+}
+
+/// Pending deliveries go along with it.
+pub fn delete_webhook(id: i32, conn: &PgConnection) -> Result<usize, DieselError> {
+    diesel::delete(webhooks::table.find(id)).execute(conn)
+}
+
+/// Queue a delivery of `event` to every webhook which subscribed to it and
+/// sees the owner of the paste, or to global ones only without an owner.
+pub fn enqueue(event: &str,
+               owner: Option<Owner>,
+               data: Value,
+               conn: &PgConnection)
+               -> Result<usize, DieselError> {
+    let hooks = match owner {
+        Some(Owner::User(user_id)) => {
+            webhooks::table
+                .filter(webhooks::global
+                            .eq(true)
+                            .or(webhooks::org_id.is_null().and(webhooks::user_id.eq(user_id))))
+                .load::<Webhook>(conn)?
+        }
+        Some(Owner::Org(org_id)) => {
+            let hooks = webhooks::table
+                .filter(webhooks::global.eq(true).or(webhooks::org_id.eq(org_id)))
+                .load::<Webhook>(conn)?;
+            // org webhooks go quiet once whoever registered them no longer
+            // manages the org
+            let mut kept = Vec::with_capacity(hooks.len());
+            for hook in hooks {
+                if hook.global ||
+                   org_serv::get_role(org_id, hook.user_id, conn)?
+                       .map_or(false, |role| org_serv::can_manage(&role)) {
+                    kept.push(hook);
+                }
+            }
+            kept
+        }
+        None => {
+            webhooks::table
+                .filter(webhooks::global.eq(true))
+                .load::<Webhook>(conn)?
+        }
+    };
+
+    let now = time::get_time().sec;
+    let payload = json!({"event": event, "created_at": now, "data": data}).to_string();
+    let deliveries = hooks
+        .iter()
+        .filter(|hook| hook.events.iter().any(|subscribed| subscribed == event))
+        .map(|hook| {
+                 NewWebhookDelivery {
+                     webhook_id: hook.id,
+                     event,
+                     payload: &payload,
+                     status: PENDING,
+                     next_attempt_at: now,
+                     created_at: now,
+                 }
+             })
+        .collect::<Vec<NewWebhookDelivery>>();
+    if deliveries.is_empty() {
+        return Ok(0);
+    }
+    diesel::insert(&deliveries)
+        .into(webhook_deliveries::table)
+        .execute(conn)
+}
+
+/// Latest deliveries first.
+pub fn get_deliveries_by_webhook_id(webhook_id: i32,
+                                    conn: &PgConnection)
+                                    -> Result<Vec<WebhookDelivery>, DieselError> {
+    webhook_deliveries::table
+        .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+        .order(webhook_deliveries::id.desc())
+        .limit(DELIVERY_LOG_LEN)
+        .load::<WebhookDelivery>(conn)
+}
+
+/// Pending deliveries due by `now` along with their webhook.
+pub fn get_due_deliveries(now: i64,
+                          limit: i64,
+                          conn: &PgConnection)
+                          -> Result<Vec<(WebhookDelivery, Webhook)>, DieselError> {
+    let deliveries = webhook_deliveries::table
+        .filter(webhook_deliveries::status.eq(PENDING))
+        .filter(webhook_deliveries::next_attempt_at.le(now))
+        .order(webhook_deliveries::next_attempt_at)
+        .limit(limit)
+        .load::<WebhookDelivery>(conn)?;
+    deliveries
+        .into_iter()
+        .map(|delivery| {
+                 get_webhook_by_id(delivery.webhook_id, conn).map(|hook| (delivery, hook))
+             })
+        .collect()
+}
+
+/// Take a due delivery for an attempt by pushing its next attempt out to
+/// `until`, so other workers leave it alone. False if another worker was
+/// faster.
+pub fn claim_delivery(delivery: &WebhookDelivery,
+                      until: i64,
+                      conn: &PgConnection)
+                      -> Result<bool, DieselError> {
+    diesel::update(webhook_deliveries::table
+                       .find(delivery.id)
+                       .filter(webhook_deliveries::status.eq(PENDING))
+                       .filter(webhook_deliveries::next_attempt_at.eq(delivery.next_attempt_at)))
+            .set(webhook_deliveries::next_attempt_at.eq(until))
+            .execute(conn)
+            .map(|count| count == 1)
+}
+
+/// Exponential backoff after `attempts` failed attempts.
+pub fn retry_delay(attempts: i32) -> i64 {
+    let exponent = cmp::min(cmp::max(attempts - 1, 0), 16) as u32;
+    cmp::min(RETRY_BASE_SECS * 2i64.pow(exponent), RETRY_MAX_SECS)
+}
+
+pub fn record_success(id: i32,
+                      response_code: i32,
+                      conn: &PgConnection)
+                      -> Result<WebhookDelivery, DieselError> {
+    diesel::update(webhook_deliveries::table.find(id))
+        .set((webhook_deliveries::status.eq(DELIVERED),
+              webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+              webhook_deliveries::response_code.eq(Some(response_code)),
+              webhook_deliveries::last_error.eq(None::<String>),
+              webhook_deliveries::delivered_at.eq(Some(time::get_time().sec))))
+        .get_result::<WebhookDelivery>(conn)
+}
+
+/// Retried later with backoff, failed for good after `MAX_ATTEMPTS`.
+pub fn record_failure(delivery: &WebhookDelivery,
+                      response_code: Option<i32>,
+                      error: &str,
+                      conn: &PgConnection)
+                      -> Result<WebhookDelivery, DieselError> {
+    let attempts = delivery.attempts + 1;
+    let status = if attempts >= MAX_ATTEMPTS { FAILED } else { PENDING };
+    diesel::update(webhook_deliveries::table.find(delivery.id))
+        .set((webhook_deliveries::status.eq(status),
+              webhook_deliveries::attempts.eq(attempts),
+              webhook_deliveries::next_attempt_at.eq(time::get_time().sec +
+                                                     retry_delay(attempts)),
+              webhook_deliveries::response_code.eq(response_code),
+              webhook_deliveries::last_error.eq(Some(error))))
+        .get_result::<WebhookDelivery>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::PgConnection;
+
+    use DB_POOL;
+
+    use tests::helpers::testdata;
+
+    fn events(events: &[&str]) -> Vec<String> {
+        events.iter().map(|event| event.to_string()).collect()
+    }
+
+    #[test]
+    fn test_enqueue() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let data = testdata::recreate();
+        let (user_id, other_id) = (data.user.id, data.user_alt.id);
+        let url = "http://127.0.0.1:1/hook";
+
+        let personal = create_webhook(user_id, Scope::User, url, events(&EVENTS), conn).unwrap();
+        assert_eq!(personal.secret.len(), SECRET_LEN * 2);
+        let global = create_webhook(other_id, Scope::Global, url, events(&[USER_CREATED]), conn)
+            .unwrap();
+        let org = org_serv::create_org("acme", "Acme", other_id, conn).unwrap();
+        create_webhook(other_id, Scope::Org(org.id), url, events(&[PASTE_CREATED]), conn)
+            .unwrap();
+
+        let owner = Some(Owner::User(user_id));
+        assert_eq!(enqueue(PASTE_CREATED, owner, json!({}), conn), Ok(1));
+        assert_eq!(enqueue(PASTE_CREATED, Some(Owner::User(other_id)), json!({}), conn),
+                   Ok(0));
+        assert_eq!(enqueue(PASTE_CREATED, Some(Owner::Org(org.id)), json!({}), conn),
+                   Ok(1));
+        org_serv::set_role(org.id, other_id, org_serv::MEMBER, conn).unwrap();
+        assert_eq!(enqueue(PASTE_CREATED, Some(Owner::Org(org.id)), json!({}), conn),
+                   Ok(0));
+        assert_eq!(enqueue(USER_CREATED, None, json!({"id": user_id}), conn), Ok(1));
+
+        let deliveries = get_deliveries_by_webhook_id(global.webhook.id, conn).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, USER_CREATED);
+        assert_eq!(deliveries[0].status, PENDING);
+
+        assert_eq!(delete_webhook(personal.webhook.id, conn), Ok(1));
+        assert!(get_deliveries_by_webhook_id(personal.webhook.id, conn)
+                    .unwrap()
+                    .is_empty());
+    }
+
+    #[test]
+    fn test_retries() {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user_id = testdata::recreate().user.id;
+        let url = "http://127.0.0.1:1/hook";
+
+        create_webhook(user_id, Scope::User, url, events(&[PASTE_DELETED]), conn).unwrap();
+        enqueue(PASTE_DELETED, Some(Owner::User(user_id)), json!({}), conn).unwrap();
+        let now = time::get_time().sec;
+        let (mut delivery, _) = get_due_deliveries(now, 10, conn).unwrap().remove(0);
+
+        assert_eq!(claim_delivery(&delivery, now + 60, conn), Ok(true));
+        assert_eq!(claim_delivery(&delivery, now + 60, conn), Ok(false));
+        assert!(get_due_deliveries(now, 10, conn).unwrap().is_empty());
+
+        for attempt in 1..MAX_ATTEMPTS + 1 {
+            delivery = record_failure(&delivery, Some(500), "server error", conn).unwrap();
+            assert_eq!(delivery.attempts, attempt);
+        }
+        assert_eq!(delivery.status, FAILED);
+        assert_eq!(retry_delay(1), RETRY_BASE_SECS);
+        assert_eq!(retry_delay(2), RETRY_BASE_SECS * 2);
+        assert_eq!(retry_delay(MAX_ATTEMPTS), RETRY_MAX_SECS);
+    }
+}
@@ -0,0 +1,104 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, ContentType};
+use rocket::Response;
+
+use serde_json;
+
+use helpers::error::Error;
+
+use services::user::User;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! form_req {
+    ($endpoint: expr, $body: expr) => (
+        MockRequest::new(Post, $endpoint)
+        .header(ContentType::Form)
+        .body(&$body);
+    )
+}
+
+#[test]
+fn test_verify_email() {
+    testdata::recreate();
+    let rocket = rocket();
+
+    let body = "username=verify_user&email=verify_user@example.com&password=password&\
+                confirm_password=password";
+    let req = form_req!("/users", body);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(user.email_verified, false);
+    });
+
+    let token = testdata::last_mail_token().expect("verification mail not sent");
+    let req = form_req!("/users/verify", format!("token={}", token));
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(user.username, "verify_user");
+        assert_eq!(user.email_verified, true);
+    });
+
+    let req = form_req!("/users/verify", "token=wrongtoken");
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::BadRequest.code);
+        assert_eq!(err.msg, "invalid or expired token");
+    });
+}
+
+#[test]
+fn test_reset_password() {
+    let user = testdata::recreate().user;
+    let rocket = rocket();
+
+    let req = form_req!("/password/forgot", format!("email={}", user.email));
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let token = testdata::last_mail_token().expect("reset mail not sent");
+
+    // unknown emails get the same answer
+    let req = form_req!("/password/forgot", "email=nobody@example.com");
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    let body = format!("token={}&password=newpassword&confirm_password=mismatch",
+                       token);
+    let req = form_req!("/password/reset", body);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.msg, "password mismatch");
+    });
+
+    let body = format!("token={}&password=newpassword&confirm_password=newpassword",
+                       token);
+    let req = form_req!("/password/reset", body.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let reset_user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(reset_user.id, user.id);
+    });
+
+    let req = form_req!("/login", format!("username={}&password=newpassword", user.username));
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    // token can only be used once
+    let req = form_req!("/password/reset", body);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::BadRequest.code);
+        assert_eq!(err.msg, "invalid or expired token");
+    });
+}
@@ -0,0 +1,233 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header, ContentType};
+use rocket::Response;
+
+use serde_json;
+
+use helpers::error::{Error, ErrorKind};
+
+use services::user::User;
+use services::audit::AuditEvent;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! form_req {
+    ($endpoint: expr, $body: expr, $header: expr) => ({
+        let mut req = MockRequest::new(Post, $endpoint)
+            .header(ContentType::Form)
+            .body($body);
+        req.add_header($header);
+        req
+    })
+}
+
+#[test]
+fn test_grant_and_revoke_role() {
+    let testdata::Data {
+        user_alt,
+        admin,
+        admin_header,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let endpoint = format!("/users/{}/roles", user_alt.id);
+    let req = form_req!(&endpoint, "role=moderator", admin_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(user.roles, vec!["user", "moderator"]);
+    });
+
+    let req = form_req!(&endpoint, "role=superuser", admin_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::BadRequest.code);
+        assert_eq!(err.msg, "invalid role superuser");
+    });
+
+    let normal_req = req!(Delete, format!("{}/moderator", endpoint), normal_header.clone());
+    let admin_req = req!(Delete, format!("{}/moderator", endpoint), admin_header.clone());
+    trivial_perm_tests!(&rocket, normal_req, admin_req, |mut response: Response| {
+        let body = body_string!(response);
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(user.roles, vec!["user"]);
+    });
+
+    let req = req!(Delete, format!("/users/{}/roles/admin", admin.id), admin_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.msg, "cannot revoke own admin role");
+    });
+
+    // roles apply to tokens issued before they were granted or revoked
+    let req = form_req!(&endpoint, "role=admin", admin_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let req = req!(Get, "/users", normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let req = req!(Delete, format!("{}/admin", endpoint), admin_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let req = req!(Get, "/users", normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+}
+
+#[test]
+fn test_suspend_user() {
+    let testdata::Data {
+        user,
+        admin_header,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let endpoint = format!("/users/{}/suspend", user.id);
+    let req = form_req!(&endpoint, "reason=spam", admin_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert!(user.suspended_at.is_some());
+    });
+
+    // existing tokens are rejected
+    let req = req!(Get, "/users/me", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Forbidden.code);
+        assert_eq!(err.kind, ErrorKind::AccountSuspended);
+    });
+
+    // and so is login
+    let body = format!("username={}&password={}",
+                       testdata::TEST_USER.username,
+                       testdata::TEST_USER.password);
+    let req = MockRequest::new(Post, "/login")
+        .header(ContentType::Form)
+        .body(&body);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.kind, ErrorKind::AccountSuspended);
+        assert_eq!(err.details.unwrap()["reason"], "spam");
+    });
+
+    let endpoint = format!("/users/{}/unsuspend", user.id);
+    let req = req!(Post, &endpoint, admin_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let req = req!(Get, "/users/me", normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket, req!(Post, &endpoint, dummy_header.clone()));
+}
+
+#[test]
+fn test_impersonate_user() {
+    let testdata::Data {
+        user,
+        admin_header,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let endpoint = format!("/users/{}/impersonate", user.id);
+    let mut token = String::new();
+    let normal_req = req!(Post, &endpoint, normal_header.clone());
+    let admin_req = req!(Post, &endpoint, admin_header.clone());
+    trivial_perm_tests!(&rocket, normal_req, admin_req, |mut response: Response| {
+        let body = body_string!(response);
+        let body: String = serde_json::from_str(&body).unwrap();
+        token = body.trim_left_matches("token: ").to_string();
+    });
+
+    let impersonation_header = Header::new("Authorization", format!("Bearer {}", token));
+    let req = req!(Get, "/users/me", impersonation_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let me: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(me.id, user.id);
+    });
+
+    // read only
+    let req = MockRequest::new(Post, "/pastes")
+        .header(ContentType::Form)
+        .header(impersonation_header.clone())
+        .body(&format!("user_id={}&data=impersonated", user.id));
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.kind, ErrorKind::InsufficientScope);
+    });
+}
+
+#[test]
+fn test_audit_events() {
+    let testdata::Data {
+        user,
+        paste,
+        admin_header,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let endpoint = format!("/users/{}/pastes/{}", paste.user_id, paste.id);
+    run_test!(&rocket, req!(Delete, &endpoint, normal_header.clone()), |mut response: Response| {
+        assert_eq!(body_string!(response), "1");
+    });
+
+    let req = MockRequest::new(Post, "/login")
+        .header(ContentType::Form)
+        .body(&format!("username={}&password=wrong", user.username));
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::BadRequest);
+    });
+
+    let endpoint = format!("/admin/audit?action=paste.delete&target_id={}", paste.id);
+    let normal_req = req!(Get, &endpoint, normal_header.clone());
+    let admin_req = req!(Get, &endpoint, admin_header.clone());
+    trivial_perm_tests!(&rocket, normal_req, admin_req, |mut response: Response| {
+        let body = body_string!(response);
+        let events: Vec<AuditEvent> = serde_json::from_str(&body).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].actor_id, Some(user.id));
+        assert_eq!(events[0].target_type, "paste");
+        let before = events[0].before.as_ref().unwrap();
+        assert_eq!(before["user_id"], json!(paste.user_id));
+        // paste data is kept out of the audit log
+        assert!(before.get("data").is_none());
+    });
+
+    let endpoint = format!("/admin/audit?target_type=user&target_id={}", user.id);
+    run_test!(&rocket, req!(Get, &endpoint, admin_header.clone()), |mut response: Response| {
+        let body = body_string!(response);
+        let events: Vec<AuditEvent> = serde_json::from_str(&body).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, "login_failed");
+        assert_eq!(events[0].actor_id, None);
+        assert_eq!(events[0].after.as_ref().unwrap()["reason"], "wrong password");
+    });
+
+    trivial_token_tests!(&rocket, MockRequest::new(Get, "/admin/audit"));
+}
@@ -0,0 +1,126 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header, ContentType};
+use rocket::Response;
+
+use serde_json;
+
+use helpers::error::Error;
+
+use models::api_key::ApiKey;
+use models::paste::Paste;
+use services::api_key::CreatedApiKey;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! create_api_key_req {
+    ($name: expr, $scopes: expr, $header: expr) => ({
+        let mut req = MockRequest::new(Post, "/users/me/api-keys")
+            .header(ContentType::Form)
+            .body(&format!("name={}&scopes={}", $name, $scopes));
+        req.add_header($header);
+        req
+    })
+}
+
+#[test]
+fn test_create_api_key() {
+    let testdata::Data {
+        user,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let mut key = String::new();
+    let req = create_api_key_req!("ci", "paste:read,paste:write", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let created: CreatedApiKey = serde_json::from_str(&body).unwrap();
+        assert_eq!(created.api_key.user_id, user.id);
+        assert_eq!(created.api_key.scopes, vec!["paste:read", "paste:write"]);
+        assert!(created.key.starts_with("pb_"));
+        key = created.key;
+    });
+
+    // api key is accepted as bearer token
+    let api_key_header = Header::new("Authorization", format!("Bearer {}", key));
+    let endpoint = format!("/users/{}/pastes", user.id);
+    let req = req!(Get, &endpoint, api_key_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let pastes: Vec<Paste> = serde_json::from_str(&body).unwrap();
+        assert_eq!(pastes.len(), 1);
+    });
+
+    // but only for granted scopes
+    let req = req!(Get, "/users/me", api_key_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Forbidden.code);
+        assert_eq!(err.msg, "insufficient scope");
+    });
+
+    // api keys cannot create api keys
+    let req = create_api_key_req!("nested", "paste:read", api_key_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+
+    // user:write can't be granted
+    let req = create_api_key_req!("ci", "user:write", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::BadRequest.code);
+        assert_eq!(err.msg, "invalid scope user:write");
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket, create_api_key_req!("ci", "paste:read", dummy_header.clone()));
+}
+
+#[test]
+fn test_revoke_api_key() {
+    let normal_header = testdata::recreate().normal_header;
+    let rocket = rocket();
+
+    let mut created: Option<CreatedApiKey> = None;
+    let req = create_api_key_req!("ci", "user:read", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        created = Some(serde_json::from_str(&body).unwrap());
+    });
+    let created = created.unwrap();
+    let api_key_header = Header::new("Authorization", format!("Bearer {}", created.key));
+
+    let req = req!(Get, "/users/me/api-keys", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let api_keys: Vec<ApiKey> = serde_json::from_str(&body).unwrap();
+        assert_eq!(api_keys.len(), 1);
+        assert_eq!(api_keys[0].prefix, created.api_key.prefix);
+        assert!(!body.contains(&created.key));
+    });
+
+    let endpoint = format!("/users/me/api-keys/{}", created.api_key.id);
+    let req = req!(Delete, &endpoint, normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let api_key: ApiKey = serde_json::from_str(&body).unwrap();
+        assert!(api_key.revoked_at.is_some());
+    });
+
+    let req = req!(Get, "/users/me", api_key_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Unauthorized.code);
+        assert_eq!(err.msg, "invalid api key");
+    });
+
+    trivial_token_tests!(&rocket, MockRequest::new(Delete, &endpoint));
+}
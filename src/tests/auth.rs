@@ -1,12 +1,23 @@
 use rocket;
 use rocket::testing::MockRequest;
 use rocket::http::Method::*;
-use rocket::http::{Status, ContentType};
+use rocket::http::{Status, Header, ContentType};
 use rocket::Response;
 
 use serde_json;
 
+use time;
+
+use diesel::pg::PgConnection;
+
+use DB_POOL;
+
 use helpers::error::Error;
+use helpers::throttle;
+use helpers::totp;
+
+use services::user as user_serv;
+use services::mfa as mfa_serv;
 
 use tests::helpers;
 use self::helpers::testdata;
@@ -55,3 +66,114 @@ fn test_login() {
         assert_eq!(err.msg, "wrong username or password");
     });
 }
+
+#[test]
+fn test_login_lockout() {
+    testdata::recreate();
+
+    let test_user = testdata::TEST_USER;
+    let rocket = rocket();
+
+    for _ in 0..throttle::MAX_FAILED_ATTEMPTS {
+        let req = login_req!(test_user.username, "wrong password");
+        run_test!(&rocket, req, |response: Response| {
+            assert_eq!(response.status(), Status::BadRequest);
+        });
+    }
+
+    // locked even with correct password, answered like an unknown username
+    let req = login_req!(test_user.username, test_user.password);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(err.msg, "wrong username or password");
+        assert!(err.details.is_none());
+    });
+}
+
+macro_rules! login_mfa_req {
+    ($mfa_token: expr, $code: expr) => (
+        MockRequest::new(Post, "/login/mfa")
+        .header(ContentType::Form)
+        .body(&format!("mfa_token={}&code={}",
+                       $mfa_token,
+                       $code));
+    )
+}
+
+#[test]
+fn test_login_mfa() {
+    let user = testdata::recreate().user;
+    let test_user = testdata::TEST_USER;
+    let rocket = rocket();
+
+    let secret = {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        mfa_serv::enroll(user.id, conn).unwrap();
+        let user = user_serv::get_user_by_id(user.id, conn).unwrap();
+        let secret = user.totp_secret.clone().unwrap();
+        // the code of the previous step, the current one is used to log in
+        let code = totp::generate_at(&secret, time::get_time().sec - totp::STEP);
+        assert_eq!(mfa_serv::confirm(&user, &code, conn), Ok(true));
+        secret
+    };
+
+    let mut mfa_token = String::new();
+    let req = login_req!(test_user.username, test_user.password);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let body: String = serde_json::from_str(&body).unwrap();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(body.starts_with("mfa_token: "));
+        mfa_token = body.trim_left_matches("mfa_token: ").to_string();
+    });
+
+    // mfa pending token is not accepted by guards
+    let header = Header::new("Authorization", format!("Bearer {}", mfa_token));
+    let req = req!(Get, "/users/me", header);
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Unauthorized);
+    });
+
+    // wrong code
+    let req = login_mfa_req!(mfa_token, "abcdef");
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::BadRequest.code);
+        assert_eq!(err.msg, "wrong 2fa code");
+    });
+
+    // invalid mfa token
+    let req = login_mfa_req!("wrongtoken", totp::generate(&secret));
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Unauthorized.code);
+        assert_eq!(err.msg, "invalid mfa token");
+    });
+
+    let code = totp::generate(&secret);
+    let req = login_mfa_req!(mfa_token, code);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert_eq!(response.status(), Status::Ok);
+        assert!(body.contains("token: "));
+        assert!(!body.contains("mfa_token"));
+    });
+
+    // a code is only accepted once
+    let req = login_req!(test_user.username, test_user.password);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let body: String = serde_json::from_str(&body).unwrap();
+        mfa_token = body.trim_left_matches("mfa_token: ").to_string();
+    });
+    let req = login_mfa_req!(mfa_token, code);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.msg, "wrong 2fa code");
+    });
+}
@@ -0,0 +1,120 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header, ContentType};
+use rocket::Response;
+
+use serde_json;
+
+use helpers::error::Error;
+
+use models::comment::Comment;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! form_req {
+    ($method: expr, $endpoint: expr, $body: expr, $header: expr) => ({
+        let mut req = MockRequest::new($method, $endpoint)
+            .header(ContentType::Form)
+            .body(&$body);
+        req.add_header($header);
+        req
+    })
+}
+
+#[test]
+fn test_comments() {
+    let testdata::Data {
+        user,
+        paste,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+    let comments_endpoint = format!("/pastes/{}/comments", paste.id);
+
+    // the test paste has a single line
+    let req = form_req!(Post,
+                        &comments_endpoint,
+                        "body=typo&line_start=1&line_end=2",
+                        normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::UnprocessableEntity.code);
+        assert!(body.contains("line_end"));
+    });
+    let req = form_req!(Post,
+                        &comments_endpoint,
+                        "body=typo&line_start=1",
+                        normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::BadRequest);
+    });
+    let req = form_req!(Post,
+                        &comments_endpoint,
+                        "body=typo&line_start=1&line_end=1",
+                        normal_header_alt.clone());
+    let mut response = req.dispatch_with(&rocket);
+    let body = body_string!(response);
+    let comment: Comment = serde_json::from_str(&body).unwrap();
+    assert_eq!((comment.line_start, comment.line_end), (Some(1), Some(1)));
+    // anchored to the current version
+    assert_eq!(comment.version, Some(1));
+    let comment_endpoint = format!("{}/{}", comments_endpoint, comment.id);
+
+    // readable by whoever may read the paste
+    run_test!(&rocket,
+              MockRequest::new(Get, &comments_endpoint),
+              |mut response: Response| {
+        let body = body_string!(response);
+        let comments: Vec<Comment> = serde_json::from_str(&body).unwrap();
+        assert_eq!(comments, vec![comment]);
+    });
+
+    // only the author edits
+    let req = form_req!(Put, &comment_endpoint, "body=no typo", normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let req = form_req!(Put, &comment_endpoint, "body=+", normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    });
+    let req = form_req!(Put, &comment_endpoint, "body=typo?", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let comment: Comment = serde_json::from_str(&body).unwrap();
+        assert_eq!(comment.body, "typo?");
+        assert!(comment.updated_at.is_some());
+    });
+
+    // the owner of the paste removes comments of others
+    let req = req!(Delete, &comment_endpoint, normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert_eq!(body, "1");
+    });
+
+    // hidden along with the paste while it is in the trash
+    let req = form_req!(Post, &comments_endpoint, "body=lgtm", normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let paste_endpoint = format!("/users/{}/pastes/{}", user.id, paste.id);
+    let req = req!(Delete, &paste_endpoint, normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    run_test!(&rocket,
+              MockRequest::new(Get, &comments_endpoint),
+              |response: Response| {
+        assert_eq!(response.status(), Status::NotFound);
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket,
+                         form_req!(Post, &comments_endpoint, "body=lgtm", dummy_header.clone()));
+}
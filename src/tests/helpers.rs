@@ -27,6 +27,7 @@ macro_rules! trivial_token_tests {
             let err: Error = serde_json::from_str(&body).unwrap();
             assert_eq!(err.code, Status::Unauthorized.code);
             assert_eq!(err.msg, "token not found");
+            assert_eq!(err.kind, ::helpers::error::ErrorKind::TokenMissing);
         });
 
         // invalid token test
@@ -38,6 +39,7 @@ macro_rules! trivial_token_tests {
             let err: Error = serde_json::from_str(&body).unwrap();
             assert_eq!(err.code, Status::Unauthorized.code);
             assert_eq!(err.msg, "invalid token");
+            assert_eq!(err.kind, ::helpers::error::ErrorKind::TokenInvalid);
         });
 
         // expired token test
@@ -49,6 +51,7 @@ macro_rules! trivial_token_tests {
             let err: Error = serde_json::from_str(&body).unwrap();
             assert_eq!(err.code, Status::Unauthorized.code);
             assert_eq!(err.msg, "expired token");
+            assert_eq!(err.kind, ::helpers::error::ErrorKind::TokenExpired);
         });
     )
 }
@@ -61,6 +64,7 @@ macro_rules! trivial_perm_tests {
             let err: Error = serde_json::from_str(&body).unwrap();
             assert_eq!(err.code, Status::Forbidden.code);
             assert_eq!(err.msg, "permission denied");
+            assert_eq!(err.kind, ::helpers::error::ErrorKind::PermissionDenied);
         });
 
         // id with admin token
@@ -72,6 +76,9 @@ macro_rules! trivial_perm_tests {
 }
 
 pub mod testdata {
+    use std::fs::File;
+    use std::io::Read;
+
     use diesel;
     use diesel::prelude::*;
     use diesel::pg::PgConnection;
@@ -106,11 +113,17 @@ pub mod testdata {
         email: "test_alt@example.com",
         password: "password",
     };
+    pub const TEST_ADMIN: NewUser = NewUser {
+        username: "test_admin",
+        email: "test_admin@example.com",
+        password: "password",
+    };
     pub const TEST_PASTE_DATA: &str = "test paste data";
 
     pub struct Data<'a> {
         pub user: User,
         pub user_alt: User,
+        pub admin: User,
         pub paste: Paste,
         pub admin_header: Header<'a>,
         pub normal_header: Header<'a>,
@@ -121,23 +134,34 @@ pub mod testdata {
         let conn: &PgConnection = &DB_POOL.get().unwrap();
         let user = create_user(&TEST_USER, conn).expect("Fail to create test user");
         let user_alt = create_user(&TEST_USER_ALT, conn).expect("Fail to create test user alt");
+        // the guards check the account behind every token
+        let admin = create_user(&TEST_ADMIN, conn).expect("Fail to create test admin");
+        let admin = grant_role(admin.id, "admin", conn).expect("Fail to grant admin role");
         let test_paste = NewPaste {
             user_id: user.id,
             data: TEST_PASTE_DATA.to_string(),
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            password: None,
+            org_id: None,
         };
-        let paste = create_paste(&test_paste, conn).expect("Fail to create test paste");
+        let paste = create_paste(&test_paste, conn)
+            .expect("Fail to create test paste")
+            .paste;
 
         let normal_token = normal_user_auth_token(user.id, &user.username);
         let normal_header = Header::new("Authorization", "Bearer ".to_string() + &normal_token);
         let normal_token_alt = normal_user_auth_token(user_alt.id, &user_alt.username);
         let normal_header_alt = Header::new("Authorization",
                                             "Bearer ".to_string() + &normal_token_alt);
-        let admin_token = admin_user_auth_token(1, "admin");
+        let admin_token = admin_user_auth_token(admin.id, &admin.username);
         let admin_header = Header::new("Authorization", "Bearer ".to_string() + &admin_token);
 
         Data {
             user,
             user_alt,
+            admin,
             paste,
             normal_header,
             normal_header_alt,
@@ -151,6 +175,12 @@ pub mod testdata {
         diesel::delete(pastes::table)
             .execute(conn)
             .expect("Fail to clear pastes table");
+        diesel::delete(api_keys::table)
+            .execute(conn)
+            .expect("Fail to clear api_keys table");
+        diesel::delete(orgs::table)
+            .execute(conn)
+            .expect("Fail to clear orgs table");
         diesel::delete(users::table)
             .execute(conn)
             .expect("Fail to clear users table");
@@ -169,6 +199,7 @@ pub mod testdata {
             user_id,
             username: username.to_string(),
             roles: vec!["user".to_owned()],
+            impersonated_by: None,
         };
         let jwt_secret: &str = ENV.jwt_secret.as_ref();
         encode(&JwtHeader::default(), &claims, jwt_secret.as_bytes()).unwrap()
@@ -182,11 +213,27 @@ pub mod testdata {
             user_id,
             username: username.to_string(),
             roles: vec!["admin".to_owned(), "user".to_owned()],
+            impersonated_by: None,
         };
         let jwt_secret: &str = ENV.jwt_secret.as_ref();
         encode(&JwtHeader::default(), &claims, jwt_secret.as_bytes()).unwrap()
     }
 
+    /// Token of the last mail written by the file mailer.
+    pub fn last_mail_token() -> Option<String> {
+        let mut content = String::new();
+        if File::open(&ENV.mail_file)
+               .and_then(|mut file| file.read_to_string(&mut content))
+               .is_err() {
+            return None;
+        }
+        content
+            .lines()
+            .filter(|line| line.starts_with("token: "))
+            .last()
+            .map(|line| line.trim_left_matches("token: ").to_string())
+    }
+
     pub fn expired_token() -> String {
         let now = time::get_time().sec;
         let claims = JwtClaims {
@@ -195,6 +242,7 @@ pub mod testdata {
             user_id: 1,
             username: "test user".to_string(),
             roles: vec!["user".to_owned()],
+            impersonated_by: None,
         };
         let jwt_secret: &str = ENV.jwt_secret.as_ref();
         encode(&JwtHeader::default(), &claims, jwt_secret.as_bytes()).unwrap()
@@ -1,5 +1,15 @@
 #[macro_use]
 pub mod helpers;
 pub mod auth;
+pub mod account;
 pub mod user;
 pub mod paste;
+pub mod ratelimit;
+pub mod api_key;
+pub mod report;
+pub mod admin;
+pub mod share;
+pub mod org;
+pub mod webhook;
+pub mod comment;
+pub mod star;
@@ -0,0 +1,154 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header, ContentType};
+use rocket::Response;
+
+use serde_json;
+
+use helpers::error::Error;
+
+use models::org::{Org, OrgInvitation};
+use models::paste::Paste;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! form_req {
+    ($method: expr, $endpoint: expr, $body: expr, $header: expr) => ({
+        let mut req = MockRequest::new($method, $endpoint)
+            .header(ContentType::Form)
+            .body(&$body);
+        req.add_header($header);
+        req
+    })
+}
+
+#[test]
+fn test_org_pastes() {
+    let testdata::Data {
+        user,
+        user_alt,
+        paste,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let req = form_req!(Post, "/orgs", "slug=Acme Corp&name=Acme", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::UnprocessableEntity.code);
+    });
+    let req = form_req!(Post, "/orgs", "slug=acme&name=Acme", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let org: Org = serde_json::from_str(&body).unwrap();
+        assert_eq!(org.slug, "acme");
+    });
+
+    let req = req!(Get, "/orgs/acme/pastes", normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+
+    // personal pastes move to the org
+    let transfer_endpoint = format!("/pastes/{}/transfer", paste.id);
+    let req = form_req!(Post, &transfer_endpoint, "org=acme", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let paste: Paste = serde_json::from_str(&body).unwrap();
+        assert!(paste.org_id.is_some());
+    });
+    let req = form_req!(Post, &transfer_endpoint, "org=acme", normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::BadRequest);
+    });
+    // still listed with the pastes of its author
+    let req = req!(Get, format!("/users/{}/pastes", user.id), normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let pastes: Vec<Paste> = serde_json::from_str(&body).unwrap();
+        assert_eq!(pastes.len(), 1);
+        assert_eq!(pastes[0].id, paste.id);
+    });
+
+    let invite_body = format!("user_id={}&role=member", user_alt.id);
+    let req = form_req!(Post, "/orgs/acme/invitations", invite_body, normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let req = req!(Get, "/users/me/invitations", normal_header_alt.clone());
+    let mut response = req.dispatch_with(&rocket);
+    let body = body_string!(response);
+    let invitations: Vec<OrgInvitation> = serde_json::from_str(&body).unwrap();
+    assert_eq!(invitations.len(), 1);
+    let accept_endpoint = format!("/invitations/{}/accept", invitations[0].id);
+    let req = req!(Post, &accept_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    let req = req!(Get, "/orgs/acme/pastes", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let pastes: Vec<Paste> = serde_json::from_str(&body).unwrap();
+        assert_eq!(pastes.len(), 1);
+        assert_eq!(pastes[0].id, paste.id);
+    });
+
+    // members only change their own pastes, maintainers all of them
+    let paste_endpoint = format!("/users/{}/pastes/{}", user.id, paste.id);
+    let update_body = format!("id={}&user_id={}&data=edited by alt&version=1",
+                              paste.id,
+                              user.id);
+    let req = form_req!(Put, &paste_endpoint, update_body, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let member_endpoint = format!("/orgs/acme/members/{}", user_alt.id);
+    let req = form_req!(Put, &member_endpoint, "role=maintainer", normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let req = form_req!(Put, &paste_endpoint, update_body, normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let paste: Paste = serde_json::from_str(&body).unwrap();
+        assert_eq!(paste.data, "edited by alt");
+    });
+
+    // the last owner stays
+    let req = req!(Delete,
+                   format!("/orgs/acme/members/{}", user.id),
+                   normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.msg, "an org needs at least one owner");
+    });
+    let req = req!(Delete, &member_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    // orgs go once their pastes are gone
+    let req = req!(Delete, "/orgs/acme", normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Conflict);
+    });
+    let req = req!(Delete, &paste_endpoint, normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+    let req = req!(Delete, "/orgs/acme", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert_eq!(body, "1");
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket, req!(Get, "/orgs/acme/pastes", dummy_header.clone()));
+}
@@ -0,0 +1,63 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::Status;
+use rocket::Response;
+
+use serde_json;
+
+use ENV;
+
+use helpers::error::Error;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+#[test]
+fn test_rate_limit_headers() {
+    let test_paste = testdata::recreate().paste;
+    let rocket = rocket();
+    let limit = ENV.rate_limit_read.capacity;
+
+    let req = MockRequest::new(Get, format!("/pastes/{}", test_paste.id));
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("X-RateLimit-Limit"),
+                   Some(limit.to_string().as_ref()));
+        assert_eq!(response.headers().get_one("X-RateLimit-Remaining"),
+                   Some((limit - 1).to_string().as_ref()));
+        assert!(response.headers().get_one("X-RateLimit-Reset").is_some());
+        assert!(response.headers().get_one("Retry-After").is_none());
+    });
+}
+
+#[test]
+fn test_rate_limit_exceeded() {
+    let normal_header = testdata::recreate().normal_header;
+    let rocket = rocket();
+
+    // permission denied, but still counted against the write bucket
+    for _ in 0..ENV.rate_limit_write.capacity {
+        let req = req!(Delete, "/users/-1/pastes/-1", normal_header.clone());
+        run_test!(&rocket, req, |response: Response| {
+            assert_eq!(response.status(), Status::Forbidden);
+        });
+    }
+
+    let req = req!(Delete, "/users/-1/pastes/-1", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert_eq!(response.headers().get_one("X-RateLimit-Remaining"), Some("0"));
+        assert!(response.headers().get_one("Retry-After").is_some());
+
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.msg, "write rate limit exceeded");
+    });
+
+    // other route groups are not affected
+    let req = req!(Get, "/users/me", normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+}
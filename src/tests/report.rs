@@ -0,0 +1,133 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header, ContentType};
+use rocket::Response;
+
+use diesel::pg::PgConnection;
+use serde_json;
+
+use DB_POOL;
+
+use helpers::error::{Error, ErrorKind};
+
+use models::paste::Paste;
+use models::report::Report;
+use services::user as user_serv;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! reason_req {
+    ($endpoint: expr, $reason: expr, $header: expr) => ({
+        let mut req = MockRequest::new(Post, $endpoint)
+            .header(ContentType::Form)
+            .body(&format!("reason={}", $reason));
+        req.add_header($header);
+        req
+    })
+}
+
+#[test]
+fn test_report_and_resolve() {
+    let testdata::Data {
+        user,
+        paste,
+        normal_header: moderator_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+    let conn: &PgConnection = &DB_POOL.get().unwrap();
+    user_serv::grant_role(user.id, "moderator", conn).unwrap();
+
+    let endpoint = format!("/pastes/{}/report", paste.id);
+    let mut report_id = 0;
+    let req = reason_req!(&endpoint, "spam", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let report: Report = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.paste_id, paste.id);
+        assert_eq!(report.status, "open");
+        report_id = report.id;
+    });
+
+    let req = reason_req!(&endpoint, "", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::UnprocessableEntity.code);
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket, reason_req!(&endpoint, "spam", dummy_header.clone()));
+
+    // moderation queue is for moderators and admins
+    let normal_req = req!(Get, "/reports", normal_header_alt.clone());
+    let moderator_req = req!(Get, "/reports", moderator_header.clone());
+    trivial_perm_tests!(&rocket, normal_req, moderator_req, |mut response: Response| {
+        let body = body_string!(response);
+        let reports: Vec<Report> = serde_json::from_str(&body).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].id, report_id);
+    });
+
+    let resolve_endpoint = format!("/reports/{}/resolve", report_id);
+    let req = reason_req!(&resolve_endpoint, "spam", moderator_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let report: Report = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.status, "resolved");
+    });
+
+    let req = MockRequest::new(Get, format!("/pastes/{}", paste.id));
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, 451);
+        assert_eq!(err.kind, ErrorKind::HiddenByModerator);
+        assert_eq!(err.details.unwrap()["reason"], "spam");
+    });
+
+    let req = req!(Post, format!("/pastes/{}/unhide", paste.id), moderator_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let paste: Paste = serde_json::from_str(&body).unwrap();
+        assert!(!paste.hidden_by_moderator);
+        assert_eq!(paste.hidden_reason, None);
+    });
+}
+
+#[test]
+fn test_dismiss_report() {
+    let testdata::Data {
+        paste,
+        admin_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let endpoint = format!("/pastes/{}/report", paste.id);
+    let mut report_id = 0;
+    let req = reason_req!(&endpoint, "meh", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let report: Report = serde_json::from_str(&body).unwrap();
+        report_id = report.id;
+    });
+
+    let dismiss_endpoint = format!("/reports/{}/dismiss", report_id);
+    let normal_req = req!(Post, &dismiss_endpoint, normal_header_alt.clone());
+    let admin_req = req!(Post, &dismiss_endpoint, admin_header.clone());
+    trivial_perm_tests!(&rocket, normal_req, admin_req, |mut response: Response| {
+        let body = body_string!(response);
+        let report: Report = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.status, "dismissed");
+    });
+
+    let req = MockRequest::new(Get, format!("/pastes/{}", paste.id));
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+}
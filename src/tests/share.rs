@@ -0,0 +1,186 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header, ContentType};
+use rocket::Response;
+
+use serde_json;
+
+use helpers::error::Error;
+
+use models::group::Group;
+use models::paste::Paste;
+use models::paste_share::PasteShare;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! form_req {
+    ($method: expr, $endpoint: expr, $body: expr, $header: expr) => ({
+        let mut req = MockRequest::new($method, $endpoint)
+            .header(ContentType::Form)
+            .body(&$body);
+        req.add_header($header);
+        req
+    })
+}
+
+#[test]
+fn test_share_with_user() {
+    let testdata::Data {
+        user,
+        user_alt,
+        paste,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+    let paste_endpoint = format!("/users/{}/pastes/{}", user.id, paste.id);
+    let update_body = format!("id={}&user_id={}&data=edited by alt&version=1",
+                              paste.id,
+                              user.id);
+
+    let req = form_req!(Put, &paste_endpoint, update_body, normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Forbidden.code);
+    });
+
+    // only the owner shares
+    let shares_endpoint = format!("/pastes/{}/shares", paste.id);
+    let share_body = format!("user_id={}&access=edit", user_alt.id);
+    let req = form_req!(Post, &shares_endpoint, share_body, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let req = form_req!(Post, &shares_endpoint, "access=edit", normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::BadRequest);
+    });
+    let req = form_req!(Post, &shares_endpoint, share_body, normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let share: PasteShare = serde_json::from_str(&body).unwrap();
+        assert_eq!(share.user_id, Some(user_alt.id));
+        assert_eq!(share.access, "edit");
+    });
+
+    let req = form_req!(Put, &paste_endpoint, update_body, normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let paste: Paste = serde_json::from_str(&body).unwrap();
+        assert_eq!(paste.data, "edited by alt");
+    });
+
+    let req = req!(Get, "/users/me/shared", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let pastes: Vec<Paste> = serde_json::from_str(&body).unwrap();
+        assert_eq!(pastes.len(), 1);
+        assert_eq!(pastes[0].id, paste.id);
+    });
+
+    let req = req!(Get, &shares_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+
+    // edit access doesn't allow deleting either, under the owner's user id
+    // or one's own
+    let req = req!(Delete, &paste_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let own_endpoint = format!("/users/{}/pastes/{}", user_alt.id, paste.id);
+    let req = req!(Delete, &own_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let req = req!(Get, format!("/pastes/{}", paste.id), normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_share_with_group() {
+    let testdata::Data {
+        user,
+        user_alt,
+        paste,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let req = form_req!(Post, "/groups", "name=team", normal_header.clone());
+    let mut response = req.dispatch_with(&rocket);
+    let body = body_string!(response);
+    let group: Group = serde_json::from_str(&body).unwrap();
+    assert_eq!(group.owner_id, user.id);
+
+    let shares_endpoint = format!("/pastes/{}/shares", paste.id);
+    let share_body = format!("group_id={}&access=read", group.id);
+    let req = form_req!(Post, &shares_endpoint, share_body, normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    let req = req!(Get, "/users/me/shared", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let pastes: Vec<Paste> = serde_json::from_str(&body).unwrap();
+        assert!(pastes.is_empty());
+    });
+
+    // only the owner adds members
+    let members_endpoint = format!("/groups/{}/members", group.id);
+    let member_body = format!("user_id={}", user_alt.id);
+    let req = form_req!(Post, &members_endpoint, member_body, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let req = form_req!(Post, &members_endpoint, member_body, normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    let req = req!(Get, "/users/me/shared", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let pastes: Vec<Paste> = serde_json::from_str(&body).unwrap();
+        assert_eq!(pastes.len(), 1);
+    });
+
+    // read access doesn't allow deleting
+    let paste_endpoint = format!("/users/{}/pastes/{}", user.id, paste.id);
+    let req = req!(Delete, &paste_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+
+    // members can leave, the owner can't
+    let req = req!(Delete,
+                   format!("{}/{}", members_endpoint, user.id),
+                   normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::BadRequest);
+    });
+    let req = req!(Delete,
+                   format!("{}/{}", members_endpoint, user_alt.id),
+                   normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    let req = req!(Get, &members_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket, req!(Get, "/users/me/shared", dummy_header.clone()));
+}
@@ -0,0 +1,94 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header};
+use rocket::Response;
+
+use diesel::pg::PgConnection;
+use serde_json;
+
+use {DB_POOL, PASTE_VIEWS};
+
+use helpers::error::Error;
+
+use models::paste::Paste;
+use models::star::Star;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+#[test]
+fn test_stars() {
+    let testdata::Data {
+        paste,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+    let star_endpoint = format!("/pastes/{}/star", paste.id);
+
+    let req = req!(Post, &star_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let star: Star = serde_json::from_str(&body).unwrap();
+        assert_eq!(star.paste_id, paste.id);
+    });
+    let req = req!(Post, "/pastes/0/star", normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::NotFound);
+    });
+
+    let req = req!(Get, "/users/me/stars", normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let pastes: Vec<Paste> = serde_json::from_str(&body).unwrap();
+        assert_eq!(pastes.len(), 1);
+        assert_eq!(pastes[0].id, paste.id);
+    });
+    let req = req!(Get, "/users/me/stars", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert_eq!(body, "[]");
+    });
+
+    let req = req!(Delete, &star_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert_eq!(body, "1");
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket, req!(Post, &star_endpoint, dummy_header.clone()));
+}
+
+#[test]
+fn test_views() {
+    let testdata::Data { paste, .. } = testdata::recreate();
+    let rocket = rocket();
+    let endpoint = format!("/pastes/{}", paste.id);
+    let conn: &PgConnection = &DB_POOL.get().unwrap();
+
+    for _ in 0..2 {
+        run_test!(&rocket,
+                  MockRequest::new(Get, &endpoint),
+                  |response: Response| { assert_eq!(response.status(), Status::Ok); });
+    }
+    // only counted in memory until flushed
+    run_test!(&rocket,
+              MockRequest::new(Get, &endpoint),
+              |mut response: Response| {
+        let body = body_string!(response);
+        let paste: Paste = serde_json::from_str(&body).unwrap();
+        assert_eq!(paste.views, 0);
+    });
+
+    PASTE_VIEWS.flush(conn).unwrap();
+    run_test!(&rocket,
+              MockRequest::new(Get, &endpoint),
+              |mut response: Response| {
+        let body = body_string!(response);
+        let paste: Paste = serde_json::from_str(&body).unwrap();
+        assert_eq!(paste.views, 3);
+    });
+}
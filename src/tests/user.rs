@@ -6,9 +6,18 @@ use rocket::Response;
 
 use serde_json;
 
-use helpers::error::Error;
+use diesel::pg::PgConnection;
 
+use DB_POOL;
+
+use helpers::error::{Error, ErrorKind};
+use helpers::throttle;
+use helpers::totp;
+
+use services::user as user_serv;
 use services::user::User;
+use services::mfa as mfa_serv;
+use services::mfa::Enrollment;
 use controllers::user::UserPayload;
 
 use tests::helpers;
@@ -49,7 +58,7 @@ fn test_get_users() {
     run_test!(&rocket, req, |mut response: Response| {
         let body = body_string!(response);
         let users: Vec<User> = serde_json::from_str(&body).unwrap();
-        assert_eq!(users.len(), 2);
+        assert_eq!(users.len(), 3);
         assert_eq!(users[0].id, test_user.id);
         assert_eq!(users[0].username, test_user.username);
         assert_eq!(users[0].email, test_user.email);
@@ -118,6 +127,10 @@ fn test_create_user() {
         let err: Error = serde_json::from_str(&body).unwrap();
         assert_eq!(err.code, Status::BadRequest.code);
         assert_eq!(err.msg, "duplicate username");
+        assert_eq!(err.kind, ErrorKind::Duplicate);
+        // the request id is returned both in the body and as header
+        let request_id = response.headers().get_one("X-Request-Id").unwrap().to_string();
+        assert_eq!(err.request_id, Some(request_id));
     });
 
     // duplicate email
@@ -130,6 +143,20 @@ fn test_create_user() {
         assert_eq!(err.code, Status::BadRequest.code);
         assert_eq!(err.msg, "duplicate email");
     });
+
+    // invalid fields
+    new_user.username = "a_very_long_username".to_string();
+    new_user.email = "not an email".to_string();
+    let req = create_user_req!(new_user);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::UnprocessableEntity.code);
+        assert_eq!(err.msg, "validation failed");
+        assert!(err.fields.contains_key("username"));
+        assert!(err.fields.contains_key("email"));
+        assert!(!err.fields.contains_key("password"));
+    });
 }
 
 
@@ -167,6 +194,65 @@ fn test_get_user_by_id() {
     });
 }
 
+#[test]
+fn test_profile() {
+    let testdata::Data {
+        user: test_user,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let endpoint = format!("/users/{}/profile", test_user.id);
+    let rocket = rocket();
+
+    let mut req = MockRequest::new(Put, &endpoint)
+        .header(ContentType::Form)
+        .body("display_name=Test User&avatar_url=ftp://example.com/avatar.png");
+    req.add_header(normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    });
+    let mut req = MockRequest::new(Put, &endpoint)
+        .header(ContentType::Form)
+        .body("avatar_url=http://example.com/avatar.png");
+    req.add_header(normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert!(body.contains("must be an https url"));
+    });
+    let mut req = MockRequest::new(Put, &endpoint)
+        .header(ContentType::Form)
+        .body("display_name=Test User");
+    req.add_header(normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let mut req = MockRequest::new(Put, &endpoint)
+        .header(ContentType::Form)
+        .body("display_name=Test User&bio=runbooks");
+    req.add_header(normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    // readable by anyone, without email
+    run_test!(&rocket,
+              MockRequest::new(Get, &endpoint),
+              |mut response: Response| {
+        let body = body_string!(response);
+        assert!(!body.contains(&test_user.email));
+        let profile: user_serv::Profile = serde_json::from_str(&body).unwrap();
+        assert_eq!(profile.username, test_user.username);
+        assert_eq!(profile.display_name, Some("Test User".to_string()));
+        assert_eq!(profile.bio, Some("runbooks".to_string()));
+        assert!(profile.created_at.is_some());
+    });
+
+    run_test!(&rocket,
+              MockRequest::new(Get, "/users/-1/profile"),
+              |response: Response| { assert_eq!(response.status(), Status::NotFound); });
+}
+
 macro_rules! update_user_req {
     ($updated_user: expr, $user_id: expr, $header: expr) => ({
         let mut req = MockRequest::new(Put, format!("/users/{}", $user_id))
@@ -201,6 +287,18 @@ fn test_update_user_by_id() {
         assert_eq!(user.email, updated_user.1);
     });
 
+    // the password is checked against the current username when it is kept
+    let mut req = MockRequest::new(Put, format!("/users/{}", test_user.id))
+        .header(ContentType::Form)
+        .body("password=Update_User&confirm_password=Update_User");
+    req.add_header(normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::UnprocessableEntity.code);
+        assert!(body.contains("must not be the same as username"));
+    });
+
     let dummy_header = Header::new("dummy", "dummy");
     trivial_token_tests!(&rocket,
                          update_user_req!(updated_user, test_user.id, dummy_header.clone()));
@@ -220,6 +318,7 @@ fn test_delete_user_by_id() {
     let testdata::Data {
         user_alt: test_user,
         admin_header,
+        normal_header: other_header,
         normal_header_alt: normal_header,
         ..
     } = testdata::recreate();
@@ -234,13 +333,187 @@ fn test_delete_user_by_id() {
         assert!(body.contains("1"));
     });
 
+    // tokens of the deleted account are refused right away
+    let req = req!(Get, "/users/me", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Unauthorized.code);
+        assert_eq!(err.kind, ErrorKind::AccountDeleted);
+    });
+
     trivial_token_tests!(&rocket, MockRequest::new(Delete, &endpoint));
 
     endpoint = format!("/users/{}", -1);
-    let normal_req = req!(Delete, &endpoint, normal_header.clone());
+    let normal_req = req!(Delete, &endpoint, other_header.clone());
     let admin_req = req!(Delete, &endpoint, admin_header.clone());
     trivial_perm_tests!(&rocket, normal_req, admin_req, |mut response: Response| {
         let body = body_string!(response);
         assert!(body.contains("0"));
     });
 }
+
+#[test]
+fn test_delete_user_with_pastes() {
+    let testdata::Data {
+        user: test_user,
+        paste: test_paste,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let endpoint = format!("/users/{}", test_user.id);
+    let rocket = rocket();
+
+    // restrict by default
+    let req = req!(Delete, &endpoint, normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Conflict.code);
+        assert_eq!(err.msg, "user still owns pastes");
+        assert_eq!(err.details.unwrap()["pastes"], json!([test_paste.id]));
+    });
+
+    let req = req!(Delete, format!("{}?mode=wipe", endpoint), normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::BadRequest.code);
+        assert_eq!(err.msg, "invalid delete mode wipe");
+    });
+
+    let req = req!(Delete, format!("{}?mode=reassign", endpoint), normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert_eq!(body, "1");
+    });
+
+    let conn: &PgConnection = &DB_POOL.get().unwrap();
+    let ghost = user_serv::get_ghost_user(conn).unwrap();
+    assert_eq!(user_serv::get_delete_blockers(ghost.id, conn), Ok(vec![test_paste.id]));
+}
+
+#[test]
+fn test_unlock_user_by_id() {
+    let testdata::Data {
+        user: test_user,
+        admin_header,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let endpoint = format!("/users/{}/unlock", test_user.id);
+    let rocket = rocket();
+
+    {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        for _ in 0..throttle::MAX_FAILED_ATTEMPTS {
+            user_serv::record_login_failure(test_user.id, conn).unwrap();
+        }
+    }
+
+    let req = req!(Post, &endpoint, admin_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        // lockout state is not for clients
+        assert!(!body.contains("failed_attempts"));
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(user.id, test_user.id);
+    });
+    {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user = user_serv::get_user_by_id(test_user.id, conn).unwrap();
+        assert_eq!(user.failed_attempts, 0);
+        assert_eq!(user.locked_until, None);
+    }
+
+    trivial_token_tests!(&rocket, MockRequest::new(Post, &endpoint));
+
+    // normal user token
+    let req = req!(Post, &endpoint, normal_header);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Forbidden.code);
+        assert_eq!(err.msg, "permission denied");
+    });
+}
+
+#[test]
+fn test_enroll_mfa() {
+    let testdata::Data {
+        user: test_user,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+
+    let req = req!(Post, "/users/me/2fa", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let enrollment: Enrollment = serde_json::from_str(&body).unwrap();
+        assert!(enrollment.otpauth_uri.contains(&test_user.username));
+        assert!(enrollment.otpauth_uri.contains(&enrollment.secret));
+        assert_eq!(enrollment.recovery_codes.len(), 10);
+    });
+
+    let code = {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        let user = user_serv::get_user_by_id(test_user.id, conn).unwrap();
+        totp::generate(user.totp_secret.as_ref().unwrap())
+    };
+    let mut req = MockRequest::new(Post, "/users/me/2fa/confirm")
+        .header(ContentType::Form)
+        .body(&format!("code={}", code));
+    req.add_header(normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(user.totp_enabled, true);
+    });
+
+    // enrolling again would replace the active secret
+    let req = req!(Post, "/users/me/2fa", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::BadRequest.code);
+        assert_eq!(err.msg, "2fa already enabled");
+    });
+
+    trivial_token_tests!(&rocket, MockRequest::new(Post, "/users/me/2fa"));
+}
+
+#[test]
+fn test_reset_mfa_by_id() {
+    let testdata::Data {
+        user: test_user,
+        admin_header,
+        normal_header,
+        ..
+    } = testdata::recreate();
+    let endpoint = format!("/users/{}/2fa", test_user.id);
+    let rocket = rocket();
+
+    {
+        let conn: &PgConnection = &DB_POOL.get().unwrap();
+        mfa_serv::enroll(test_user.id, conn).unwrap();
+    }
+
+    let req = req!(Delete, &endpoint, admin_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let user: User = serde_json::from_str(&body).unwrap();
+        assert_eq!(user.totp_enabled, false);
+    });
+
+    trivial_token_tests!(&rocket, MockRequest::new(Delete, &endpoint));
+
+    // normal user token
+    let req = req!(Delete, &endpoint, normal_header);
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::Forbidden.code);
+        assert_eq!(err.msg, "permission denied");
+    });
+}
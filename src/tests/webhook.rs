@@ -0,0 +1,127 @@
+use rocket;
+use rocket::testing::MockRequest;
+use rocket::http::Method::*;
+use rocket::http::{Status, Header, ContentType};
+use rocket::Response;
+
+use serde_json;
+
+use helpers::error::Error;
+
+use models::webhook::{Webhook, WebhookDelivery};
+use services::webhook::CreatedWebhook;
+
+use tests::helpers;
+use self::helpers::testdata;
+
+macro_rules! form_req {
+    ($method: expr, $endpoint: expr, $body: expr, $header: expr) => ({
+        let mut req = MockRequest::new($method, $endpoint)
+            .header(ContentType::Form)
+            .body(&$body);
+        req.add_header($header);
+        req
+    })
+}
+
+#[test]
+fn test_webhook_deliveries() {
+    let testdata::Data {
+        user,
+        normal_header,
+        normal_header_alt,
+        ..
+    } = testdata::recreate();
+    let rocket = rocket();
+    let url = "url=http://203.0.113.7:1/hook";
+
+    let req = form_req!(Post,
+                        "/webhooks",
+                        "url=http://127.0.0.1:1/hook&events=paste.created",
+                        normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.code, Status::UnprocessableEntity.code);
+        assert!(body.contains("private address"));
+    });
+
+    let req = form_req!(Post,
+                        "/webhooks",
+                        format!("{}&events=paste.created,paste.starred", url),
+                        normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let err: Error = serde_json::from_str(&body).unwrap();
+        assert_eq!(err.msg, "invalid event paste.starred");
+    });
+    let req = form_req!(Post,
+                        "/webhooks",
+                        format!("{}&events=user.created", url),
+                        normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::BadRequest);
+    });
+    let req = form_req!(Post,
+                        "/admin/webhooks",
+                        format!("{}&events=user.created", url),
+                        normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+
+    let req = form_req!(Post,
+                        "/webhooks",
+                        format!("{}&events=paste.created,paste.deleted", url),
+                        normal_header.clone());
+    let mut response = req.dispatch_with(&rocket);
+    let body = body_string!(response);
+    let created: CreatedWebhook = serde_json::from_str(&body).unwrap();
+    assert!(!created.secret.is_empty());
+    // the secret is only returned once
+    let req = req!(Get, "/users/me/webhooks", normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert!(!body.contains(&created.secret));
+        let webhooks: Vec<Webhook> = serde_json::from_str(&body).unwrap();
+        assert_eq!(webhooks.len(), 1);
+    });
+
+    let req = form_req!(Post,
+                        "/pastes",
+                        format!("user_id={}&data=deploy notes", user.id),
+                        normal_header.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Ok);
+    });
+
+    let deliveries_endpoint = format!("/webhooks/{}/deliveries", created.webhook.id);
+    let req = req!(Get, &deliveries_endpoint, normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        let deliveries: Vec<WebhookDelivery> = serde_json::from_str(&body).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, "paste.created");
+        assert_eq!(deliveries[0].status, "pending");
+        assert!(!deliveries[0].payload.contains("deploy notes"));
+    });
+
+    // only the owner sees and removes a webhook
+    let req = req!(Get, &deliveries_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let webhook_endpoint = format!("/webhooks/{}", created.webhook.id);
+    let req = req!(Delete, &webhook_endpoint, normal_header_alt.clone());
+    run_test!(&rocket, req, |response: Response| {
+        assert_eq!(response.status(), Status::Forbidden);
+    });
+    let req = req!(Delete, &webhook_endpoint, normal_header.clone());
+    run_test!(&rocket, req, |mut response: Response| {
+        let body = body_string!(response);
+        assert_eq!(body, "1");
+    });
+
+    let dummy_header = Header::new("dummy", "dummy");
+    trivial_token_tests!(&rocket, req!(Get, "/users/me/webhooks", dummy_header.clone()));
+}